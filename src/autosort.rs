@@ -0,0 +1,697 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel, Weak};
+use tokio::sync::broadcast;
+
+use crate::config::write_config_sync;
+use crate::diva::open_error_window;
+use crate::divamodarchive::PostType;
+use crate::groups::{current_order, edit_current_order};
+use crate::modmanagement::DivaMod;
+use crate::modpacks::{apply_mod_priority, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::storage::mod_category;
+use crate::tags::{get_tags, parse_tags};
+use crate::util::dir_files;
+use crate::{AutoSortWindow, ModpackLogic, SortPin, SortPreviewRow, WindowLogic, MODS, R4D_CFG};
+
+/// Keeps `above` higher in the priority than `below`, both are dir names
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct PinRule {
+    pub above: String,
+    pub below: String,
+}
+
+/// Saved in rust4diva.toml so the same rules get used next time
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct SortRules {
+    pub plugins_first: bool,
+    pub ui_last: bool,
+    /// mods with a tag earlier in this list go higher up
+    pub tag_order: Vec<String>,
+    pub pins: Vec<PinRule>,
+    /// smaller mods that replace files from a bigger one go above it
+    pub use_conflicts: bool,
+}
+
+impl Default for SortRules {
+    fn default() -> Self {
+        Self {
+            plugins_first: true,
+            ui_last: true,
+            tag_order: vec![],
+            pins: vec![],
+            use_conflicts: false,
+        }
+    }
+}
+
+/// Two mods shipping some of the same files, `winner` is the one that should load first
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub winner: String,
+    pub loser: String,
+    pub files: usize,
+}
+
+/// Finds mods that ship the same files. Only stuff in subfolders counts, every mod has its own
+/// config.toml and readme at the top
+pub fn file_conflicts(mods: &HashMap<String, DivaMod>) -> Vec<Conflict> {
+    let mut owners: HashMap<String, Vec<String>> = HashMap::new();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for (dir, module) in mods.iter() {
+        let mut mod_dir = PathBuf::from(module.path.clone());
+        mod_dir.pop();
+        let files = dir_files(&mod_dir);
+        counts.insert(dir.clone(), files.len());
+        for (rel, _) in files {
+            let rel = rel.to_string_lossy().replace('\\', "/").to_lowercase();
+            if rel.contains('/') {
+                owners.entry(rel).or_default().push(dir.clone());
+            }
+        }
+    }
+    let mut pairs: HashMap<(String, String), usize> = HashMap::new();
+    for dirs in owners.values().filter(|d| d.len() > 1) {
+        for (i, a) in dirs.iter().enumerate() {
+            for b in dirs[i + 1..].iter() {
+                let key = match a < b {
+                    true => (a.clone(), b.clone()),
+                    false => (b.clone(), a.clone()),
+                };
+                *pairs.entry(key).or_default() += 1;
+            }
+        }
+    }
+    pairs
+        .into_iter()
+        .map(|((a, b), files)| {
+            // the smaller one is most likely a patch for the bigger one
+            let (winner, loser) = match counts.get(&a) <= counts.get(&b) {
+                true => (a, b),
+                false => (b, a),
+            };
+            Conflict {
+                winner,
+                loser,
+                files,
+            }
+        })
+        .collect()
+}
+
+fn mod_name(mods: &HashMap<String, DivaMod>, dir: &str) -> String {
+    match mods.get(dir) {
+        Some(m) => m.config["name"].as_str().unwrap_or(dir).to_string(),
+        None => dir.to_string(),
+    }
+}
+
+/// Sorts one run of mods between two separators
+fn sort_group(
+    group: &[ModPackMod],
+    rules: &SortRules,
+    mods: &HashMap<String, DivaMod>,
+    conflicts: &[Conflict],
+    reasons: &mut HashMap<String, Vec<String>>,
+) -> Vec<ModPackMod> {
+    let dirs: Vec<String> = group
+        .iter()
+        .map(|m| m.dir_name().unwrap_or(m.name.clone()))
+        .collect();
+
+    let mut keys = vec![];
+    for dir in dirs.iter() {
+        let why = reasons.entry(dir.clone()).or_default();
+        let mut category = 1;
+        if let Some(module) = mods.get(dir) {
+            match mod_category(module) {
+                PostType::Plugin if rules.plugins_first => {
+                    category = 0;
+                    why.push("plugin".to_owned());
+                }
+                PostType::Ui if rules.ui_last => {
+                    category = 2;
+                    why.push("UI".to_owned());
+                }
+                _ => {}
+            }
+        }
+        let tags = get_tags(dir);
+        let tag = rules
+            .tag_order
+            .iter()
+            .position(|t| tags.contains(t))
+            .unwrap_or(rules.tag_order.len());
+        if let Some(t) = rules.tag_order.get(tag) {
+            why.push(format!("tag: {t}"));
+        }
+        keys.push((category, tag));
+    }
+    let mut order: Vec<usize> = (0..group.len()).collect();
+    order.sort_by_key(|i| keys[*i]);
+    let rank: Vec<usize> = {
+        let mut rank = vec![0; group.len()];
+        for (pos, i) in order.iter().enumerate() {
+            rank[*i] = pos;
+        }
+        rank
+    };
+
+    // what has to go above what, pins win over conflicts
+    let idx = |dir: &String| dirs.iter().position(|d| d == dir);
+    let mut edges: Vec<(usize, usize)> = vec![];
+    for pin in rules.pins.iter() {
+        if let (Some(a), Some(b)) = (idx(&pin.above), idx(&pin.below)) {
+            edges.push((a, b));
+            let below = mod_name(mods, &pin.below);
+            reasons
+                .entry(pin.above.clone())
+                .or_default()
+                .push(format!("pinned above {below}"));
+        }
+    }
+    for conflict in conflicts.iter() {
+        if let (Some(a), Some(b)) = (idx(&conflict.winner), idx(&conflict.loser)) {
+            if !edges.contains(&(b, a)) {
+                edges.push((a, b));
+                let loser = mod_name(mods, &conflict.loser);
+                reasons
+                    .entry(conflict.winner.clone())
+                    .or_default()
+                    .push(format!("replaces {} files from {loser}", conflict.files));
+            }
+        }
+    }
+
+    // topological sort that sticks as close as it can to the rule order, anything left in a
+    // cycle just goes in rule order
+    let mut incoming = vec![0; group.len()];
+    for (_, b) in edges.iter() {
+        incoming[*b] += 1;
+    }
+    let mut done = vec![false; group.len()];
+    let mut sorted = vec![];
+    while sorted.len() < group.len() {
+        let next = order
+            .iter()
+            .copied()
+            .filter(|i| !done[*i] && incoming[*i] == 0)
+            .min_by_key(|i| rank[*i])
+            .or_else(|| order.iter().copied().find(|i| !done[*i]))
+            .unwrap();
+        done[next] = true;
+        for (a, b) in edges.iter() {
+            if *a == next && !done[*b] && incoming[*b] > 0 {
+                incoming[*b] -= 1;
+            }
+        }
+        sorted.push(group[next].clone());
+    }
+    sorted
+}
+
+/// New order for a load order list, separators stay where they are and groups get sorted on
+/// their own. Also hands back why each mod ended up where it did
+pub fn auto_sort(
+    list: &[ModPackMod],
+    rules: &SortRules,
+    mods: &HashMap<String, DivaMod>,
+    conflicts: &[Conflict],
+) -> (Vec<ModPackMod>, HashMap<String, Vec<String>>) {
+    let mut reasons = HashMap::new();
+    let mut sorted = vec![];
+    let mut start = 0;
+    while start < list.len() {
+        if list[start].separator {
+            sorted.push(list[start].clone());
+            start += 1;
+        }
+        let end = (start..list.len())
+            .find(|i| list[*i].separator)
+            .unwrap_or(list.len());
+        sorted.extend(sort_group(
+            &list[start..end],
+            rules,
+            mods,
+            conflicts,
+            &mut reasons,
+        ));
+        start = end;
+    }
+    (sorted, reasons)
+}
+
+fn preview_rows(
+    old: &[ModPackMod],
+    new: &[ModPackMod],
+    reasons: &HashMap<String, Vec<String>>,
+) -> Vec<(String, i32, i32, String, bool)> {
+    let key = |m: &ModPackMod| match m.separator {
+        true => format!("separator:{}", m.name),
+        false => m.dir_name().unwrap_or(m.name.clone()),
+    };
+    let old_keys: Vec<String> = old.iter().map(key).collect();
+    let mods = MODS.try_lock().map(|m| m.clone()).unwrap_or_default();
+    new.iter()
+        .enumerate()
+        .map(|(pos, m)| {
+            let k = key(m);
+            let was = old_keys.iter().position(|o| *o == k).unwrap_or(pos);
+            let name = match m.separator {
+                true => m.name.clone(),
+                false => mod_name(&mods, &k),
+            };
+            let why = reasons.get(&k).map(|r| r.join(", ")).unwrap_or_default();
+            (name, was as i32 + 1, pos as i32 + 1, why, m.separator)
+        })
+        .collect()
+}
+
+fn rules_from_ui(window: &AutoSortWindow) -> SortRules {
+    SortRules {
+        plugins_first: window.get_plugins_first(),
+        ui_last: window.get_ui_last(),
+        tag_order: parse_tags(&window.get_tag_order()),
+        pins: window
+            .get_pins()
+            .iter()
+            .map(|p| PinRule {
+                above: p.above.to_string(),
+                below: p.below.to_string(),
+            })
+            .collect(),
+        use_conflicts: window.get_use_conflicts(),
+    }
+}
+
+type ConflictCache = Arc<Mutex<Option<Vec<Conflict>>>>;
+
+fn conflicts_for(
+    rules: &SortRules,
+    mods: &HashMap<String, DivaMod>,
+    cache: &ConflictCache,
+) -> Vec<Conflict> {
+    if !rules.use_conflicts {
+        return vec![];
+    }
+    if let Ok(mut cache) = cache.lock() {
+        if cache.is_none() {
+            *cache = Some(file_conflicts(mods));
+        }
+        return cache.clone().unwrap_or_default();
+    }
+    vec![]
+}
+
+fn show_preview(window: Weak<AutoSortWindow>, rules: SortRules, cache: ConflictCache) {
+    tokio::spawn(async move {
+        let mods = MODS.try_lock().map(|m| m.clone()).unwrap_or_default();
+        let list = current_order();
+        let conflicts = conflicts_for(&rules, &mods, &cache);
+        let (sorted, reasons) = auto_sort(&list, &rules, &mods, &conflicts);
+        let rows = preview_rows(&list, &sorted, &reasons);
+        let moved = rows.iter().filter(|r| r.1 != r.2).count();
+        let _ = window.upgrade_in_event_loop(move |window| {
+            let rows: Vec<SortPreviewRow> = rows
+                .into_iter()
+                .map(|(name, old, new, reason, separator)| SortPreviewRow {
+                    name: name.into(),
+                    old,
+                    new,
+                    reason: reason.into(),
+                    separator,
+                })
+                .collect();
+            window.set_rows(ModelRc::new(VecModel::from(rows)));
+            window.set_moved(moved as i32);
+            window.set_loading(false);
+        });
+    });
+}
+
+fn save_rules(rules: &SortRules) {
+    if let Ok(mut cfg) = R4D_CFG.try_lock() {
+        cfg.sort_rules = rules.clone();
+        if let Err(e) = write_config_sync(cfg.clone()) {
+            eprintln!("{e}");
+        }
+    }
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_auto_sort(move || {
+        let ui = weak.unwrap();
+        let window = match AutoSortWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+
+        let rules = match R4D_CFG.try_lock() {
+            Ok(cfg) => cfg.sort_rules.clone(),
+            Err(_) => SortRules::default(),
+        };
+        let pack = ui.get_active_pack().to_string();
+        window.set_pack(match pack.as_str() {
+            "" => "All Mods".into(),
+            _ => pack.into(),
+        });
+
+        // everything that can be pinned, by name for the dropdowns
+        let mods = MODS.try_lock().map(|m| m.clone()).unwrap_or_default();
+        let mut choices: Vec<(String, String)> = current_order()
+            .iter()
+            .filter_map(|m| m.dir_name())
+            .map(|dir| (mod_name(&mods, &dir), dir))
+            .collect();
+        choices.sort_by_key(|(name, _)| name.to_lowercase());
+        let names: Vec<SharedString> = choices.iter().map(|(n, _)| n.into()).collect();
+        let dirs: Vec<SharedString> = choices.iter().map(|(_, d)| d.into()).collect();
+        window.set_mod_names(ModelRc::new(VecModel::from(names)));
+        window.set_mod_dirs(ModelRc::new(VecModel::from(dirs)));
+
+        window.set_plugins_first(rules.plugins_first);
+        window.set_ui_last(rules.ui_last);
+        window.set_use_conflicts(rules.use_conflicts);
+        window.set_tag_order(rules.tag_order.join(", ").into());
+        let pins: Vec<SortPin> = rules
+            .pins
+            .iter()
+            .map(|p| SortPin {
+                above: p.above.clone().into(),
+                below: p.below.clone().into(),
+                above_name: mod_name(&mods, &p.above).into(),
+                below_name: mod_name(&mods, &p.below).into(),
+            })
+            .collect();
+        window.set_pins(ModelRc::new(VecModel::from(pins)));
+
+        let cache: ConflictCache = Arc::new(Mutex::new(None));
+        window.set_loading(true);
+        show_preview(window.as_weak(), rules, cache.clone());
+
+        let window_weak = window.as_weak();
+        let preview_cache = cache.clone();
+        window.on_rules_changed(move || {
+            let window = window_weak.unwrap();
+            let rules = rules_from_ui(&window);
+            save_rules(&rules);
+            window.set_loading(true);
+            show_preview(window_weak.clone(), rules, preview_cache.clone());
+        });
+
+        let window_weak = window.as_weak();
+        window.on_add_pin(move |above, below| {
+            let window = window_weak.unwrap();
+            let (dirs, names) = (window.get_mod_dirs(), window.get_mod_names());
+            let (Some(above_dir), Some(below_dir)) =
+                (dirs.row_data(above as usize), dirs.row_data(below as usize))
+            else {
+                return;
+            };
+            if above_dir == below_dir {
+                return;
+            }
+            // a new pin replaces one between the same two mods
+            let mut pins: Vec<SortPin> = window
+                .get_pins()
+                .iter()
+                .filter(|p| {
+                    let same = p.above == above_dir && p.below == below_dir;
+                    let flipped = p.above == below_dir && p.below == above_dir;
+                    !(same || flipped)
+                })
+                .collect();
+            pins.push(SortPin {
+                above: above_dir,
+                below: below_dir,
+                above_name: names.row_data(above as usize).unwrap_or_default(),
+                below_name: names.row_data(below as usize).unwrap_or_default(),
+            });
+            window.set_pins(ModelRc::new(VecModel::from(pins)));
+            window.invoke_rules_changed();
+        });
+
+        let window_weak = window.as_weak();
+        window.on_remove_pin(move |idx| {
+            let window = window_weak.unwrap();
+            let mut pins: Vec<SortPin> = window.get_pins().iter().collect();
+            if (idx as usize) < pins.len() {
+                pins.remove(idx as usize);
+            }
+            window.set_pins(ModelRc::new(VecModel::from(pins)));
+            window.invoke_rules_changed();
+        });
+
+        let window_weak = window.as_weak();
+        let main_weak = weak.clone();
+        let apply_cache = cache.clone();
+        window.on_apply(move || {
+            let window = window_weak.unwrap();
+            let rules = rules_from_ui(&window);
+            save_rules(&rules);
+            window.set_loading(true);
+            let window_weak = window_weak.clone();
+            let main_weak = main_weak.clone();
+            let cache = apply_cache.clone();
+            tokio::spawn(async move {
+                // sort again from what's there now in case it changed since the preview
+                let mods = MODS.try_lock().map(|m| m.clone()).unwrap_or_default();
+                let conflicts = conflicts_for(&rules, &mods, &cache);
+                let result = edit_current_order(Some("Auto sort"), |list| {
+                    *list = auto_sort(list, &rules, &mods, &conflicts).0;
+                });
+                let applied = match result {
+                    Ok(applied) => applied,
+                    Err(e) => {
+                        open_error_window(format!("Unable to save the new order: \n{e}"));
+                        let _ = window_weak.upgrade_in_event_loop(|w| w.set_loading(false));
+                        return;
+                    }
+                };
+                if let Err(e) = apply_mod_priority().await {
+                    eprintln!("{e}");
+                    open_error_window(format!("Unable to apply the new order: \n{e}"));
+                }
+                let _ = main_weak.upgrade_in_event_loop(move |ui| {
+                    ui.global::<ModpackLogic>()
+                        .invoke_change_modpack(applied.into());
+                });
+                let _ = window_weak.upgrade_in_event_loop(|w| {
+                    w.hide().unwrap();
+                });
+            });
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::new_separator;
+    use crate::metadata::ModMeta;
+    use crate::MOD_META;
+    use std::fs;
+
+    fn entry(dir: &str) -> ModPackMod {
+        ModPackMod {
+            name: dir.to_owned(),
+            enabled: true,
+            path: String::new(),
+            separator: false,
+            collapsed: false,
+            id: String::new(),
+        }
+    }
+
+    /// A mod in a temp folder holding `files`, a dll in the config makes it a plugin
+    fn test_mod(dir: &str, dll: bool, files: &[&str]) -> DivaMod {
+        let mod_dir = std::env::temp_dir()
+            .join(format!("rust4diva-autosort-{}", std::process::id()))
+            .join(dir);
+        for file in files {
+            let path = mod_dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        let dll = match dll {
+            true => "[\"plugin.dll\"]",
+            false => "[]",
+        };
+        DivaMod {
+            path: mod_dir.join("config.toml").display().to_string(),
+            config: format!("name = \"{dir}\"\nenabled = true\ndll = {dll}")
+                .parse()
+                .unwrap(),
+            size: 0,
+            file_count: files.len(),
+            id: String::new(),
+        }
+    }
+
+    fn mods() -> HashMap<String, DivaMod> {
+        [
+            ("plug", test_mod("plug", true, &[])),
+            ("ui", test_mod("ui", false, &["rom/2d/spr_ui.farc"])),
+            ("song", test_mod("song", false, &["rom/pv_db.txt"])),
+            ("patch", test_mod("patch", false, &[])),
+            ("big", test_mod("big", false, &[])),
+        ]
+        .into_iter()
+        .map(|(dir, m)| (dir.to_owned(), m))
+        .collect()
+    }
+
+    fn sorted(list: &[&str], rules: &SortRules, conflicts: &[Conflict]) -> Vec<String> {
+        let list: Vec<ModPackMod> = list
+            .iter()
+            .map(|name| match name.starts_with(char::is_uppercase) {
+                true => new_separator(name),
+                false => entry(name),
+            })
+            .collect();
+        auto_sort(&list, rules, &mods(), conflicts)
+            .0
+            .into_iter()
+            .map(|m| m.name)
+            .collect()
+    }
+
+    fn no_rules() -> SortRules {
+        SortRules {
+            plugins_first: false,
+            ui_last: false,
+            ..Default::default()
+        }
+    }
+
+    fn pin(above: &str, below: &str) -> PinRule {
+        PinRule {
+            above: above.to_owned(),
+            below: below.to_owned(),
+        }
+    }
+
+    #[test]
+    fn plugins_first_ui_last() {
+        let list = ["ui", "song", "plug"];
+        assert_eq!(
+            sorted(&list, &SortRules::default(), &[]),
+            ["plug", "song", "ui"]
+        );
+        // without rules nothing moves
+        assert_eq!(sorted(&list, &no_rules(), &[]), list);
+    }
+
+    #[test]
+    fn groups_sort_on_their_own() {
+        assert_eq!(
+            sorted(
+                &["ui", "plug", "Songs", "song", "Ui", "ui"],
+                &SortRules::default(),
+                &[]
+            ),
+            ["plug", "ui", "Songs", "song", "Ui", "ui"]
+        );
+    }
+
+    #[test]
+    fn tag_order() {
+        if let Ok(mut meta) = MOD_META.lock() {
+            for (dir, tag) in [("autosort-first", "first"), ("autosort-second", "second")] {
+                meta.insert(
+                    dir.to_owned(),
+                    ModMeta {
+                        tags: vec![tag.to_owned()],
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        let rules = SortRules {
+            tag_order: vec!["first".to_owned(), "second".to_owned()],
+            ..no_rules()
+        };
+        assert_eq!(
+            sorted(&["big", "autosort-second", "autosort-first"], &rules, &[]),
+            ["autosort-first", "autosort-second", "big"]
+        );
+    }
+
+    #[test]
+    fn pins_beat_categories() {
+        let rules = SortRules {
+            pins: vec![pin("song", "plug")],
+            ..Default::default()
+        };
+        assert_eq!(sorted(&["plug", "song"], &rules, &[]), ["song", "plug"]);
+
+        let list: Vec<ModPackMod> = ["plug", "song"].into_iter().map(entry).collect();
+        let (_, reasons) = auto_sort(&list, &rules, &mods(), &[]);
+        assert!(reasons["song"].contains(&"pinned above plug".to_owned()));
+        assert!(reasons["plug"].contains(&"plugin".to_owned()));
+    }
+
+    #[test]
+    fn conflicts() {
+        let conflict = [Conflict {
+            winner: "patch".to_owned(),
+            loser: "big".to_owned(),
+            files: 3,
+        }];
+        assert_eq!(
+            sorted(&["big", "patch"], &no_rules(), &conflict),
+            ["patch", "big"]
+        );
+        // a pin the other way round wins
+        let rules = SortRules {
+            pins: vec![pin("big", "patch")],
+            ..no_rules()
+        };
+        assert_eq!(
+            sorted(&["big", "patch"], &rules, &conflict),
+            ["big", "patch"]
+        );
+    }
+
+    #[test]
+    fn cycles_fall_back_to_rule_order() {
+        let rules = SortRules {
+            pins: vec![pin("song", "plug"), pin("plug", "song")],
+            ..Default::default()
+        };
+        assert_eq!(sorted(&["song", "plug"], &rules, &[]), ["plug", "song"]);
+    }
+}
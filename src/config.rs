@@ -10,16 +10,20 @@ use slint::{CloseRequestResponse, ComponentHandle, Model, ModelRc, SharedString,
 use tokio::fs;
 use tokio::sync::broadcast::Sender;
 
+use crate::autosort::SortRules;
 use crate::diva::{
     find_diva_folder, get_config_dir_sync, get_diva_folder, get_steam_folder, open_error_window,
 };
-use crate::modmanagement::{get_mods, load_mods, set_mods_table, DivaModLoader};
+use crate::modmanagement::{
+    get_mods, get_mods_folder, load_mods, move_mods_folder, set_mods_table, DivaModLoader,
+};
 use crate::modpacks::{load_mod_packs, ModPackMod};
 use crate::slint_generatedApp::App;
 use crate::{DML_CFG, MOD_PACKS};
 
 use crate::{
-    diva::get_config_dir, DivaLogic, LangTL, SettingsLogic, SettingsWindow, WindowLogic, R4D_CFG,
+    diva::get_config_dir, DivaLogic, LangTL, ModpackLogic, SettingsLogic, SettingsWindow,
+    WindowLogic, R4D_CFG,
 };
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -77,6 +81,11 @@ pub struct DivaConfig {
     pub lang: i32,
     #[serde(default)]
     pub use_dirname: bool,
+    #[serde(default)]
+    pub sort_rules: SortRules,
+    /// shared mod store that gets linked into every install, empty when it isn't used
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub library_dir: String,
 }
 impl DivaConfig {
     pub fn new() -> Self {
@@ -96,6 +105,8 @@ impl DivaConfig {
             scale: 1.0,
             lang: 1,
             use_dirname: false,
+            sort_rules: SortRules::default(),
+            library_dir: "".to_owned(),
         }
     }
 }
@@ -112,6 +123,9 @@ impl From<OldDivaConfig> for DivaConfig {
                     name: "".to_owned(),
                     enabled: true,
                     path: buf.to_str().unwrap().to_owned(),
+                    separator: false,
+                    collapsed: false,
+                    id: String::new(),
                 });
             }
         }
@@ -132,6 +146,8 @@ impl From<OldDivaConfig> for DivaConfig {
             scale: 1.0,
             lang: 0,
             use_dirname: false,
+            sort_rules: SortRules::default(),
+            library_dir: "".to_owned(),
         }
     }
 }
@@ -282,6 +298,9 @@ pub async fn init_ui(diva_ui: &App, dark_tx: Sender<ColorScheme>) {
 
                 settings.set_steam_dir(steam_dir.into());
                 settings.set_diva_dir(diva_dir.into());
+                if let Some(mods_dir) = get_mods_folder() {
+                    settings.set_mods_dir(mods_dir.display().to_string().into());
+                }
                 settings.invoke_set_color_scheme(current_scheme);
 
                 if let Ok(cfg) = R4D_CFG.try_lock() {
@@ -348,6 +367,58 @@ pub async fn init_ui(diva_ui: &App, dark_tx: Sender<ColorScheme>) {
                         });
                     });
 
+                let move_handle = settings.as_weak();
+                let move_main_handle = main_ui_handle.clone();
+                settings
+                    .global::<SettingsLogic>()
+                    .on_move_mods_folder(move |current| {
+                        let move_handle = move_handle.clone();
+                        let main_handle = move_main_handle.clone();
+                        let mut start = PathBuf::from(current.to_string());
+                        start.pop();
+                        let picker = AsyncFileDialog::new()
+                            .set_title("Pick an empty folder for the mods")
+                            .set_directory(start);
+                        tokio::spawn(async move {
+                            let Some(dir) = picker.pick_folder().await else {
+                                return;
+                            };
+                            let _ =
+                                move_handle.upgrade_in_event_loop(|ui| ui.set_moving_mods(true));
+                            let dest = dir.path().to_path_buf();
+                            // copying to another drive takes a while
+                            let res = tokio::task::spawn_blocking(move || {
+                                move_mods_folder(dest).map_err(|e| e.to_string())
+                            })
+                            .await;
+                            let res = match res {
+                                Ok(res) => res,
+                                Err(e) => Err(e.to_string()),
+                            };
+                            let moved = res.clone().ok();
+                            let _ = move_handle.upgrade_in_event_loop(move |ui| {
+                                ui.set_moving_mods(false);
+                                if let Some(dest) = moved {
+                                    ui.set_mods_dir(dest.display().to_string().into());
+                                }
+                            });
+                            match res {
+                                Ok(_) => {
+                                    let _ = set_mods_table(&get_mods(), main_handle.clone());
+                                    let _ = main_handle.upgrade_in_event_loop(|ui| {
+                                        let applied = ui.get_active_pack();
+                                        ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+                                    });
+                                }
+                                Err(e) => {
+                                    open_error_window(format!(
+                                        "Unable to move the mods folder: \n{e}"
+                                    ));
+                                }
+                            }
+                        });
+                    });
+
                 let diva_dir_handle = settings.as_weak();
 
                 settings
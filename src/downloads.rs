@@ -1,5 +1,11 @@
-use std::{error::Error, fs::File, io::Write, path::PathBuf};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
+use filenamify::filenamify;
 use futures_util::StreamExt;
 use slint::{ComponentHandle, Model, ModelRc, Rgba8Pixel, SharedPixelBuffer, VecModel, Weak};
 use tokio::{
@@ -9,6 +15,7 @@ use tokio::{
 
 use crate::{
     diva::{get_temp_folder, open_error_window},
+    metadata::{self, ModSource},
     modmanagement::{get_mods, load_mods, set_mods_table, unpack_mod_path},
     util::reqwest_client,
     App, Download, DownloadLogic, GameBananaLogic, HyperLink, SearchDetailsWindow,
@@ -55,6 +62,7 @@ pub fn create_deets_window(
         });
     }
     let item_id = item.id.clone();
+    let source = ModSource::from_preview(&item, String::new());
 
     deets
         .global::<HyperLink>()
@@ -126,6 +134,8 @@ pub fn create_deets_window(
                 None => return,
             };
             if let Some(idx) = files.iter().position(|i| i.id == download.id) {
+                let mut source = source.clone();
+                source.file_id = download.id.to_string();
                 let deets_weak = deets_weak.clone();
                 let (tx, mut rx) = channel::<(usize, u64)>(30000);
                 let row = idx.clone();
@@ -150,59 +160,25 @@ pub fn create_deets_window(
                 });
 
                 tokio::spawn(async move {
-                    let req = reqwest_client().get(download.url.to_string()).send();
-                    let res = match req.await {
-                        Ok(res) => match res.error_for_status() {
-                            Ok(res) => res,
-                            Err(e) => {
-                                open_error_window(e.to_string());
-                                return;
-                            }
-                        },
-                        Err(e) => {
+                    if let Some(dir) = get_temp_folder() {
+                        let mut buf = PathBuf::from(dir);
+                        buf.push(filenamify(&download.name));
+                        let res = download_file(&download.url, &buf, move |len, size| {
+                            let _ = tx.try_send((len, size));
+                        })
+                        .await;
+                        if let Err(e) = res {
                             open_error_window(e.to_string());
                             return;
                         }
-                    };
-                    println!("{}", res.status());
-                    println!("{}", res.content_length().unwrap_or(0));
-                    let file_size = res.content_length().unwrap_or(0);
-
-                    let mut stream = res.bytes_stream();
-                    let mut bytes = vec![];
-                    let tx = tx;
-                    while let Some(chunk) = stream.next().await {
-                        match chunk {
-                            Ok(chunk) => {
-                                let _ = tx.try_send((chunk.len(), file_size.clone()));
-                                bytes.push(chunk);
-                            }
-                            Err(e) => {
-                                open_error_window(e.to_string());
-                                return;
-                            }
-                        }
-                    }
-                    println!("Done, len: {}", bytes.len());
-                    if let Some(dir) = get_temp_folder() {
-                        let mut buf = PathBuf::from(dir);
-                        buf.push(download.name.to_string());
-                        match File::create(buf.clone()) {
-                            Ok(mut file) => {
-                                for chunk in bytes {
-                                    if let Err(e) = file.write_all(&chunk) {
-                                        open_error_window(e.to_string());
-                                        return;
+                        match unpack_mod_path(buf).await {
+                            Ok(installed) => {
+                                for dir in installed {
+                                    if let Some(dir_name) = dir.file_name() {
+                                        let dir_name = dir_name.to_string_lossy().to_string();
+                                        metadata::record_source(dir_name, source.clone()).await;
                                     }
                                 }
-                            }
-                            Err(e) => {
-                                open_error_window(e.to_string());
-                                return;
-                            }
-                        }
-                        match unpack_mod_path(buf).await {
-                            Ok(_) => {
                                 if load_mods().is_ok() {
                                     match set_mods_table(&get_mods(), weak.clone()) {
                                         Ok(_) => {}
@@ -236,6 +212,38 @@ pub fn create_deets_window(
     deets
 }
 
+/// Streams `url` into `dest` a chunk at a time. `progress` gets every chunk's length and the
+/// total size, which is 0 when the server doesn't say. A failed download doesn't leave half a
+/// file behind
+pub async fn download_file<F: FnMut(usize, u64)>(
+    url: &str,
+    dest: &Path,
+    mut progress: F,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let res = reqwest_client().get(url).send().await?.error_for_status()?;
+    let size = res.content_length().unwrap_or(0);
+    let mut file = File::create(dest)?;
+    let mut stream = res.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let written: Result<usize, Box<dyn Error + Send + Sync>> = match chunk {
+            Ok(chunk) => file
+                .write_all(&chunk)
+                .map(|_| chunk.len())
+                .map_err(|e| e.into()),
+            Err(e) => Err(e.into()),
+        };
+        match written {
+            Ok(len) => progress(len, size),
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(dest);
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
 /// dummy implementation
 pub async fn _get_and_set_preview_image(weak: Weak<App>, item: SearchPreviewData) {
     let mut buffer = missing_image_buf();
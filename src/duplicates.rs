@@ -0,0 +1,577 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::sync::broadcast;
+
+use crate::config::write_config_sync;
+use crate::config::write_dml_config;
+use crate::diva::open_error_window;
+use crate::history::{order_key, record, trash_mod, Change};
+use crate::library::LINK_MARKER;
+use crate::metadata::save_metadata;
+use crate::modmanagement::{get_mods, load_mods, set_mods_table, DivaMod};
+use crate::modpacks::{save_modpack_sync, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::util::{dir_files, format_size};
+use crate::{
+    DuplicateGroup, DuplicateMod, DuplicatesWindow, ModpackLogic, WindowLogic, DML_CFG, MOD_META,
+    MOD_PACKS, R4D_CFG,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateReason {
+    Name,
+    Content,
+    Both,
+}
+
+impl DuplicateReason {
+    fn join(self, other: DuplicateReason) -> DuplicateReason {
+        if self == other {
+            self
+        } else {
+            DuplicateReason::Both
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            DuplicateReason::Name => "Same name",
+            DuplicateReason::Content => "Identical files",
+            DuplicateReason::Both => "Same name and identical files",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Duplicates {
+    pub reason: DuplicateReason,
+    pub mods: Vec<DivaMod>,
+    /// index into `mods` of the copy we suggest keeping
+    pub keep: usize,
+    /// every copy has the same files, otherwise removing the others loses something
+    pub identical: bool,
+}
+
+fn mod_dir(module: &DivaMod) -> PathBuf {
+    let mut dir = PathBuf::from(module.path.clone());
+    dir.pop();
+    dir
+}
+
+fn mod_name(module: &DivaMod) -> String {
+    module.config["name"].as_str().unwrap_or("").to_string()
+}
+
+/// config.toml differs in `enabled` between copies, and the link marker is only in copies linked
+/// from the library
+fn is_local_file(rel: &Path) -> bool {
+    rel == Path::new("config.toml") || rel == Path::new(LINK_MARKER)
+}
+
+/// Total size and file count, leaving out what `is_local_file` does
+fn content_summary(module: &DivaMod) -> (u64, usize) {
+    let files: Vec<(PathBuf, u64)> = dir_files(&mod_dir(module))
+        .into_iter()
+        .filter(|(rel, _)| !is_local_file(rel))
+        .collect();
+    (files.iter().map(|(_, size)| size).sum(), files.len())
+}
+
+/// Sha256 over every path and file in the mod except config.toml, links into the library are
+/// hashed as what they point at
+pub fn hash_mod(module: &DivaMod) -> io::Result<String> {
+    let dir = mod_dir(module);
+    let mut hasher = Sha256::new();
+    for (rel, _) in dir_files(&dir) {
+        if is_local_file(&rel) {
+            continue;
+        }
+        hasher.update(rel.to_string_lossy().replace('\\', "/").as_bytes());
+        hasher.update([0u8]);
+        io::copy(&mut File::open(dir.join(&rel))?, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Folders named after the archive they came from are the ones `unpack_mod_path` made up
+fn looks_like_archive_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    [".zip", ".7z", ".rar", ".tar.gz", ".tar"]
+        .iter()
+        .any(|ext| lower.ends_with(ext))
+}
+
+fn suggest_keep(mods: &[DivaMod]) -> usize {
+    let in_use = |m: &DivaMod| -> usize {
+        let dir = m.dir_name().unwrap_or_default();
+        let mut count = 0;
+        if let Ok(packs) = MOD_PACKS.try_lock() {
+            count += packs
+                .values()
+                .filter(|p| p.mods.iter().any(|pm| pm.dir_name() == Some(dir.clone())))
+                .count();
+        }
+        count
+    };
+    mods.iter()
+        .enumerate()
+        .max_by_key(|(_, m)| {
+            (
+                !looks_like_archive_name(&m.dir_name().unwrap_or_default()),
+                in_use(m),
+                m.config["enabled"].as_bool().unwrap_or(false),
+            )
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+pub fn find_duplicates(mods: &[DivaMod]) -> Vec<Duplicates> {
+    // union find over dir names so a mod that matches on both name and content ends up in one group
+    let dirs: Vec<String> = mods
+        .iter()
+        .map(|m| m.dir_name().unwrap_or_default())
+        .collect();
+    let mut parent: Vec<usize> = (0..mods.len()).collect();
+    let mut reasons: HashMap<usize, DuplicateReason> = HashMap::new();
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut i = i;
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut union = |group: &[usize], reason: DuplicateReason| {
+        for pair in group.windows(2) {
+            let (a, b) = (find(&mut parent, pair[0]), find(&mut parent, pair[1]));
+            let joined = [reasons.get(&a), reasons.get(&b)]
+                .iter()
+                .flatten()
+                .fold(reason, |r, o| r.join(**o));
+            parent[b] = a;
+            reasons.insert(a, joined);
+        }
+    };
+
+    let mut by_name: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, m) in mods.iter().enumerate() {
+        let name = mod_name(m).trim().to_lowercase();
+        if !name.is_empty() {
+            by_name.entry(name).or_default().push(i);
+        }
+    }
+    for group in by_name.values().filter(|g| g.len() > 1) {
+        union(group, DuplicateReason::Name);
+    }
+
+    // only hash mods that could possibly match, hashing every song pack would take forever
+    let mut hashes: HashMap<usize, String> = HashMap::new();
+    let mut by_summary: HashMap<(u64, usize), Vec<usize>> = HashMap::new();
+    for (i, m) in mods.iter().enumerate() {
+        let summary = content_summary(m);
+        if summary.1 > 0 {
+            by_summary.entry(summary).or_default().push(i);
+        }
+    }
+    for candidates in by_summary.values().filter(|g| g.len() > 1) {
+        let mut by_hash: HashMap<String, Vec<usize>> = HashMap::new();
+        for i in candidates {
+            match hash_mod(&mods[*i]) {
+                Ok(hash) => {
+                    hashes.insert(*i, hash.clone());
+                    by_hash.entry(hash).or_default().push(*i);
+                }
+                Err(e) => eprintln!("Unable to hash {}: {e}", dirs[*i]),
+            }
+        }
+        for group in by_hash.values().filter(|g| g.len() > 1) {
+            union(group, DuplicateReason::Content);
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..mods.len() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut found: Vec<Duplicates> = groups
+        .into_iter()
+        .filter(|(_, g)| g.len() > 1)
+        .map(|(root, g)| {
+            let identical = g
+                .iter()
+                .all(|i| hashes.contains_key(i) && hashes.get(i) == hashes.get(&g[0]));
+            let mut group: Vec<DivaMod> = g.iter().map(|i| mods[*i].clone()).collect();
+            group.sort_by_key(|m| m.dir_name().unwrap_or_default().to_lowercase());
+            Duplicates {
+                reason: reasons.get(&root).copied().unwrap_or(DuplicateReason::Name),
+                keep: suggest_keep(&group),
+                mods: group,
+                identical,
+            }
+        })
+        .collect();
+    found.sort_by_key(|d| mod_name(&d.mods[0]).to_lowercase());
+    found
+}
+
+/// A mod's id and folder, what `points_at` checks pack entries against
+fn identity(module: &DivaMod) -> (String, PathBuf) {
+    (module.id.clone(), mod_dir(module))
+}
+
+/// Whether `pm` is the mod, by id when both have one. The copies all have different ids, their
+/// folders are only looked at for entries saved before mods had one
+fn points_at(pm: &ModPackMod, (id, dir): &(String, PathBuf)) -> bool {
+    if pm.separator {
+        return false;
+    }
+    match pm.id.is_empty() || id.is_empty() {
+        true => Path::new(&pm.path).parent() == Some(dir.as_path()),
+        false => pm.id == *id,
+    }
+}
+
+/// Points every reference to one of `remove` at `keep` instead, a list that already has `keep`
+/// just loses the duplicate entry
+fn rewrite_refs(list: &mut Vec<ModPackMod>, keep: &DivaMod, remove: &[DivaMod]) -> bool {
+    let keep_identity = identity(keep);
+    let removed: Vec<(String, PathBuf)> = remove.iter().map(identity).collect();
+    let is_removed = |pm: &ModPackMod| removed.iter().any(|r| points_at(pm, r));
+    let is_keep = |pm: &ModPackMod| points_at(pm, &keep_identity);
+    if !list.iter().any(is_removed) {
+        return false;
+    }
+    let enabled = list
+        .iter()
+        .any(|pm| pm.enabled && (is_removed(pm) || is_keep(pm)));
+    let mut seen = false;
+    let mut out = vec![];
+    for pm in list.drain(..) {
+        if is_removed(&pm) || is_keep(&pm) {
+            if seen {
+                continue;
+            }
+            seen = true;
+            let mut pm: ModPackMod = keep.clone().into();
+            pm.enabled = enabled;
+            out.push(pm);
+        } else {
+            out.push(pm);
+        }
+    }
+    *list = out;
+    true
+}
+
+/// Keeps `keep`, points packs, priority and dml at it, then moves the other copies into the
+/// trash. Whatever got done is recorded even when something fails part way
+pub fn merge_duplicates(
+    keep: &DivaMod,
+    remove: &[DivaMod],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut changes = vec![];
+    let res = merge_into(keep, remove, &mut changes);
+    record(format!("Merge duplicates of {}", mod_name(keep)), changes);
+    res
+}
+
+fn merge_into(
+    keep: &DivaMod,
+    remove: &[DivaMod],
+    changes: &mut Vec<Change>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let remove_names: Vec<String> = remove.iter().filter_map(|m| m.dir_name()).collect();
+    let keep_name = keep.dir_name().unwrap_or_default();
+
+    let packs: Vec<_> = {
+        let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+        packs
+            .values_mut()
+            .filter_map(|p| {
+                let before = p.mods.clone();
+                rewrite_refs(&mut p.mods, keep, remove).then(|| (before, p.clone()))
+            })
+            .collect()
+    };
+    for (before, pack) in packs {
+        changes.push(Change::Order {
+            pack: pack.name.clone(),
+            before,
+            after: pack.mods.clone(),
+        });
+        save_modpack_sync(pack)?;
+    }
+
+    let cfg = {
+        let mut cfg = R4D_CFG.try_lock().map_err(|e| e.to_string())?;
+        let before = cfg.priority.clone();
+        if rewrite_refs(&mut cfg.priority, keep, remove) {
+            changes.push(Change::Order {
+                pack: order_key("All Mods"),
+                before,
+                after: cfg.priority.clone(),
+            });
+        }
+        cfg.clone()
+    };
+    write_config_sync(cfg)?;
+
+    let dml = {
+        let mut dml = DML_CFG.try_lock().map_err(|e| e.to_string())?;
+        let mut prio = vec![];
+        for dir in dml.priority.drain(..) {
+            let dir = if remove_names.contains(&dir) {
+                keep_name.clone()
+            } else {
+                dir
+            };
+            if !prio.contains(&dir) {
+                prio.push(dir);
+            }
+        }
+        dml.priority = prio;
+        dml.clone()
+    };
+    write_dml_config(dml)?;
+
+    // the removed copies keep their own metadata so undoing brings them back as they were
+    {
+        let mut meta = MOD_META.try_lock().map_err(|e| e.to_string())?;
+        for name in remove_names.iter() {
+            if let Some(old) = meta.get(name).cloned() {
+                let kept = meta.entry(keep_name.clone()).or_default();
+                if kept.source.is_none() {
+                    kept.source = old.source;
+                }
+                for tag in old.tags {
+                    if !kept.tags.contains(&tag) {
+                        kept.tags.push(tag);
+                    }
+                }
+            }
+        }
+    }
+    save_metadata()?;
+
+    for name in remove_names {
+        changes.extend(trash_mod(&name)?);
+    }
+    Ok(())
+}
+
+fn to_group(dup: &Duplicates) -> DuplicateGroup {
+    let mods: Vec<DuplicateMod> = dup
+        .mods
+        .iter()
+        .map(|m| {
+            let (size, files) = content_summary(m);
+            DuplicateMod {
+                name: mod_name(m).into(),
+                dirname: m.dir_name().unwrap_or_default().into(),
+                version: m
+                    .config
+                    .get("version")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .into(),
+                size: format!("{} in {files} files", format_size(size)).into(),
+            }
+        })
+        .collect();
+    DuplicateGroup {
+        reason: dup.reason.describe().into(),
+        mods: ModelRc::new(VecModel::from(mods)),
+        keep: dup.keep as i32,
+        identical: dup.identical,
+        confirmed: false,
+    }
+}
+
+fn scan_into(window: Weak<DuplicatesWindow>, found: Arc<Mutex<Vec<Duplicates>>>) {
+    tokio::spawn(async move {
+        let dups = find_duplicates(&get_mods());
+        if let Ok(mut found) = found.lock() {
+            *found = dups.clone();
+        }
+        let _ = window.upgrade_in_event_loop(move |window| {
+            let groups: Vec<DuplicateGroup> = dups.iter().map(to_group).collect();
+            window.set_groups(ModelRc::new(VecModel::from(groups)));
+            window.set_scanning(false);
+        });
+    });
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_duplicates(move || {
+        let ui = weak.unwrap();
+        let window = match DuplicatesWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        window.set_scanning(true);
+
+        let found: Arc<Mutex<Vec<Duplicates>>> = Arc::new(Mutex::new(vec![]));
+        scan_into(window.as_weak(), found.clone());
+
+        let window_weak = window.as_weak();
+        let rescan_found = found.clone();
+        window.on_rescan(move || {
+            window_weak.unwrap().set_scanning(true);
+            scan_into(window_weak.clone(), rescan_found.clone());
+        });
+
+        let window_weak = window.as_weak();
+        let main_weak = weak.clone();
+        window.on_merge(move |group, keep| {
+            let dup = match found.lock() {
+                Ok(found) => match found.get(group as usize) {
+                    Some(dup) => dup.clone(),
+                    None => return,
+                },
+                Err(_) => return,
+            };
+            let Some(kept) = dup.mods.get(keep as usize).cloned() else {
+                return;
+            };
+            let remove: Vec<DivaMod> = dup
+                .mods
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != keep as usize)
+                .map(|(_, m)| m.clone())
+                .collect();
+            window_weak.unwrap().set_scanning(true);
+            let window_weak = window_weak.clone();
+            let main_weak = main_weak.clone();
+            let found = found.clone();
+            tokio::spawn(async move {
+                if let Err(e) = merge_duplicates(&kept, &remove) {
+                    open_error_window(format!("Unable to merge duplicates: {e}"));
+                }
+                if load_mods().is_ok() {
+                    let _ = set_mods_table(&get_mods(), main_weak.clone());
+                }
+                let applied = match R4D_CFG.try_lock() {
+                    Ok(cfg) if !cfg.applied_pack.is_empty() => cfg.applied_pack.clone(),
+                    _ => "All Mods".to_string(),
+                };
+                let _ = main_weak.upgrade_in_event_loop(move |ui| {
+                    ui.global::<ModpackLogic>()
+                        .invoke_change_modpack(applied.into());
+                });
+                scan_into(window_weak, found);
+            });
+        });
+
+        let weak = window.as_weak();
+        window.on_close(move || {
+            weak.unwrap().hide().unwrap();
+        });
+
+        let weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::ModMeta;
+    use std::fs;
+
+    /// A mod in a temp folder, with `id` in the metadata
+    fn test_mod(dir: &str, id: &str) -> DivaMod {
+        let mod_dir = std::env::temp_dir()
+            .join(format!("rust4diva-duplicates-{}", std::process::id()))
+            .join(dir);
+        fs::create_dir_all(&mod_dir).unwrap();
+        if let Ok(mut meta) = MOD_META.lock() {
+            meta.insert(
+                dir.to_owned(),
+                ModMeta {
+                    id: id.to_owned(),
+                    ..Default::default()
+                },
+            );
+        }
+        DivaMod {
+            path: mod_dir.join("config.toml").display().to_string(),
+            config: "name = \"Pack\"\nenabled = true".parse().unwrap(),
+            size: 0,
+            file_count: 0,
+            id: id.to_owned(),
+        }
+    }
+
+    fn entry(path: &str, id: &str, enabled: bool) -> ModPackMod {
+        ModPackMod {
+            name: "Pack".to_owned(),
+            enabled,
+            path: path.to_owned(),
+            separator: false,
+            collapsed: false,
+            id: id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn rewrites_by_id() {
+        let keep = test_mod("dup-keep", "id-keep");
+        let copy = test_mod("dup-copy", "id-copy");
+        let other = entry("/elsewhere/other/config.toml", "id-other", true);
+        // the copy's entry has a stale path, only its id says what it is
+        let mut list = vec![
+            entry("/old/renamed/config.toml", "id-copy", true),
+            other.clone(),
+            entry(&keep.path, "id-keep", false),
+        ];
+        assert!(rewrite_refs(&mut list, &keep, std::slice::from_ref(&copy)));
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].id, "id-keep");
+        assert_eq!(list[0].path, keep.path);
+        assert!(list[0].enabled);
+        assert_eq!(list[1].id, other.id);
+
+        // nothing to do when the copy isn't in there
+        assert!(!rewrite_refs(&mut list, &keep, &[copy]));
+    }
+
+    #[test]
+    fn old_entries_match_by_folder() {
+        let keep = test_mod("dup-old-keep", "id-old-keep");
+        let copy = test_mod("dup-old-copy", "id-old-copy");
+        let mut list = vec![entry(&copy.path, "", false)];
+        assert!(rewrite_refs(&mut list, &keep, &[copy]));
+        assert_eq!(list[0].path, keep.path);
+        assert!(!list[0].enabled);
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rfd::AsyncFileDialog;
+use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::ComponentHandle;
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+use toml_edit::{value, DocumentMut};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::diva::open_error_window;
+use crate::library::LINK_MARKER;
+use crate::slint_generatedApp::App;
+use crate::{ExportModDialog, WindowLogic};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZ,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::SevenZ => "7z",
+        }
+    }
+}
+
+/// Where things go inside a pack bundle
+pub const BUNDLE_PACK_FILE: &str = "pack.r4dpack";
+pub const BUNDLE_MODS_DIR: &str = "mods";
+
+/// Files and folders that only mean something on the machine they were made on
+const USER_LOCAL_NAMES: [&str; 7] = [
+    ".git",
+    ".DS_Store",
+    "__MACOSX",
+    "Thumbs.db",
+    "desktop.ini",
+    ".directory",
+    "dxvk.cache",
+];
+
+const USER_LOCAL_EXTENSIONS: [&str; 6] = ["log", "bak", "old", "orig", "tmp", "swp"];
+
+pub fn is_user_local(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if name.ends_with('~')
+        || USER_LOCAL_NAMES
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(&name))
+    {
+        return true;
+    }
+    match path.extension() {
+        Some(ext) => USER_LOCAL_EXTENSIONS
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(&ext.to_string_lossy())),
+        None => false,
+    }
+}
+
+/// Every file and folder in `dir` relative to `dir`, folders come before their contents.
+/// Links go in as what they point at, so a mod linked in from the library ships its files
+/// rather than links into our library. Links to nothing are left out, and so is a folder that's
+/// already in, which is what stops a link back up the tree going round forever
+fn collect_entries(dir: &Path, strip: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut entries = vec![];
+    let mut seen = HashSet::from([fs::canonicalize(dir)?]);
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel) = stack.pop() {
+        let mut read: Vec<_> = fs::read_dir(dir.join(&rel))?.flatten().collect();
+        read.sort_by_key(|e| e.file_name());
+        for entry in read.iter().rev() {
+            let path = entry.path();
+            // where the library copy lives only means something here
+            if (strip && is_user_local(&path)) || entry.file_name() == LINK_MARKER {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            let is_dir = match file_type.is_symlink() {
+                true => match fs::metadata(&path) {
+                    Ok(meta) => meta.is_dir(),
+                    Err(_) => continue,
+                },
+                false => file_type.is_dir(),
+            };
+            let rel = rel.join(entry.file_name());
+            if is_dir {
+                if !seen.insert(fs::canonicalize(&path)?) {
+                    continue;
+                }
+                stack.push(rel.clone());
+            }
+            entries.push(rel);
+        }
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+/// The config.toml as it should be shipped, stripping resets `enabled` so whoever installs it
+/// doesn't get our local state
+fn exported_config(mod_dir: &Path, strip: bool) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+    let config = fs::read_to_string(mod_dir.join("config.toml"))?;
+    if !strip {
+        return Ok(config.into_bytes());
+    }
+    let mut config = config.parse::<DocumentMut>()?;
+    config["enabled"] = value(true);
+    Ok(config.to_string().into_bytes())
+}
+
+fn entry_name(root: &str, rel: &Path) -> String {
+    let mut name = root.to_owned();
+    for component in rel.components() {
+        name.push('/');
+        name.push_str(&component.as_os_str().to_string_lossy());
+    }
+    name
+}
+
+/// Writes zips and 7zs as entries come in, file contents are streamed rather than read whole
+enum ArchiveWriter {
+    Zip(ZipWriter<File>),
+    SevenZ(SevenZWriter<File>),
+}
+
+impl ArchiveWriter {
+    fn create(dest: &Path, format: ArchiveFormat) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let file = File::create(dest)?;
+        Ok(match format {
+            ArchiveFormat::Zip => ArchiveWriter::Zip(ZipWriter::new(file)),
+            ArchiveFormat::SevenZ => ArchiveWriter::SevenZ(SevenZWriter::new(file)?),
+        })
+    }
+
+    fn zip_options(modified: SystemTime, dir: bool, size: u64) -> SimpleFileOptions {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(if dir { 0o755 } else { 0o644 })
+            .large_file(size > u32::MAX as u64);
+        match zip::DateTime::try_from(OffsetDateTime::from(modified)) {
+            Ok(time) => options.last_modified_time(time),
+            Err(_) => options,
+        }
+    }
+
+    /// Adds a file or folder from disk, `data` replaces what's in the file
+    fn add(
+        &mut self,
+        name: &str,
+        path: &Path,
+        data: Option<&[u8]>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let meta = fs::metadata(path)?;
+        let dir = meta.is_dir();
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                let size = data.map(|d| d.len() as u64).unwrap_or(meta.len());
+                let options = Self::zip_options(meta.modified()?, dir, size);
+                if dir {
+                    zip.add_directory(name, options)?;
+                    return Ok(());
+                }
+                zip.start_file(name, options)?;
+                match data {
+                    Some(data) => zip.write_all(data)?,
+                    None => {
+                        io::copy(&mut File::open(path)?, zip)?;
+                    }
+                }
+            }
+            ArchiveWriter::SevenZ(sz) => {
+                let entry = SevenZArchiveEntry::from_path(path, name.trim_end_matches('/').into());
+                match (dir, data) {
+                    (true, _) => sz.push_archive_entry::<File>(entry, None)?,
+                    (false, Some(data)) => sz.push_archive_entry(entry, Some(data))?,
+                    (false, None) => sz.push_archive_entry(entry, Some(File::open(path)?))?,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// A file that only exists in memory
+    fn add_data(&mut self, name: &str, data: &[u8]) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match self {
+            ArchiveWriter::Zip(zip) => {
+                let options = Self::zip_options(SystemTime::now(), false, data.len() as u64);
+                zip.start_file(name, options)?;
+                zip.write_all(data)?;
+            }
+            ArchiveWriter::SevenZ(sz) => {
+                let mut entry = SevenZArchiveEntry::new();
+                entry.name = name.to_owned();
+                entry.has_stream = true;
+                if let Ok(time) = SystemTime::now().try_into() {
+                    entry.last_modified_date = time;
+                    entry.has_last_modified_date = true;
+                }
+                sz.push_archive_entry(entry, Some(data))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut file = match self {
+            ArchiveWriter::Zip(zip) => zip.finish()?,
+            ArchiveWriter::SevenZ(sz) => sz.finish()?,
+        };
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Packages `mod_dir` into `dest` with the mod folder as the only thing at the root of the
+/// archive, which is what `check_archive_valid_structure` wants
+pub fn export_mod(
+    mod_dir: &Path,
+    dest: &Path,
+    format: ArchiveFormat,
+    strip: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let root = match mod_dir.file_name() {
+        Some(root) => root.to_string_lossy().to_string(),
+        None => return Err("Invalid mod folder".into()),
+    };
+    let mut writer = ArchiveWriter::create(dest, format)?;
+    add_mod(&mut writer, mod_dir, &root, strip)?;
+    writer.finish()
+}
+
+/// Writes a pack bundle, `pack` goes in as `BUNDLE_PACK_FILE` and every mod folder under
+/// `BUNDLE_MODS_DIR`
+pub fn export_bundle(
+    pack: &[u8],
+    mod_dirs: &[PathBuf],
+    dest: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut writer = ArchiveWriter::create(dest, ArchiveFormat::Zip)?;
+    writer.add_data(BUNDLE_PACK_FILE, pack)?;
+    for mod_dir in mod_dirs {
+        let root = match mod_dir.file_name() {
+            Some(root) => format!("{BUNDLE_MODS_DIR}/{}", root.to_string_lossy()),
+            None => return Err("Invalid mod folder".into()),
+        };
+        add_mod(&mut writer, mod_dir, &root, true)?;
+    }
+    writer.finish()
+}
+
+fn add_mod(
+    writer: &mut ArchiveWriter,
+    mod_dir: &Path,
+    root: &str,
+    strip: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let entries = collect_entries(mod_dir, strip)?;
+    let config = exported_config(mod_dir, strip)?;
+    writer.add(&format!("{root}/"), mod_dir, None)?;
+    for rel in entries {
+        let path = mod_dir.join(&rel);
+        let mut name = entry_name(root, &rel);
+        if path.is_dir() {
+            name.push('/');
+        }
+        if rel == Path::new("config.toml") {
+            writer.add(&name, &path, Some(&config))?;
+        } else {
+            writer.add(&name, &path, None)?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>()
+        .on_open_export_mod(move |module| {
+            let ui = weak.unwrap();
+            let dialog = match ExportModDialog::new() {
+                Ok(dialog) => dialog,
+                Err(e) => {
+                    open_error_window(e.to_string());
+                    return;
+                }
+            };
+            dialog.invoke_set_color_scheme(ui.get_color_scheme());
+            dialog.set_name(module.name.clone());
+
+            let dweak = dialog.as_weak();
+            dialog.on_cancel(move || {
+                dweak.unwrap().hide().unwrap();
+            });
+
+            let dweak = dialog.as_weak();
+            dialog.on_export(move |seven_z, strip| {
+                let format = if seven_z {
+                    ArchiveFormat::SevenZ
+                } else {
+                    ArchiveFormat::Zip
+                };
+                let mut mod_dir = PathBuf::from(module.path.to_string());
+                mod_dir.pop();
+                let file_name = format!(
+                    "{}.{}",
+                    mod_dir
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or("mod".to_string()),
+                    format.extension()
+                );
+                let picker = AsyncFileDialog::new()
+                    .add_filter("Archive", &[format.extension()])
+                    .set_file_name(file_name)
+                    .set_directory(dirs::home_dir().unwrap_or_default());
+                let dweak = dweak.clone();
+                dweak.unwrap().set_exporting(true);
+                tokio::spawn(async move {
+                    let dest = match picker.save_file().await {
+                        Some(handle) => handle.path().to_path_buf(),
+                        None => {
+                            let _ = dweak.upgrade_in_event_loop(|ui| ui.set_exporting(false));
+                            return;
+                        }
+                    };
+                    let res = {
+                        let dest = dest.clone();
+                        tokio::task::spawn_blocking(move || {
+                            export_mod(&mod_dir, &dest, format, strip).map_err(|e| e.to_string())
+                        })
+                        .await
+                    };
+                    let res = match res {
+                        Ok(res) => res,
+                        Err(e) => Err(e.to_string()),
+                    };
+                    let _ = dweak.upgrade_in_event_loop(move |ui| {
+                        ui.set_exporting(false);
+                        match res {
+                            Ok(_) => {
+                                ui.hide().unwrap();
+                            }
+                            Err(e) => {
+                                // don't leave half written archives around
+                                let _ = fs::remove_file(&dest);
+                                open_error_window(format!("Unable to export mod: {e}"));
+                            }
+                        }
+                    });
+                });
+            });
+
+            let dweak = dialog.as_weak();
+            let mut scheme_rx = dark_rx.resubscribe();
+            let scheme_task = tokio::spawn(async move {
+                while let Ok(scheme) = scheme_rx.recv().await {
+                    let _ = dweak.upgrade_in_event_loop(move |ui| {
+                        ui.invoke_set_color_scheme(scheme);
+                    });
+                }
+            });
+
+            dialog.window().on_close_requested(move || {
+                scheme_task.abort();
+                slint::CloseRequestResponse::HideWindow
+            });
+
+            dialog.show().unwrap();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::symlink;
+
+    #[test]
+    fn links_are_followed_once() {
+        let base = std::env::temp_dir().join(format!("rust4diva-export-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (library, mod_dir) = (base.join("library"), base.join("mod"));
+        fs::create_dir_all(library.join("rom")).unwrap();
+        fs::write(library.join("rom/pv_db.txt"), b"").unwrap();
+        fs::create_dir_all(mod_dir.join("sub")).unwrap();
+        fs::write(mod_dir.join("config.toml"), b"").unwrap();
+        fs::write(mod_dir.join(LINK_MARKER), b"").unwrap();
+        symlink(&library.join("rom"), &mod_dir.join("rom")).unwrap();
+        symlink(&mod_dir, &mod_dir.join("sub/loop")).unwrap();
+        symlink(&base.join("nothing"), &mod_dir.join("broken")).unwrap();
+
+        let entries = collect_entries(&mod_dir, true).unwrap();
+        let expected: Vec<PathBuf> = ["config.toml", "rom", "rom/pv_db.txt", "sub"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(entries, expected);
+    }
+}
@@ -8,6 +8,7 @@ use futures_util::future::try_join_all;
 
 use crate::config::write_config_sync;
 use crate::diva::{get_diva_folder, open_error_window};
+use crate::modmanagement::get_mods_folder;
 use crate::modpacks::{self, ModPack, ModPackMod};
 use crate::slint_generatedApp::App;
 use crate::{FirstSetup, Loadout, SetupLogic, R4D_CFG};
@@ -49,14 +50,15 @@ pub struct DmmLoadoutMod {
 
 impl DmmLoadoutMod {
     pub fn to_packmod(self: &Self, mut mods_dir: PathBuf) -> ModPackMod {
-        // let mut buf = PathBuf::from(mods_dir.clone());
-        mods_dir.push("mods");
         mods_dir.push(self.name.clone());
         mods_dir.push("config.toml");
         ModPackMod {
             name: self.name.clone(),
             enabled: self.enabled.clone(),
             path: mods_dir.to_str().unwrap().to_owned(),
+            separator: false,
+            collapsed: false,
+            id: String::new(),
         }
     }
 }
@@ -224,6 +226,8 @@ pub async fn init(_diva_ui: &App) -> Result<(), slint::PlatformError> {
 
                                         let mut pack =
                                             ModPack::new(filenamify(loadout.name.to_string()));
+                                        let mods_buf =
+                                            get_mods_folder().unwrap_or(diva_buf.join("mods"));
 
                                         println!(
                                             r#"Converting Loadout: "{}" to modpack"#,
@@ -233,7 +237,7 @@ pub async fn init(_diva_ui: &App) -> Result<(), slint::PlatformError> {
                                             config.loadouts.get(&loadout.name.to_string()).unwrap()
                                         {
                                             println!("{}", module.name);
-                                            pack.mods.push(module.to_packmod(diva_buf.clone()))
+                                            pack.mods.push(module.to_packmod(mods_buf.clone()))
                                         }
                                         loadouts.push(pack.clone());
                                     }
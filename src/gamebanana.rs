@@ -129,6 +129,14 @@ pub struct GBSearch {
     preview_media: GbPreview,
     #[serde(rename(deserialize = "_aFiles"), default)]
     pub files: Vec<GbModDownload>,
+    #[serde(rename(deserialize = "_aRootCategory"), default)]
+    root_category: Option<GbCategory>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GbCategory {
+    #[serde(rename(deserialize = "_sName"), default)]
+    name: String,
 }
 
 impl From<GBSearch> for SearchPreviewData {
@@ -152,7 +160,11 @@ impl From<GBSearch> for SearchPreviewData {
             author: value.submitter.into(),
             id: value.id as i32,
             image: Default::default(),
-            item_type: value.model_name.into(),
+            // the root category (Songs, Modules, UI...) says a lot more than "Mod" does
+            item_type: match value.root_category {
+                Some(category) if !category.name.is_empty() => category.name.into(),
+                _ => value.model_name.into(),
+            },
             name: value.name.into(),
             updated: updated.into(),
             image_url: imgurl.into(),
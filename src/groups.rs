@@ -0,0 +1,398 @@
+use std::cmp::min;
+use std::collections::HashMap;
+use std::error::Error;
+
+use slint::{ComponentHandle, SharedString};
+
+use crate::config::write_config_sync;
+use crate::diva::open_error_window;
+use crate::history::{order_key, record, Change};
+use crate::modmanagement::DivaMod;
+use crate::modpacks::{save_modpack_sync, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::{DivaModElement, ModLogic, ModpackLogic, MOD_PACKS, R4D_CFG};
+
+pub fn new_separator(name: &str) -> ModPackMod {
+    ModPackMod {
+        name: name.to_owned(),
+        enabled: true,
+        path: "".to_owned(),
+        separator: true,
+        collapsed: false,
+        id: String::new(),
+    }
+}
+
+/// Separator heading the group `idx` is in, None for anything above the first separator
+pub fn group_of(list: &[ModPackMod], idx: usize) -> Option<usize> {
+    (0..=min(idx, list.len().saturating_sub(1)))
+        .rev()
+        .find(|i| list[*i].separator)
+}
+
+/// One past the last member of the group that starts at `start`
+pub fn group_end(list: &[ModPackMod], start: usize) -> usize {
+    (start + 1..list.len())
+        .find(|i| list[*i].separator)
+        .unwrap_or(list.len())
+}
+
+fn is_hidden(list: &[ModPackMod], idx: usize) -> bool {
+    !list[idx].separator && group_of(list, idx).is_some_and(|s| list[s].collapsed)
+}
+
+/// Indexes into `list` of the rows the table actually shows, members of collapsed groups and
+/// mods that aren't installed anymore are left out
+pub fn view_indices(list: &[ModPackMod]) -> Vec<usize> {
+    (0..list.len())
+        .filter(|i| (list[*i].separator || list[*i].dir_name().is_some()) && !is_hidden(list, *i))
+        .collect()
+}
+
+/// Moves an entry, a separator takes its whole group along with it.
+/// Groups are only ever dropped between other groups so they can't end up split
+pub fn move_entry(list: &mut Vec<ModPackMod>, old: usize, new: usize) {
+    if old >= list.len() || old == new {
+        return;
+    }
+    let new = min(new, list.len() - 1);
+    if !list[old].separator {
+        let item = list.remove(old);
+        list.insert(min(new, list.len()), item);
+        return;
+    }
+    let end = group_end(list, old);
+    let at = if new < old {
+        group_of(list, new).unwrap_or(0)
+    } else {
+        // dropping it on one of its own members means "below the next group"
+        let target = if new < end { end } else { new };
+        if target >= list.len() {
+            return;
+        }
+        group_end(list, group_of(list, target).unwrap_or(target)) - (end - old)
+    };
+    let block: Vec<ModPackMod> = list.drain(old..end).collect();
+    list.splice(at..at, block);
+}
+
+/// Same as `move_entry` but with the row numbers the table sends
+pub fn move_in_view(list: &mut Vec<ModPackMod>, old: usize, new: usize) {
+    let view = view_indices(list);
+    if view.is_empty() {
+        return;
+    }
+    let old = view[min(old, view.len() - 1)];
+    let new = view[min(new, view.len() - 1)];
+    move_entry(list, old, new);
+}
+
+/// Rows for the load order table, separators included and collapsed groups folded away
+pub fn order_elements(list: &[ModPackMod], mods: &[DivaMod]) -> Vec<DivaModElement> {
+    let by_dir: HashMap<String, &DivaMod> = mods
+        .iter()
+        .filter_map(|m| m.dir_name().map(|d| (d, m)))
+        .collect();
+    let mut elements = vec![];
+    for (i, entry) in list.iter().enumerate() {
+        if entry.separator {
+            let count = list[i + 1..group_end(list, i)]
+                .iter()
+                .filter(|m| m.dir_name().is_some_and(|d| by_dir.contains_key(&d)))
+                .count();
+            let mut element = entry.to_element();
+            element.description = format!("{count} mods").into();
+            elements.push(element);
+        } else if !is_hidden(list, i) {
+            if let Some(m) = by_dir.get(&entry.dir_name().unwrap_or_default()) {
+                elements.push((*m).clone().into());
+            }
+        }
+    }
+    elements
+}
+
+/// Turns what the table shows back into a full list, collapsed groups get their members back
+/// from `stored`. Separators keep their order so the nth one in the table is the nth one stored
+pub fn expand_view(
+    elements: impl Iterator<Item = DivaModElement>,
+    stored: &[ModPackMod],
+) -> Vec<ModPackMod> {
+    let groups: Vec<Vec<ModPackMod>> = (0..stored.len())
+        .filter(|i| stored[*i].separator)
+        .map(|i| stored[i + 1..group_end(stored, i)].to_vec())
+        .collect();
+    let mut list = vec![];
+    let mut nth = 0;
+    for element in elements {
+        list.push(element.to_packmod());
+        if element.separator {
+            if element.collapsed {
+                if let Some(members) = groups.get(nth) {
+                    list.extend(members.iter().cloned());
+                }
+            }
+            nth += 1;
+        }
+    }
+    list
+}
+
+/// Entries in `list` for mods that aren't installed, separators are never missing
+pub fn missing_entries(list: &[ModPackMod], mods: &HashMap<String, DivaMod>) -> Vec<ModPackMod> {
+    list.iter()
+        .filter(|p| !p.separator && !mods.contains_key(&p.dir_name().unwrap_or_default()))
+        .cloned()
+        .collect()
+}
+
+/// The table only has installed mods, so anything missing from `stored` gets put back after
+/// whatever it came after before. Otherwise saving a pack would quietly drop them
+pub fn restore_missing(
+    mut list: Vec<ModPackMod>,
+    stored: &[ModPackMod],
+    mods: &HashMap<String, DivaMod>,
+) -> Vec<ModPackMod> {
+    for (i, entry) in stored.iter().enumerate() {
+        if entry.separator || mods.contains_key(&entry.dir_name().unwrap_or_default()) {
+            continue;
+        }
+        let at = match i {
+            0 => 0,
+            _ => list
+                .iter()
+                .position(|m| *m == stored[i - 1])
+                .map(|p| p + 1)
+                .unwrap_or(list.len()),
+        };
+        list.insert(at, entry.clone());
+    }
+    list
+}
+
+/// Drops entries for mods that are gone, separators are always kept
+pub fn prune_missing(list: &[ModPackMod], mods: &HashMap<String, DivaMod>) -> Vec<ModPackMod> {
+    list.iter()
+        .filter_map(|p| match p.separator {
+            true => Some(p.clone()),
+            false => mods
+                .get(&p.dir_name().unwrap_or_default())
+                .map(|m| m.clone().into()),
+        })
+        .collect()
+}
+
+/// Load order of whatever is applied right now, the global priority or a pack
+pub fn current_order() -> Vec<ModPackMod> {
+    let cfg = match R4D_CFG.try_lock() {
+        Ok(cfg) => cfg,
+        Err(_) => return vec![],
+    };
+    if cfg.applied_pack.is_empty() || cfg.applied_pack == "All Mods" {
+        return cfg.priority.clone();
+    }
+    match MOD_PACKS.try_lock() {
+        Ok(packs) => match packs.get(&cfg.applied_pack) {
+            Some(pack) => pack.mods.clone(),
+            None => vec![],
+        },
+        Err(_) => vec![],
+    }
+}
+
+/// Edits the current load order and saves it, returns the applied pack so the table can be
+/// reloaded. With a `label` the edit goes into the undo history
+pub fn edit_current_order<F: FnOnce(&mut Vec<ModPackMod>)>(
+    label: Option<&str>,
+    edit: F,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let mut cfg = R4D_CFG.try_lock().map_err(|e| e.to_string())?;
+    let applied = cfg.applied_pack.clone();
+    let (before, after) = if applied.is_empty() || applied == "All Mods" {
+        let before = cfg.priority.clone();
+        edit(&mut cfg.priority);
+        write_config_sync(cfg.clone())?;
+        (before, cfg.priority.clone())
+    } else {
+        let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+        let pack = packs
+            .get_mut(&applied)
+            .ok_or(format!("Mod pack {applied} doesn't exist"))?;
+        let before = pack.mods.clone();
+        edit(&mut pack.mods);
+        save_modpack_sync(pack.clone())?;
+        (before, pack.mods.clone())
+    };
+    if let Some(label) = label {
+        let pack = order_key(&applied);
+        record(
+            label,
+            vec![Change::Order {
+                pack,
+                before,
+                after,
+            }],
+        );
+    }
+    Ok(applied)
+}
+
+fn edit_and_reload<F: FnOnce(&mut Vec<ModPackMod>)>(ui: &App, label: Option<&str>, edit: F) {
+    match edit_current_order(label, edit) {
+        Ok(applied) => {
+            ui.global::<ModpackLogic>()
+                .invoke_change_modpack(SharedString::from(applied));
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            open_error_window(format!("Unable to update the load order: \n{e}"));
+        }
+    }
+}
+
+/// Turns a table row into an index into the list, only if that row is a separator
+fn separator_at(list: &[ModPackMod], row: i32) -> Option<usize> {
+    let idx = *view_indices(list).get(usize::try_from(row).ok()?)?;
+    list[idx].separator.then_some(idx)
+}
+
+pub async fn init(ui: &App) {
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_add_separator(move |row, name| {
+        let name = name.trim().to_owned();
+        if name.is_empty() {
+            return;
+        }
+        let label = format!("Add group {name}");
+        edit_and_reload(&weak.unwrap(), Some(&label), |list| {
+            let view = view_indices(list);
+            let at = match usize::try_from(row).ok().and_then(|r| view.get(r)) {
+                Some(idx) => *idx,
+                None => list.len(),
+            };
+            list.insert(at, new_separator(&name));
+        });
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>()
+        .on_rename_separator(move |row, name| {
+            let name = name.trim().to_owned();
+            if name.is_empty() {
+                return;
+            }
+            let label = format!("Rename group to {name}");
+            edit_and_reload(&weak.unwrap(), Some(&label), |list| {
+                if let Some(idx) = separator_at(list, row) {
+                    list[idx].name = name;
+                }
+            });
+        });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_remove_separator(move |row| {
+        // the members just fall into whatever group is above
+        edit_and_reload(&weak.unwrap(), Some("Remove group"), |list| {
+            if let Some(idx) = separator_at(list, row) {
+                list.remove(idx);
+            }
+        });
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_toggle_group(move |row| {
+        // folding a group away isn't worth an undo step
+        edit_and_reload(&weak.unwrap(), None, |list| {
+            if let Some(idx) = separator_at(list, row) {
+                list[idx].collapsed = !list[idx].collapsed;
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Capitalised names are separators
+    fn list(names: &[&str]) -> Vec<ModPackMod> {
+        names
+            .iter()
+            .map(|name| match name.starts_with(char::is_uppercase) {
+                true => new_separator(name),
+                false => ModPackMod {
+                    name: name.to_string(),
+                    enabled: true,
+                    path: String::new(),
+                    separator: false,
+                    collapsed: false,
+                    id: String::new(),
+                },
+            })
+            .collect()
+    }
+
+    fn names(list: &[ModPackMod]) -> Vec<&str> {
+        list.iter().map(|m| m.name.as_str()).collect()
+    }
+
+    const START: [&str; 8] = ["a", "One", "b", "c", "Two", "d", "Three", "e"];
+
+    fn moved(old: usize, new: usize) -> Vec<String> {
+        let mut list = list(&START);
+        move_entry(&mut list, old, new);
+        names(&list).into_iter().map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn groups() {
+        let list = list(&START);
+        assert_eq!(group_of(&list, 0), None);
+        assert_eq!(group_of(&list, 3), Some(1));
+        assert_eq!(group_of(&list, 4), Some(4));
+        assert_eq!(group_end(&list, 1), 4);
+        assert_eq!(group_end(&list, 6), 8);
+    }
+
+    #[test]
+    fn mods_move_on_their_own() {
+        assert_eq!(
+            moved(0, 3),
+            ["One", "b", "c", "a", "Two", "d", "Three", "e"]
+        );
+        assert_eq!(
+            moved(7, 2),
+            ["a", "One", "e", "b", "c", "Two", "d", "Three"]
+        );
+        // past the end is the end
+        assert_eq!(
+            moved(0, 100),
+            ["One", "b", "c", "Two", "d", "Three", "e", "a"]
+        );
+    }
+
+    #[test]
+    fn separators_take_their_group() {
+        // dropped on d, the group goes below Two's
+        assert_eq!(
+            moved(1, 5),
+            ["a", "Two", "d", "One", "b", "c", "Three", "e"]
+        );
+        // dropped on c, the group goes above One's
+        assert_eq!(
+            moved(6, 3),
+            ["a", "Three", "e", "One", "b", "c", "Two", "d"]
+        );
+        // dropped on its own member it swaps with the next group
+        assert_eq!(moved(1, 2), moved(1, 5));
+    }
+
+    #[test]
+    fn nothing_to_do() {
+        let unchanged: Vec<String> = START.iter().map(|s| s.to_string()).collect();
+        assert_eq!(moved(3, 3), unchanged);
+        assert_eq!(moved(100, 0), unchanged);
+        // the last group has nowhere further down to go
+        assert_eq!(moved(6, 7), unchanged);
+    }
+}
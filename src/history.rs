@@ -0,0 +1,750 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chrono::Local;
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::sync::broadcast;
+use toml_edit::value;
+
+use crate::config::write_config_sync;
+use crate::diva::open_error_window;
+use crate::layers::set_parent;
+use crate::modmanagement::{
+    get_mods, get_mods_folder, load_mods, rename_mod_folder, save_mod_config, set_mods_table,
+};
+use crate::modpacks::{
+    get_modpacks_folder, refresh_pack_list, save_modpack_sync, ModPack, ModPackMod,
+};
+use crate::packops::rename_pack;
+use crate::slint_generatedApp::App;
+use crate::{
+    HistoryItem, HistoryWindow, ModLogic, ModpackLogic, WindowLogic, MODS, MOD_PACKS, R4D_CFG,
+};
+
+/// How many steps back you can go
+const MAX_ENTRIES: usize = 100;
+/// Deleted mods older than this are cleared out of the trash at startup
+const TRASH_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const TRASH_FOLDER: &str = ".rust4diva-trash";
+
+/// One reversible piece of an operation
+#[derive(Clone, Debug)]
+pub enum Change {
+    /// load order of a pack, "" is the global priority
+    Order {
+        pack: String,
+        before: Vec<ModPackMod>,
+        after: Vec<ModPackMod>,
+    },
+    /// enabled flag in a mod's config.toml
+    Enabled {
+        dir: String,
+        before: bool,
+        after: bool,
+    },
+    /// mod folder that showed up, it sits in `trash` while undone
+    Added {
+        dir: String,
+        trash: PathBuf,
+    },
+    /// mod folder that got deleted, it sits in `trash` until undone. Can also be a path to
+    /// something inside a mod, relative to the mods folder
+    Removed {
+        dir: String,
+        trash: PathBuf,
+    },
+    /// something inside the mods folder that moved, both relative to the mods folder
+    Moved {
+        from: String,
+        to: String,
+    },
+    PackDeleted {
+        pack: ModPack,
+    },
+    PackCreated {
+        pack: ModPack,
+    },
+    PackRenamed {
+        from: String,
+        to: String,
+    },
+    /// the pack a pack is layered on, empty for none
+    PackParent {
+        pack: String,
+        before: String,
+        after: String,
+    },
+    /// mod folder name, the packs and priorities follow along
+    Renamed {
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    label: String,
+    time: String,
+    changes: Vec<Change>,
+}
+
+#[derive(Default)]
+struct History {
+    done: Vec<Entry>,
+    undone: Vec<Entry>,
+}
+
+static HISTORY: LazyLock<Mutex<History>> = LazyLock::new(|| Mutex::new(History::default()));
+static MAIN_WINDOW: LazyLock<Mutex<Option<Weak<App>>>> = LazyLock::new(|| Mutex::new(None));
+static HISTORY_WINDOW: LazyLock<Mutex<Option<Weak<HistoryWindow>>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+fn same_order(a: &[ModPackMod], b: &[ModPackMod]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b.iter()).all(|(a, b)| {
+            a.name == b.name
+                && a.path == b.path
+                && a.enabled == b.enabled
+                && a.separator == b.separator
+                && a.collapsed == b.collapsed
+        })
+}
+
+fn is_noop(change: &Change) -> bool {
+    match change {
+        Change::Order { before, after, .. } => same_order(before, after),
+        Change::Enabled { before, after, .. } => before == after,
+        Change::Renamed { from, to } | Change::Moved { from, to } => from == to,
+        _ => false,
+    }
+}
+
+/// Adds an operation to the history, anything that was undone can't be redone after this
+pub fn record(label: impl Into<String>, changes: Vec<Change>) {
+    let changes: Vec<Change> = changes.into_iter().filter(|c| !is_noop(c)).collect();
+    if changes.is_empty() {
+        return;
+    }
+    if let Ok(mut history) = HISTORY.lock() {
+        history.done.push(Entry {
+            label: label.into(),
+            time: Local::now().format("%H:%M:%S").to_string(),
+            changes,
+        });
+        if history.done.len() > MAX_ENTRIES {
+            history.done.remove(0);
+        }
+        // redo entries that deleted something leave their folder in the trash until it's
+        // emptied or gets old
+        history.undone.clear();
+    }
+    notify();
+}
+
+/// `applied` as stored in the config, "All Mods" and "" both mean the global priority
+pub fn order_key(applied: &str) -> String {
+    match applied {
+        "All Mods" => "".to_owned(),
+        _ => applied.to_owned(),
+    }
+}
+
+/// Points everything in the history at a mod folder's new name
+pub fn rename_history(from: &str, to: &str) {
+    // paths inside the mod follow along too
+    let fix = |dir: &mut String| {
+        if dir == from {
+            *dir = to.to_owned();
+        } else if let Ok(rest) = Path::new(dir.as_str()).strip_prefix(from) {
+            *dir = Path::new(to).join(rest).display().to_string();
+        }
+    };
+    let fix_list = |list: &mut Vec<ModPackMod>| {
+        for entry in list
+            .iter_mut()
+            .filter(|m| !m.separator && m.stored_dir() == from)
+        {
+            let mut buf = PathBuf::from(&entry.path);
+            buf.pop();
+            buf.set_file_name(to);
+            entry.path = buf.join("config.toml").display().to_string();
+        }
+    };
+    let Ok(mut history) = HISTORY.lock() else {
+        return;
+    };
+    let history = &mut *history;
+    for entry in history.done.iter_mut().chain(history.undone.iter_mut()) {
+        for change in entry.changes.iter_mut() {
+            match change {
+                Change::Order { before, after, .. } => {
+                    fix_list(before);
+                    fix_list(after);
+                }
+                Change::Enabled { dir, .. }
+                | Change::Added { dir, .. }
+                | Change::Removed { dir, .. } => fix(dir),
+                Change::Renamed {
+                    from: before,
+                    to: after,
+                }
+                | Change::Moved {
+                    from: before,
+                    to: after,
+                } => {
+                    fix(before);
+                    fix(after);
+                }
+                Change::PackDeleted { pack } | Change::PackCreated { pack } => {
+                    fix_list(&mut pack.mods)
+                }
+                Change::PackRenamed { .. } | Change::PackParent { .. } => {}
+            }
+        }
+    }
+}
+
+/// Points everything in the history at a pack's new name
+pub fn rename_pack_history(from: &str, to: &str) {
+    let fix = |name: &mut String| {
+        if name == from {
+            *name = to.to_owned();
+        }
+    };
+    let Ok(mut history) = HISTORY.lock() else {
+        return;
+    };
+    let history = &mut *history;
+    for entry in history.done.iter_mut().chain(history.undone.iter_mut()) {
+        for change in entry.changes.iter_mut() {
+            match change {
+                Change::Order { pack, .. } => fix(pack),
+                Change::PackDeleted { pack } | Change::PackCreated { pack } => {
+                    fix(&mut pack.name);
+                    fix(&mut pack.parent);
+                }
+                Change::PackRenamed {
+                    from: before,
+                    to: after,
+                } => {
+                    fix(before);
+                    fix(after);
+                }
+                Change::PackParent {
+                    pack,
+                    before,
+                    after,
+                } => {
+                    fix(pack);
+                    fix(before);
+                    fix(after);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Where deleted mods go so they can be brought back. It's a hidden folder next to the mods
+/// folder so it's on the same drive and deleting is only a rename, made when something first
+/// goes in
+pub fn get_trash_folder() -> Option<PathBuf> {
+    Some(get_mods_folder()?.parent()?.join(TRASH_FOLDER))
+}
+
+/// When a trash entry went in, from the stamp at the start of its name
+fn trashed_at(name: &str) -> Option<SystemTime> {
+    let millis = name.split_once('-')?.0.parse::<u64>().ok()?;
+    Some(UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+/// Removes trash entries older than `TRASH_MAX_AGE`, history doesn't outlive a session so
+/// nothing points at them anymore
+fn clean_trash() {
+    let Some(trash) = get_trash_folder().filter(|t| t.exists()) else {
+        return;
+    };
+    let Ok(entries) = fs::read_dir(&trash) else {
+        return;
+    };
+    let cutoff = SystemTime::now() - TRASH_MAX_AGE;
+    for entry in entries.flatten() {
+        let old = trashed_at(&entry.file_name().to_string_lossy()).is_some_and(|t| t < cutoff);
+        if old {
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                eprintln!(
+                    "Unable to clear {} from the trash: {e}",
+                    entry.path().display()
+                );
+            }
+        }
+    }
+}
+
+/// Whether undoing or redoing `entry` needs a folder that's sitting in the trash
+fn uses_trash(entry: &Entry) -> bool {
+    entry.changes.iter().any(|c| match c {
+        Change::Added { trash, .. } | Change::Removed { trash, .. } => trash.exists(),
+        _ => false,
+    })
+}
+
+/// Deletes everything in the trash for good. Entries that needed something from it can't be
+/// undone or redone anymore, so they go along with everything before them
+fn empty_trash() -> Result<(), String> {
+    if let Ok(mut history) = HISTORY.lock() {
+        if let Some(last) = history.done.iter().rposition(uses_trash) {
+            history.done.drain(..=last);
+        }
+        if let Some(last) = history.undone.iter().rposition(uses_trash) {
+            history.undone.drain(..=last);
+        }
+    }
+    notify();
+    match get_trash_folder() {
+        Some(trash) if trash.exists() => {
+            fs::remove_dir_all(&trash).map_err(|e| format!("Unable to empty the trash: {e}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Forgets everything, for when the mods folder itself moves
+pub fn clear_history() {
+    if let Ok(mut history) = HISTORY.lock() {
+        *history = History::default();
+    }
+    notify();
+}
+
+fn new_trash_path(dir: &str) -> Option<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let trash = get_trash_folder()?;
+    fs::create_dir_all(&trash).ok()?;
+    let name = Path::new(dir).file_name()?.to_string_lossy().to_string();
+    // several files with the same name can go in at once
+    let mut path = trash.join(format!("{stamp}-{name}"));
+    let mut n = 1;
+    while fs::symlink_metadata(&path).is_ok() {
+        path = trash.join(format!("{stamp}-{n}-{name}"));
+        n += 1;
+    }
+    Some(path)
+}
+
+fn mod_path(dir: &str) -> Result<PathBuf, String> {
+    match get_mods_folder() {
+        Some(mods) => Ok(mods.join(dir)),
+        None => Err("Unable to find the mods folder".to_owned()),
+    }
+}
+
+/// Deletes a mod folder by moving it into the trash, returns the change to record. `dir` can
+/// also point at a file or folder inside a mod.
+/// When it can't be moved into the trash (the mods folder is a drive of its own) it's deleted for
+/// good with a warning, there's nothing to undo then
+pub fn trash_mod(dir: &str) -> Result<Option<Change>, String> {
+    let path = mod_path(dir)?;
+    let moved = match new_trash_path(dir) {
+        Some(trash) => move_folder(&path, &trash).map(|_| trash),
+        None => Err("Unable to create the trash folder".to_owned()),
+    };
+    match moved {
+        Ok(trash) => Ok(Some(Change::Removed {
+            dir: dir.to_owned(),
+            trash,
+        })),
+        Err(e) => {
+            let removed = match fs::symlink_metadata(&path) {
+                Ok(meta) if meta.is_dir() => fs::remove_dir_all(&path),
+                _ => fs::remove_file(&path),
+            };
+            removed.map_err(|e| format!("Unable to delete {}: {e}", path.display()))?;
+            open_error_window(format!(
+                "{dir} couldn't go in the trash so it was deleted for good, it can't be \
+                 brought back: \n{e}"
+            ));
+            Ok(None)
+        }
+    }
+}
+
+/// Moves something inside the mods folder, returns the change to record
+pub fn move_mod_path(from: &str, to: &str) -> Result<Change, String> {
+    move_folder(&mod_path(from)?, &mod_path(to)?)?;
+    Ok(Change::Moved {
+        from: from.to_owned(),
+        to: to.to_owned(),
+    })
+}
+
+/// A freshly installed mod, undoing moves it into the trash
+pub fn installed_change(dir: &str) -> Option<Change> {
+    Some(Change::Added {
+        dir: dir.to_owned(),
+        trash: new_trash_path(dir)?,
+    })
+}
+
+/// Only ever a rename, the trash is next to the mods folder so nothing has to be copied
+fn move_folder(from: &Path, to: &Path) -> Result<(), String> {
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(format!("{} already exists", to.display()));
+    }
+    fs::rename(from, to).map_err(|e| format!("Unable to move {}: {e}", from.display()))
+}
+
+fn set_order(pack: &str, list: &[ModPackMod]) -> Result<(), String> {
+    if pack.is_empty() {
+        let mut cfg = R4D_CFG.try_lock().map_err(|e| e.to_string())?;
+        cfg.priority = list.to_vec();
+        write_config_sync(cfg.clone()).map_err(|e| e.to_string())
+    } else {
+        let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+        let modpack = packs
+            .get_mut(pack)
+            .ok_or(format!("There's no pack called {pack}"))?;
+        modpack.mods = list.to_vec();
+        save_modpack_sync(modpack.clone()).map_err(|e| e.to_string())
+    }
+}
+
+fn set_pack_parent(pack: &str, parent: &str) -> Result<(), String> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let pack = set_parent(&mut packs, pack, parent)?;
+    save_modpack_sync(pack).map_err(|e| e.to_string())
+}
+
+fn set_enabled(dir: &str, enabled: bool) -> Result<(), String> {
+    let mut mods = MODS.try_lock().map_err(|e| e.to_string())?;
+    if let Some(m) = mods.get_mut(dir) {
+        m.config["enabled"] = value(enabled);
+        save_mod_config(PathBuf::from(m.path.clone()), &m.config).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn set_pack_deleted(pack: &ModPack, deleted: bool) -> Result<(), String> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    if deleted {
+        packs.remove(&pack.name);
+        let mut buf = get_modpacks_folder().map_err(|e| e.to_string())?;
+        buf.push(format!(
+            "{}.json",
+            filenamify::filenamify(pack.name.clone())
+        ));
+        fs::remove_file(buf).map_err(|e| e.to_string())
+    } else {
+        packs.insert(pack.name.clone(), pack.clone());
+        save_modpack_sync(pack.clone()).map_err(|e| e.to_string())
+    }
+}
+
+/// Puts a change into its before (`undo`) or after state
+fn apply(change: &Change, undo: bool) -> Result<(), String> {
+    match change {
+        Change::Order {
+            pack,
+            before,
+            after,
+        } => set_order(pack, if undo { before } else { after }),
+        Change::Enabled { dir, before, after } => {
+            set_enabled(dir, if undo { *before } else { *after })
+        }
+        Change::Added { dir, trash } => match undo {
+            true => move_folder(&mod_path(dir)?, trash),
+            false => move_folder(trash, &mod_path(dir)?),
+        },
+        Change::Removed { dir, trash } => match undo {
+            true => move_folder(trash, &mod_path(dir)?),
+            false => move_folder(&mod_path(dir)?, trash),
+        },
+        Change::PackDeleted { pack } => set_pack_deleted(pack, !undo),
+        Change::PackCreated { pack } => set_pack_deleted(pack, undo),
+        Change::PackRenamed { from, to } => match undo {
+            true => rename_pack(to, from),
+            false => rename_pack(from, to),
+        }
+        .map_err(|e| e.to_string()),
+        Change::PackParent {
+            pack,
+            before,
+            after,
+        } => set_pack_parent(pack, if undo { before } else { after }),
+        Change::Renamed { from, to } => match undo {
+            true => rename_mod_folder(to, from),
+            false => rename_mod_folder(from, to),
+        }
+        .map_err(|e| e.to_string()),
+        Change::Moved { from, to } => match undo {
+            true => move_folder(&mod_path(to)?, &mod_path(from)?),
+            false => move_folder(&mod_path(from)?, &mod_path(to)?),
+        },
+    }
+}
+
+fn touches_folders(entry: &Entry) -> bool {
+    entry.changes.iter().any(|c| {
+        matches!(
+            c,
+            Change::Added { .. }
+                | Change::Removed { .. }
+                | Change::Renamed { .. }
+                | Change::Moved { .. }
+        )
+    })
+}
+
+/// Undoes (or redoes) the most recent entry, false when there's nothing to step or it failed.
+/// `packs` is set when it created, deleted or renamed a pack
+fn step(undo: bool, packs: &mut bool) -> bool {
+    let entry = match HISTORY.lock() {
+        Ok(mut history) => match undo {
+            true => history.done.pop(),
+            false => history.undone.pop(),
+        },
+        Err(_) => None,
+    };
+    let Some(entry) = entry else {
+        return false;
+    };
+    let changes: Vec<&Change> = match undo {
+        true => entry.changes.iter().rev().collect(),
+        false => entry.changes.iter().collect(),
+    };
+    // a change that fails puts back the ones before it, the entry stays where it was
+    let mut failed = None;
+    for (idx, change) in changes.iter().enumerate() {
+        if let Err(e) = apply(change, undo) {
+            let mut errors = vec![e];
+            for change in changes[..idx].iter().rev() {
+                if let Err(e) = apply(change, !undo) {
+                    errors.push(format!("and putting it back failed: {e}"));
+                }
+            }
+            failed = Some(errors);
+            break;
+        }
+    }
+    if let Some(errors) = &failed {
+        let what = if undo { "undo" } else { "redo" };
+        open_error_window(format!(
+            "Couldn't {what} \"{}\": \n{}",
+            entry.label,
+            errors.join("\n")
+        ));
+    }
+    if touches_folders(&entry) {
+        if let Err(e) = load_mods() {
+            eprintln!("{e}");
+        }
+    }
+    *packs |= entry.changes.iter().any(|c| {
+        matches!(
+            c,
+            Change::PackDeleted { .. } | Change::PackCreated { .. } | Change::PackRenamed { .. }
+        )
+    });
+    let ok = failed.is_none();
+    if let Ok(mut history) = HISTORY.lock() {
+        match (undo, ok) {
+            (true, true) | (false, false) => history.undone.push(entry),
+            (false, true) | (true, false) => history.done.push(entry),
+        }
+    }
+    notify();
+    ok
+}
+
+/// Set while `step_to` is busy so clicking again in the meantime doesn't start another
+static STEPPING: AtomicBool = AtomicBool::new(false);
+
+/// Undoes or redoes until `target` (given how many are done now) entries are done, a step that
+/// fails stops it there. Folders get moved around so it runs on the blocking pool, the tables
+/// are reloaded once at the end
+fn step_to(ui: &App, target: impl FnOnce(usize) -> usize + Send + 'static) {
+    if STEPPING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let weak = ui.as_weak();
+    tokio::task::spawn_blocking(move || {
+        let done = || HISTORY.lock().map(|h| h.done.len()).ok();
+        let mut packs = false;
+        if let Some(start) = done() {
+            let target = target(start);
+            while let Some(done) = done() {
+                if done == target || !step(done > target, &mut packs) {
+                    break;
+                }
+            }
+        }
+        STEPPING.store(false, Ordering::SeqCst);
+        let _ = weak.upgrade_in_event_loop(move |ui| {
+            if packs {
+                refresh_pack_list(&ui);
+            }
+            let _ = set_mods_table(&get_mods(), ui.as_weak());
+            // renaming the applied pack changes what it's called
+            let applied = match R4D_CFG.try_lock() {
+                Ok(cfg) if !cfg.applied_pack.is_empty() => cfg.applied_pack.clone().into(),
+                _ => ui.get_active_pack(),
+            };
+            ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+        });
+    });
+}
+
+fn undo(ui: &App) {
+    step_to(ui, |done| done.saturating_sub(1));
+}
+
+fn redo(ui: &App) {
+    step_to(ui, |done| done + 1);
+}
+
+/// Oldest first, undone entries come after the rest
+fn history_items() -> Vec<(String, bool)> {
+    match HISTORY.lock() {
+        Ok(history) => history
+            .done
+            .iter()
+            .map(|e| (format!("{}  {}", e.time, e.label), false))
+            .chain(
+                history
+                    .undone
+                    .iter()
+                    .rev()
+                    .map(|e| (format!("{}  {}", e.time, e.label), true)),
+            )
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Updates the undo/redo buttons and the history window if it's open
+fn notify() {
+    let (undo, redo) = match HISTORY.lock() {
+        Ok(history) => (
+            history.done.last().map(|e| e.label.clone()),
+            history.undone.last().map(|e| e.label.clone()),
+        ),
+        Err(_) => return,
+    };
+    if let Ok(main) = MAIN_WINDOW.lock() {
+        if let Some(main) = main.as_ref() {
+            let _ = main.upgrade_in_event_loop(move |ui| {
+                let logic = ui.global::<ModLogic>();
+                logic.set_can_undo(undo.is_some());
+                logic.set_can_redo(redo.is_some());
+                logic.set_undo_label(undo.unwrap_or_default().into());
+                logic.set_redo_label(redo.unwrap_or_default().into());
+            });
+        }
+    }
+    let items = history_items();
+    if let Ok(window) = HISTORY_WINDOW.lock() {
+        if let Some(window) = window.as_ref() {
+            let _ = window.upgrade_in_event_loop(move |window| {
+                let items: Vec<HistoryItem> = items
+                    .into_iter()
+                    .map(|(label, undone)| HistoryItem {
+                        label: label.into(),
+                        undone,
+                    })
+                    .collect();
+                window.set_items(ModelRc::new(VecModel::from(items)));
+            });
+        }
+    }
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    if let Ok(mut main) = MAIN_WINDOW.lock() {
+        *main = Some(ui.as_weak());
+    }
+    clean_trash();
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_undo(move || {
+        undo(&weak.unwrap());
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_redo(move || {
+        redo(&weak.unwrap());
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_history(move || {
+        let ui = weak.unwrap();
+        let window = match HistoryWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        if let Ok(mut history_window) = HISTORY_WINDOW.lock() {
+            *history_window = Some(window.as_weak());
+        }
+        notify();
+
+        let main_weak = weak.clone();
+        window.on_undo(move || {
+            undo(&main_weak.unwrap());
+        });
+
+        let main_weak = weak.clone();
+        window.on_redo(move || {
+            redo(&main_weak.unwrap());
+        });
+
+        // clicking an entry undoes or redoes everything up to and including it
+        let main_weak = weak.clone();
+        window.on_jump(move |idx| {
+            let target = idx as usize + 1;
+            step_to(&main_weak.unwrap(), move |_| target);
+        });
+
+        window.on_empty_trash(|| {
+            tokio::task::spawn_blocking(|| {
+                if let Err(e) = empty_trash() {
+                    open_error_window(e);
+                }
+            });
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            if let Ok(mut history_window) = HISTORY_WINDOW.lock() {
+                *history_window = None;
+            }
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use sonic_rs::{Deserialize, Serialize};
+
+use crate::modpacks::{ModPack, ModPackMod};
+
+/// How deep packs can be layered, mostly so a hand edited file can't loop forever
+const MAX_DEPTH: usize = 16;
+
+/// What a layered pack does to its parent's list. Only ever written to disk, in memory the pack
+/// keeps its resolved list in `mods` and the parent's list it was resolved against in `inherited`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PackLayer {
+    /// added, moved or toggled entries, in the order they show up in the resolved list
+    #[serde(default)]
+    pub changed: Vec<LayerEntry>,
+    /// parent entries this pack leaves out
+    #[serde(default)]
+    pub removed: Vec<ModPackMod>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LayerEntry {
+    pub entry: ModPackMod,
+    /// added and moved entries go right after `after`, or to the top when it's None.
+    /// Entries that only toggle something stay wherever the parent has them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub placed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<ModPackMod>,
+}
+
+/// Indexes into the layered list of the biggest set of shared entries that are already in the
+/// parent's order, everything else shared counts as moved.
+/// `shared` is (index in the list, index in the parent) in list order
+fn in_order(shared: &[(usize, usize)]) -> HashSet<usize> {
+    let n = shared.len();
+    let mut len = vec![1; n];
+    let mut prev: Vec<Option<usize>> = vec![None; n];
+    for i in 0..n {
+        for j in 0..i {
+            if shared[j].1 < shared[i].1 && len[j] + 1 > len[i] {
+                len[i] = len[j] + 1;
+                prev[i] = Some(j);
+            }
+        }
+    }
+    let mut kept = HashSet::new();
+    let mut at = (0..n).max_by_key(|i| len[*i]);
+    while let Some(i) = at {
+        kept.insert(shared[i].0);
+        at = prev[i];
+    }
+    kept
+}
+
+impl PackLayer {
+    /// The changes that turn `base` into `list`
+    pub fn between(base: &[ModPackMod], list: &[ModPackMod]) -> Self {
+        let removed = base.iter().filter(|m| !list.contains(m)).cloned().collect();
+        let shared: Vec<(usize, usize)> = list
+            .iter()
+            .enumerate()
+            .filter_map(|(i, m)| base.iter().position(|b| b == m).map(|p| (i, p)))
+            .collect();
+        let kept = in_order(&shared);
+        let mut changed = vec![];
+        for (i, entry) in list.iter().enumerate() {
+            let placed = !kept.contains(&i);
+            let toggled = base
+                .iter()
+                .find(|b| *b == entry)
+                .is_some_and(|b| b.enabled != entry.enabled || b.collapsed != entry.collapsed);
+            if placed || toggled {
+                changed.push(LayerEntry {
+                    entry: entry.clone(),
+                    placed,
+                    after: match placed && i > 0 {
+                        true => Some(list[i - 1].clone()),
+                        false => None,
+                    },
+                });
+            }
+        }
+        Self { changed, removed }
+    }
+
+    /// `base` with the layer's changes made to it
+    pub fn apply(&self, base: &[ModPackMod]) -> Vec<ModPackMod> {
+        let mut list: Vec<ModPackMod> = base
+            .iter()
+            .filter(|m| !self.removed.contains(m))
+            .cloned()
+            .collect();
+        for change in self.changed.iter() {
+            let at = list.iter().position(|m| *m == change.entry);
+            if !change.placed {
+                // the parent dropped it since, nothing left to toggle
+                if let Some(at) = at {
+                    list[at].enabled = change.entry.enabled;
+                    list[at].collapsed = change.entry.collapsed;
+                }
+                continue;
+            }
+            if let Some(at) = at {
+                list.remove(at);
+            }
+            let to = match &change.after {
+                Some(after) => list
+                    .iter()
+                    .position(|m| m == after)
+                    .map(|p| p + 1)
+                    .unwrap_or(list.len()),
+                None => 0,
+            };
+            list.insert(to, change.entry.clone());
+        }
+        list
+    }
+
+    /// `ModPackMod::refresh_identity` for everything the layer points at
+    pub fn refresh_identity(&mut self, mods_folder: Option<&Path>) -> bool {
+        let mut changed = false;
+        for m in self.removed.iter_mut() {
+            changed |= m.refresh_identity(mods_folder);
+        }
+        for change in self.changed.iter_mut() {
+            changed |= change.entry.refresh_identity(mods_folder);
+            if let Some(after) = change.after.as_mut() {
+                changed |= after.refresh_identity(mods_folder);
+            }
+        }
+        changed
+    }
+}
+
+fn rebase_at(packs: &mut HashMap<String, ModPack>, name: &str, depth: usize) {
+    let parent = match packs.get(name) {
+        Some(pack) if !pack.parent.is_empty() => pack.parent.clone(),
+        _ => return,
+    };
+    if depth < MAX_DEPTH {
+        rebase_at(packs, &parent, depth + 1);
+    }
+    // a parent that's gone leaves only what the layer adds
+    let parent_mods = packs
+        .get(&parent)
+        .map(|p| p.mods.clone())
+        .unwrap_or_default();
+    let Some(pack) = packs.get_mut(name) else {
+        return;
+    };
+    // freshly loaded packs come with their layer, after that it's whatever they changed last time
+    let layer = pack
+        .layer
+        .take()
+        .unwrap_or_else(|| PackLayer::between(&pack.inherited, &pack.mods));
+    pack.mods = layer.apply(&parent_mods);
+    pack.inherited = parent_mods;
+}
+
+/// Resolves a layered pack (and whatever it's layered on) against the current parent lists, so
+/// `mods` picks up changes made to the packs underneath
+pub fn rebase(packs: &mut HashMap<String, ModPack>, name: &str) {
+    rebase_at(packs, name, 0);
+}
+
+/// Layers `name` on `parent`, or makes it a normal pack again when `parent` is empty.
+/// The pack's list stays the same, only what it's compared against changes
+pub fn set_parent(
+    packs: &mut HashMap<String, ModPack>,
+    name: &str,
+    parent: &str,
+) -> Result<ModPack, String> {
+    let mut inherited = vec![];
+    if !parent.is_empty() {
+        let mut at = parent.to_owned();
+        for _ in 0..MAX_DEPTH {
+            if at == name {
+                return Err(format!("{parent} is already layered on {name}"));
+            }
+            match packs.get(&at) {
+                Some(pack) if !pack.parent.is_empty() => at = pack.parent.clone(),
+                _ => break,
+            }
+        }
+        rebase(packs, parent);
+        inherited = packs
+            .get(parent)
+            .ok_or(format!("There's no pack called {parent}"))?
+            .mods
+            .clone();
+    }
+    rebase(packs, name);
+    let pack = packs
+        .get_mut(name)
+        .ok_or(format!("There's no pack called {name}"))?;
+    pack.parent = parent.to_owned();
+    pack.inherited = inherited;
+    Ok(pack.clone())
+}
+
+/// Packs layered directly on `name`
+pub fn children_of(packs: &HashMap<String, ModPack>, name: &str) -> Vec<String> {
+    packs
+        .values()
+        .filter(|p| p.parent == name)
+        .map(|p| p.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A leading `-` means disabled
+    fn list(names: &[&str]) -> Vec<ModPackMod> {
+        names
+            .iter()
+            .map(|name| ModPackMod {
+                name: name.trim_start_matches('-').to_owned(),
+                enabled: !name.starts_with('-'),
+                path: String::new(),
+                separator: false,
+                collapsed: false,
+                id: String::new(),
+            })
+            .collect()
+    }
+
+    fn names(list: &[ModPackMod]) -> Vec<String> {
+        list.iter()
+            .map(|m| match m.enabled {
+                true => m.name.clone(),
+                false => format!("-{}", m.name),
+            })
+            .collect()
+    }
+
+    fn round_trip(base: &[&str], layered: &[&str]) -> PackLayer {
+        let (base, layered) = (list(base), list(layered));
+        let layer = PackLayer::between(&base, &layered);
+        assert_eq!(names(&layer.apply(&base)), names(&layered));
+        layer
+    }
+
+    #[test]
+    fn same_list_is_an_empty_layer() {
+        let layer = round_trip(&["a", "b", "c"], &["a", "b", "c"]);
+        assert!(layer.changed.is_empty());
+        assert!(layer.removed.is_empty());
+    }
+
+    #[test]
+    fn changes_survive_a_round_trip() {
+        round_trip(&["a", "b", "c"], &["x", "a", "b", "c"]);
+        round_trip(&["a", "b", "c"], &["a", "x", "b", "y", "c", "z"]);
+        round_trip(&["a", "b", "c"], &["a", "c"]);
+        round_trip(&["a", "b", "c"], &["c", "b", "a"]);
+        round_trip(&["a", "b", "c", "d"], &["b", "-d", "x", "a"]);
+        round_trip(&["a", "b"], &[]);
+        round_trip(&[], &["a", "b"]);
+    }
+
+    #[test]
+    fn only_what_changed_is_kept() {
+        let layer = round_trip(&["a", "b", "c", "d"], &["a", "-b", "d", "c"]);
+        assert_eq!(layer.changed.len(), 2);
+        let toggled = &layer.changed[0];
+        assert_eq!(toggled.entry.name, "b");
+        assert!(!toggled.placed && !toggled.entry.enabled);
+        // a, b, c is still in the parent's order so it's d that moved
+        let moved = &layer.changed[1];
+        assert!(moved.placed);
+        assert_eq!(moved.entry.name, "d");
+        assert_eq!(moved.after.as_ref().map(|m| m.name.as_str()), Some("b"));
+        assert!(layer.removed.is_empty());
+    }
+
+    #[test]
+    fn parent_changes_come_through() {
+        let layer = PackLayer::between(&list(&["a", "b", "c"]), &list(&["x", "a", "-c"]));
+        // the parent got a new mod and lost one the layer toggles
+        let parent = list(&["a", "b", "n"]);
+        assert_eq!(names(&layer.apply(&parent)), ["x", "a", "n"]);
+    }
+
+    #[test]
+    fn lost_anchor_goes_to_the_end() {
+        let layer = PackLayer::between(&list(&["a", "b", "c"]), &list(&["a", "b", "x", "c"]));
+        assert_eq!(names(&layer.apply(&list(&["a", "c"]))), ["a", "c", "x"]);
+    }
+
+    #[test]
+    fn layers_cant_loop() {
+        let mut packs = HashMap::new();
+        for (name, parent) in [("base", ""), ("mid", "base"), ("top", "mid")] {
+            let mut pack = ModPack::new(name.to_owned());
+            pack.mods = list(&["a", "b"]);
+            pack.parent = parent.to_owned();
+            packs.insert(name.to_owned(), pack);
+        }
+        assert!(set_parent(&mut packs, "base", "top").is_err());
+        assert!(set_parent(&mut packs, "base", "base").is_err());
+        assert!(set_parent(&mut packs, "top", "missing").is_err());
+        assert_eq!(children_of(&packs, "base"), ["mid"]);
+    }
+}
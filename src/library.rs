@@ -0,0 +1,406 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rfd::AsyncFileDialog;
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel, Weak};
+use tokio::sync::broadcast;
+use toml_edit::DocumentMut;
+
+use crate::config::write_config_sync;
+use crate::diva::{get_diva_folder, open_error_window};
+use crate::modmanagement::{get_mods, get_mods_folder, load_mods, set_mods_table};
+use crate::slint_generatedApp::App;
+use crate::util::{copy_dir_all, symlink};
+use crate::{LibraryRow, LibraryWindow, ModpackLogic, WindowLogic, MODS, R4D_CFG};
+
+/// Left in an install's copy of a mod so we know it's linked from the library and not a real
+/// folder, DML doesn't care about it
+pub const LINK_MARKER: &str = ".rust4diva-linked";
+
+/// Row states the window knows about
+const LOCAL: i32 = 0;
+const LINKED: i32 = 1;
+const AVAILABLE: i32 = 2;
+const CONFLICT: i32 = 3;
+
+pub fn get_library_folder() -> Option<PathBuf> {
+    let dir = R4D_CFG.try_lock().ok()?.library_dir.clone();
+    match dir.is_empty() {
+        true => None,
+        false => Some(PathBuf::from(dir)),
+    }
+}
+
+/// Whether the mod folder in an install is just links into the library
+pub fn is_linked(mod_dir: &Path) -> bool {
+    mod_dir.join(LINK_MARKER).exists()
+}
+
+fn hardlink_tree(src: &Path, dest: &Path) -> io::Result<()> {
+    if !src.is_dir() {
+        return fs::hard_link(src, dest);
+    }
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        hardlink_tree(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Symlinks when the OS lets us (windows wants developer mode for that), otherwise hardlinks
+/// every file which only works when the library is on the same drive
+fn link_entry(src: &Path, dest: &Path) -> io::Result<()> {
+    symlink(src, dest).or_else(|_| hardlink_tree(src, dest))
+}
+
+/// Links a mod from the library into `mods_folder`. The config.toml is copied instead so every
+/// install keeps its own enabled state
+pub fn link_mod(library: &Path, mods_folder: &Path, dir: &str) -> io::Result<()> {
+    let src = library.join(dir);
+    let dest = mods_folder.join(dir);
+    if dest.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", dest.display()),
+        ));
+    }
+    fs::create_dir_all(&dest)?;
+    let res = (|| {
+        for entry in fs::read_dir(&src)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name == "config.toml" {
+                fs::copy(entry.path(), dest.join(&name))?;
+            } else {
+                link_entry(&entry.path(), &dest.join(&name))?;
+            }
+        }
+        fs::write(dest.join(LINK_MARKER), src.display().to_string())
+    })();
+    if res.is_err() {
+        // remove_dir_all doesn't follow links so the library is safe
+        let _ = fs::remove_dir_all(&dest);
+    }
+    res
+}
+
+/// Removes an install's links to a library mod, the library copy stays
+pub fn unlink_mod(mods_folder: &Path, dir: &str) -> io::Result<()> {
+    let dest = mods_folder.join(dir);
+    if !is_linked(&dest) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{dir} isn't linked from the library"),
+        ));
+    }
+    fs::remove_dir_all(dest)
+}
+
+/// Moves an installed mod into the library and links it back in its place. The folder name
+/// stays the same so packs don't notice
+pub fn add_to_library(library: &Path, mods_folder: &Path, dir: &str) -> io::Result<()> {
+    let src = mods_folder.join(dir);
+    let dest = library.join(dir);
+    if dest.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("The library already has a mod called {dir}"),
+        ));
+    }
+    fs::create_dir_all(library)?;
+    // on another drive it's copied and the original is only put aside until the links are in,
+    // so a link that fails can put it back
+    let aside = mods_folder.join(format!(".{dir}.rust4diva-old"));
+    let copied = fs::rename(&src, &dest).is_err();
+    if copied {
+        let put_aside = copy_dir_all(&src, &dest).and_then(|_| fs::rename(&src, &aside));
+        if let Err(e) = put_aside {
+            let _ = fs::remove_dir_all(&dest);
+            return Err(e);
+        }
+    }
+    if let Err(e) = link_mod(library, mods_folder, dir) {
+        let restored = match copied {
+            true => fs::rename(&aside, &src).and_then(|_| fs::remove_dir_all(&dest)),
+            false => fs::rename(&dest, &src),
+        };
+        return match restored {
+            Ok(_) => Err(e),
+            Err(restore) => Err(io::Error::other(format!(
+                "{e}, putting it back failed too: {restore}"
+            ))),
+        };
+    }
+    if copied {
+        if let Err(e) = fs::remove_dir_all(&aside) {
+            eprintln!("Unable to remove {}: {e}", aside.display());
+        }
+    }
+    Ok(())
+}
+
+fn library_mods(library: &Path) -> Vec<(String, String)> {
+    let Ok(entries) = fs::read_dir(library) else {
+        return vec![];
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.file_name().to_string_lossy().to_string();
+            let cfg = fs::read_to_string(entry.path().join("config.toml")).ok()?;
+            let name = cfg
+                .parse::<DocumentMut>()
+                .ok()
+                .and_then(|d| d.get("name").and_then(|n| n.as_str()).map(|n| n.to_owned()))
+                .filter(|n| !n.is_empty())
+                .unwrap_or(dir.clone());
+            Some((dir, name))
+        })
+        .collect()
+}
+
+/// Everything in the library or the current install with what can be done with it
+fn library_rows(library: Option<&Path>) -> Vec<LibraryRow> {
+    let mut rows: Vec<LibraryRow> = match MODS.try_lock() {
+        Ok(mods) => mods
+            .iter()
+            .map(|(dir, m)| {
+                let mut buf = PathBuf::from(m.path.clone());
+                buf.pop();
+                let in_library = library.is_some_and(|l| l.join(dir).exists());
+                LibraryRow {
+                    dir: dir.into(),
+                    name: m.config["name"].as_str().unwrap_or(dir).into(),
+                    state: match (is_linked(&buf), in_library) {
+                        (true, _) => LINKED,
+                        (false, true) => CONFLICT,
+                        (false, false) => LOCAL,
+                    },
+                }
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    if let Some(library) = library {
+        for (dir, name) in library_mods(library) {
+            if !rows.iter().any(|r| r.dir.as_str() == dir) {
+                rows.push(LibraryRow {
+                    dir: dir.into(),
+                    name: name.into(),
+                    state: AVAILABLE,
+                });
+            }
+        }
+    }
+    rows.sort_by_key(|r| r.name.to_lowercase());
+    rows
+}
+
+fn refresh_window(window: &LibraryWindow) {
+    let library = get_library_folder();
+    window.set_library_dir(
+        library
+            .as_ref()
+            .map(|l| l.display().to_string())
+            .unwrap_or_default()
+            .into(),
+    );
+    window.set_install(get_diva_folder().unwrap_or_default().into());
+    let rows = library_rows(library.as_deref());
+    window.set_rows(ModelRc::new(VecModel::from(rows)));
+}
+
+fn reload_main(ui: &App) {
+    let _ = set_mods_table(&get_mods(), ui.as_weak());
+    let applied = ui.get_active_pack();
+    ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+}
+
+/// Runs something against the library and the current mods folder, then reloads everything.
+/// Copying into the library can take a while so it runs on the blocking pool
+fn with_folders<F>(ui: Weak<App>, window: Weak<LibraryWindow>, f: F)
+where
+    F: FnOnce(&Path, &Path) -> Vec<String> + Send + 'static,
+{
+    let (Some(library), Some(mods)) = (get_library_folder(), get_mods_folder()) else {
+        open_error_window("Pick a library folder first".to_owned());
+        return;
+    };
+    tokio::task::spawn_blocking(move || {
+        let errors = f(&library, &mods);
+        if !errors.is_empty() {
+            open_error_window(errors.join("\n"));
+        }
+        if let Err(e) = load_mods() {
+            eprintln!("{e}");
+        }
+        let _ = ui.upgrade_in_event_loop(|ui| reload_main(&ui));
+        let _ = window.upgrade_in_event_loop(|window| refresh_window(&window));
+    });
+}
+
+fn set_library_dir(dir: PathBuf) -> Result<(), String> {
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let mut cfg = R4D_CFG.try_lock().map_err(|e| e.to_string())?;
+    cfg.library_dir = dir.display().to_string();
+    write_config_sync(cfg.clone()).map_err(|e| e.to_string())
+}
+
+fn pick_library(window: Weak<LibraryWindow>) {
+    let picker = AsyncFileDialog::new().set_title("Pick a folder for the shared library");
+    let picker = match get_library_folder() {
+        Some(dir) => picker.set_directory(dir),
+        None => picker,
+    };
+    tokio::spawn(async move {
+        let Some(dir) = picker.pick_folder().await else {
+            return;
+        };
+        let dir = dir.path().to_path_buf();
+        let _ = window.upgrade_in_event_loop(move |window| match set_library_dir(dir) {
+            Ok(_) => refresh_window(&window),
+            Err(e) => open_error_window(format!("Unable to use that folder: \n{e}")),
+        });
+    });
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_library(move || {
+        let ui = weak.unwrap();
+        let window = match LibraryWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        refresh_window(&window);
+
+        let window_weak = window.as_weak();
+        window.on_pick_folder(move || {
+            pick_library(window_weak.clone());
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_link(move |dir| {
+            with_folders(
+                main_weak.clone(),
+                window_weak.clone(),
+                move |library, mods| match link_mod(library, mods, &dir) {
+                    Ok(_) => vec![],
+                    Err(e) => vec![format!("Unable to link {dir}: {e}")],
+                },
+            );
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_unlink(move |dir| {
+            with_folders(
+                main_weak.clone(),
+                window_weak.clone(),
+                move |_, mods| match unlink_mod(mods, &dir) {
+                    Ok(_) => vec![],
+                    Err(e) => vec![format!("Unable to unlink {dir}: {e}")],
+                },
+            );
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_add_to_library(move |dir| {
+            with_folders(
+                main_weak.clone(),
+                window_weak.clone(),
+                move |library, mods| match add_to_library(library, mods, &dir) {
+                    Ok(_) => vec![],
+                    Err(e) => vec![format!("Unable to move {dir} into the library: {e}")],
+                },
+            );
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_link_all(move || {
+            let dirs: Vec<SharedString> = library_rows(get_library_folder().as_deref())
+                .into_iter()
+                .filter(|r| r.state == AVAILABLE)
+                .map(|r| r.dir)
+                .collect();
+            with_folders(
+                main_weak.clone(),
+                window_weak.clone(),
+                move |library, mods| {
+                    dirs.iter()
+                        .filter_map(|dir| {
+                            link_mod(library, mods, dir)
+                                .err()
+                                .map(|e| format!("Unable to link {dir}: {e}"))
+                        })
+                        .collect()
+                },
+            );
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn added_mods_are_linked_back() {
+        let base = std::env::temp_dir().join(format!("rust4diva-library-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (library, mods) = (base.join("library"), base.join("mods"));
+        fs::create_dir_all(mods.join("song/rom")).unwrap();
+        fs::write(mods.join("song/config.toml"), "name = \"Song\"").unwrap();
+        fs::write(mods.join("song/rom/pv_db.txt"), "pv").unwrap();
+
+        add_to_library(&library, &mods, "song").unwrap();
+        assert!(is_linked(&mods.join("song")));
+        assert!(!is_linked(&library.join("song")));
+        assert_eq!(
+            fs::read_to_string(mods.join("song/rom/pv_db.txt")).unwrap(),
+            "pv"
+        );
+        assert_eq!(
+            library_mods(&library),
+            vec![("song".to_owned(), "Song".to_owned())]
+        );
+        // nothing is left lying around in the mods folder
+        assert_eq!(fs::read_dir(&mods).unwrap().count(), 1);
+
+        let err = add_to_library(&library, &mods, "song").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(is_linked(&mods.join("song")));
+    }
+}
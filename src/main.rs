@@ -20,19 +20,41 @@ use crate::diva::{create_tmp_if_not, find_diva_folder, open_error_window};
 use crate::modmanagement::{
     get_mods, load_diva_ml_config, load_mods, set_mods_table, DivaMod, DivaModLoader,
 };
+use crate::metadata::ModMeta;
 use crate::modpacks::ModPack;
 use crate::oneclick::{spawn_listener, try_send_mmdl};
 
+mod autosort;
 mod config;
 mod diva;
 mod divamodarchive;
 mod downloads;
+mod duplicates;
+mod export;
 mod firstlaunch;
 mod gamebanana;
+mod groups;
+mod history;
 mod language;
+mod layers;
+mod library;
+mod metadata;
+mod moddetails;
 mod modmanagement;
 mod modpacks;
 mod oneclick;
+mod packfile;
+mod packcheck;
+mod packdiff;
+mod packops;
+mod pathcase;
+mod plugins;
+mod query;
+mod resolve;
+mod revisions;
+mod selection;
+mod storage;
+mod tags;
 mod util;
 
 slint::include_modules!();
@@ -43,7 +65,6 @@ pub static DIVA_DIR: LazyLock<Mutex<String>> = LazyLock::new(|| {
     let str = String::new();
     Mutex::new(str)
 });
-pub static MODS_DIR: LazyLock<Mutex<String>> = LazyLock::new(|| Mutex::new("mods".to_string()));
 
 /// Global config object
 pub static R4D_CFG: LazyLock<Mutex<DivaConfig>> = LazyLock::new(|| Mutex::new(DivaConfig::new()));
@@ -52,6 +73,10 @@ pub static R4D_CFG: LazyLock<Mutex<DivaConfig>> = LazyLock::new(|| Mutex::new(Di
 pub static MOD_PACKS: LazyLock<Mutex<HashMap<String, ModPack>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// Rust4Diva's own info about each mod key'd by the mod's dir name
+pub static MOD_META: LazyLock<Mutex<HashMap<String, ModMeta>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
 pub static DML_CFG: LazyLock<Mutex<DivaModLoader>> = LazyLock::new(|| {
     let mut cfg = None;
     if let Ok(dir) = DIVA_DIR.lock() {
@@ -161,6 +186,7 @@ async fn main() -> std::result::Result<(), Box<dyn Error>> {
             open_error_window(msg);
         }
     }
+    metadata::init();
     let _ = load_mods();
     let _ = set_mods_table(&get_mods(), app_weak.clone());
     if is_dml_installed() {
@@ -176,9 +202,27 @@ async fn main() -> std::result::Result<(), Box<dyn Error>> {
     config::init_ui(&app, dark_tx).await;
     modmanagement::init(&app, dark_rx.resubscribe()).await;
     modpacks::init(&app).await;
+    groups::init(&app).await;
+    selection::init(&app).await;
     gamebanana::init(&app, dark_rx.resubscribe()).await;
     divamodarchive::init(&app, dark_rx.resubscribe()).await;
     downloads::init(&app, dark_rx.resubscribe()).await;
+    plugins::init(&app, dark_rx.resubscribe()).await;
+    pathcase::init(&app, dark_rx.resubscribe()).await;
+    duplicates::init(&app, dark_rx.resubscribe()).await;
+    storage::init(&app, dark_rx.resubscribe()).await;
+    autosort::init(&app, dark_rx.resubscribe()).await;
+    history::init(&app, dark_rx.resubscribe()).await;
+    library::init(&app, dark_rx.resubscribe()).await;
+    packfile::init(&app, dark_rx.resubscribe()).await;
+    packops::init(&app, dark_rx.resubscribe()).await;
+    packdiff::init(&app, dark_rx.resubscribe()).await;
+    packcheck::init(&app, dark_rx.resubscribe()).await;
+    resolve::init(&app, dark_rx.resubscribe()).await;
+    revisions::init(&app, dark_rx.resubscribe()).await;
+    tags::init(&app, dark_rx.resubscribe()).await;
+    export::init(&app, dark_rx.resubscribe()).await;
+    moddetails::init(&app).await;
     let _ = oneclick::handle_dmm_oneclick(url_rx, app.as_weak(), dark_rx.resubscribe());
 
     println!("Does the app run?");
@@ -0,0 +1,384 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+
+use filenamify::filenamify;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::divamodarchive::DMA_DOMAIN;
+use crate::gamebanana::get_mod_url;
+use crate::library::LINK_MARKER;
+use crate::modmanagement::DivaMod;
+use crate::modpacks::get_install_config_dir;
+use crate::tags::tags_for_category;
+use crate::util::{dir_files, reqwest_client};
+use crate::{SearchPreviewData, SearchProvider, MOD_META};
+
+/// Where a mod was downloaded from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Provider {
+    GameBanana,
+    DivaModArchive,
+}
+
+impl From<SearchProvider> for Provider {
+    fn from(value: SearchProvider) -> Self {
+        match value {
+            SearchProvider::GameBanana => Provider::GameBanana,
+            SearchProvider::DivaModArchive => Provider::DivaModArchive,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModSource {
+    pub provider: Provider,
+    /// GameBanana mod id or DMA post id
+    pub id: i32,
+    #[serde(default)]
+    pub file_id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub image_url: String,
+    /// GameBanana category / DMA post type
+    #[serde(default)]
+    pub category: String,
+}
+
+impl ModSource {
+    pub fn from_preview(item: &SearchPreviewData, file_id: String) -> Self {
+        Self {
+            provider: item.provider.into(),
+            id: item.id,
+            file_id,
+            name: item.name.to_string(),
+            image_url: item.image_url.to_string(),
+            category: item.item_type.to_string(),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match self.provider {
+            Provider::GameBanana => get_mod_url(self.id),
+            Provider::DivaModArchive => format!("{DMA_DOMAIN}/post/{}", self.id),
+        }
+    }
+}
+
+/// Things we know about a mod that DML's config.toml has no place for, key'd by the mod's dir name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ModMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ModSource>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// What packs and the load order know the mod by, it stays the same when the folder is renamed
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+    /// File names and sizes from when the mod got its id, it's how a renamed folder is recognised
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub fingerprint: String,
+}
+
+/// id -> folder for everything that's installed, rebuilt by `assign_ids`
+static INSTALLED_IDS: LazyLock<Mutex<HashMap<String, String>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn get_metadata_path() -> std::io::Result<PathBuf> {
+    let mut buf = get_install_config_dir()?;
+    buf.push("metadata.json");
+    Ok(buf)
+}
+
+pub fn get_preview_cache_folder() -> std::io::Result<PathBuf> {
+    let mut buf = get_install_config_dir()?;
+    buf.push("previews");
+    if !buf.try_exists()? {
+        fs::create_dir(&buf)?;
+    }
+    Ok(buf)
+}
+
+pub fn get_cached_preview_path(dir_name: &str) -> std::io::Result<PathBuf> {
+    let mut buf = get_preview_cache_folder()?;
+    buf.push(filenamify(dir_name) + ".png");
+    Ok(buf)
+}
+
+pub fn load_metadata() -> std::io::Result<HashMap<String, ModMeta>> {
+    let path = get_metadata_path()?;
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let s = fs::read_to_string(path)?;
+    Ok(sonic_rs::from_str(&s)?)
+}
+
+pub fn save_metadata() -> std::io::Result<()> {
+    let meta = match MOD_META.try_lock() {
+        Ok(meta) => meta.clone(),
+        Err(e) => return Err(std::io::Error::other(e.to_string())),
+    };
+    let s = sonic_rs::to_string_pretty(&meta)?;
+    fs::write(get_metadata_path()?, s)
+}
+
+pub fn get_meta(dir_name: &str) -> ModMeta {
+    match MOD_META.try_lock() {
+        Ok(meta) => meta.get(dir_name).cloned().unwrap_or_default(),
+        Err(_) => ModMeta::default(),
+    }
+}
+
+/// Runs `f` on the metadata for `dir_name` and writes the store to disk
+pub fn update_meta<F: FnOnce(&mut ModMeta)>(dir_name: &str, f: F) -> std::io::Result<()> {
+    match MOD_META.try_lock() {
+        Ok(mut meta) => f(meta.entry(dir_name.to_owned()).or_default()),
+        Err(e) => return Err(std::io::Error::other(e.to_string())),
+    }
+    save_metadata()
+}
+
+/// Folder an installed mod with `id` is in
+pub fn dir_for_id(id: &str) -> Option<String> {
+    if id.is_empty() {
+        return None;
+    }
+    INSTALLED_IDS.try_lock().ok()?.get(id).cloned()
+}
+
+/// The id of the mod in `dir_name`, empty if it hasn't been given one
+pub fn mod_id(dir_name: &str) -> String {
+    match MOD_META.try_lock() {
+        Ok(meta) => meta.get(dir_name).map(|m| m.id.clone()).unwrap_or_default(),
+        Err(_) => String::new(),
+    }
+}
+
+/// What we last knew a mod's files looked like, from its old folder's metadata or its id
+pub fn known_fingerprint(dir_name: &str, id: &str) -> String {
+    let Ok(meta) = MOD_META.try_lock() else {
+        return String::new();
+    };
+    let known = meta
+        .get(dir_name)
+        .or_else(|| meta.values().find(|m| !id.is_empty() && m.id == id))
+        .map(|m| m.fingerprint.clone())
+        .unwrap_or_default();
+    match known.is_empty() {
+        // local ids are made out of the fingerprint
+        true => id
+            .strip_prefix("local-")
+            .and_then(|s| s.split('-').next())
+            .unwrap_or_default()
+            .to_owned(),
+        false => known,
+    }
+}
+
+/// Installed folder whose files match `print`
+pub fn installed_with_fingerprint(print: &str) -> Option<String> {
+    if print.is_empty() {
+        return None;
+    }
+    let ids = INSTALLED_IDS.try_lock().ok()?;
+    let meta = MOD_META.try_lock().ok()?;
+    ids.values()
+        .find(|dir| meta.get(*dir).is_some_and(|m| m.fingerprint == print))
+        .cloned()
+}
+
+/// Moves a mod's metadata and cached preview over to its new folder name
+pub fn rename_meta(from: &str, to: &str) -> std::io::Result<()> {
+    match MOD_META.try_lock() {
+        Ok(mut meta) => match meta.remove(from) {
+            Some(entry) => meta.insert(to.to_owned(), entry),
+            None => return Ok(()),
+        },
+        Err(e) => return Err(std::io::Error::other(e.to_string())),
+    };
+    if let (Ok(old), Ok(new)) = (get_cached_preview_path(from), get_cached_preview_path(to)) {
+        if old.exists() {
+            fs::rename(old, new)?;
+        }
+    }
+    save_metadata()
+}
+
+/// A lot cheaper than `hash_mod`, only file names and sizes go in. config.toml is left out since
+/// toggling the mod rewrites it
+fn fingerprint(dir: &Path) -> String {
+    let mut hasher = Sha256::new();
+    for (rel, size) in dir_files(dir) {
+        if rel == Path::new("config.toml") || rel == Path::new(LINK_MARKER) {
+            continue;
+        }
+        hasher.update(rel.to_string_lossy().replace('\\', "/").as_bytes());
+        hasher.update(size.to_le_bytes());
+    }
+    format!("{:x}", hasher.finalize())[..16].to_owned()
+}
+
+/// Where the mod came from if we know, otherwise what's in it
+fn new_id(meta: &ModMeta, fingerprint: &str, taken: &HashSet<String>) -> String {
+    let base = match &meta.source {
+        Some(source) => match source.provider {
+            Provider::GameBanana => format!("gb-{}", source.id),
+            Provider::DivaModArchive => format!("dma-{}", source.id),
+        },
+        None => format!("local-{fingerprint}"),
+    };
+    let mut id = base.clone();
+    let mut n = 2;
+    while taken.contains(&id) {
+        id = format!("{base}-{n}");
+        n += 1;
+    }
+    id
+}
+
+/// Gives every installed mod an id. A folder we've never seen is checked against the metadata of
+/// folders that are gone first, if the files match it's the same mod renamed and it keeps its id,
+/// tags and source. Ids have to be unique, imported packs can hand out ids that are already taken
+/// here, so on a clash the mod that got its id on this machine keeps it and the other gets a new one
+pub fn assign_ids(mods: &mut HashMap<String, DivaMod>) {
+    let Ok(mut meta) = MOD_META.lock() else {
+        return;
+    };
+    // ids we made ourselves come with a fingerprint, imported ones don't
+    let mut dirs: Vec<&String> = mods.keys().collect();
+    dirs.sort_by_key(|dir| {
+        let foreign = meta
+            .get(*dir)
+            .filter(|m| !m.fingerprint.is_empty())
+            .is_none();
+        (foreign, *dir)
+    });
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut clashes: HashSet<&String> = HashSet::new();
+    for dir in dirs.iter() {
+        let Some(id) = meta
+            .get(*dir)
+            .map(|m| m.id.clone())
+            .filter(|id| !id.is_empty())
+        else {
+            continue;
+        };
+        if !taken.insert(id) {
+            clashes.insert(*dir);
+        }
+    }
+    let mut changed = false;
+    for dir in dirs {
+        if clashes.contains(dir) {
+            let mut buf = PathBuf::from(&mods[dir].path);
+            buf.pop();
+            let print = fingerprint(&buf);
+            let entry = meta.entry(dir.clone()).or_default();
+            entry.id = new_id(entry, &print, &taken);
+            entry.fingerprint = print;
+            taken.insert(entry.id.clone());
+            changed = true;
+            continue;
+        }
+        if meta.get(dir).is_some_and(|m| !m.id.is_empty()) {
+            continue;
+        }
+        let mut buf = PathBuf::from(&mods[dir].path);
+        buf.pop();
+        let print = fingerprint(&buf);
+        let renamed = match meta.contains_key(dir) {
+            true => None,
+            false => meta
+                .iter()
+                .find(|(d, m)| !mods.contains_key(*d) && !m.id.is_empty() && m.fingerprint == print)
+                .map(|(d, _)| d.clone()),
+        };
+        let entry = match renamed {
+            Some(old) => {
+                if let (Ok(from), Ok(to)) =
+                    (get_cached_preview_path(&old), get_cached_preview_path(dir))
+                {
+                    let _ = fs::rename(from, to);
+                }
+                meta.remove(&old).unwrap_or_default()
+            }
+            None => {
+                let mut entry = meta.remove(dir).unwrap_or_default();
+                entry.id = new_id(&entry, &print, &taken);
+                entry.fingerprint = print;
+                entry
+            }
+        };
+        taken.insert(entry.id.clone());
+        meta.insert(dir.clone(), entry);
+        changed = true;
+    }
+    if let Ok(mut ids) = INSTALLED_IDS.lock() {
+        *ids = mods
+            .keys()
+            .filter_map(|dir| meta.get(dir).map(|m| (m.id.clone(), dir.clone())))
+            .collect();
+    }
+    for (dir, module) in mods.iter_mut() {
+        module.id = meta.get(dir).map(|m| m.id.clone()).unwrap_or_default();
+    }
+    drop(meta);
+    if changed {
+        if let Err(e) = save_metadata() {
+            eprintln!("Unable to save mod metadata: {e}");
+        }
+    }
+}
+
+/// Remembers where a freshly installed mod came from, tags it from the provider's category and
+/// caches its preview image
+pub async fn record_source(dir_name: String, source: ModSource) {
+    let image_url = source.image_url.clone();
+    let res = update_meta(&dir_name, |meta| {
+        if meta.tags.is_empty() {
+            meta.tags = tags_for_category(&source.category);
+        }
+        meta.source = Some(source);
+    });
+    if let Err(e) = res {
+        eprintln!("Unable to save mod metadata: {e}");
+    }
+    if image_url.is_empty() {
+        return;
+    }
+    if let Err(e) = cache_preview(&dir_name, image_url).await {
+        eprintln!("Unable to cache preview for {dir_name}: {e}");
+    }
+}
+
+pub async fn cache_preview(
+    dir_name: &str,
+    url: String,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = reqwest_client()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let image = image::load_from_memory(&bytes)?;
+    let path = get_cached_preview_path(dir_name)?;
+    image.save(&path)?;
+    Ok(path)
+}
+
+pub fn init() {
+    match load_metadata() {
+        Ok(meta) => {
+            if let Ok(mut gmeta) = MOD_META.lock() {
+                *gmeta = meta;
+            }
+        }
+        Err(e) => eprintln!("Unable to load mod metadata: {e}"),
+    }
+}
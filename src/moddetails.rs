@@ -0,0 +1,272 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use slint::{ComponentHandle, Rgba8Pixel, SharedPixelBuffer, Weak};
+
+use crate::metadata::{cache_preview, get_cached_preview_path, get_meta, Provider};
+use crate::modmanagement::DivaMod;
+use crate::slint_generatedApp::App;
+use crate::util::format_size;
+use crate::{ModDetails, ModLogic, MODS};
+
+const PREVIEW_STEMS: [&str; 7] = [
+    "preview",
+    "thumbnail",
+    "thumb",
+    "cover",
+    "banner",
+    "screenshot",
+    "icon",
+];
+const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "bmp"];
+const README_NAMES: [&str; 6] = [
+    "readme.md",
+    "readme.txt",
+    "readme",
+    "read me.txt",
+    "readme.rtf",
+    "info.txt",
+];
+
+/// Readmes can be huge changelogs, nobody is scrolling past this in a side pane anyway
+const README_MAX_LEN: usize = 20000;
+
+/// Plain data for the details pane, `slint::Image` can't leave the ui thread
+#[derive(Default)]
+struct Details {
+    dirname: String,
+    name: String,
+    author: String,
+    version: String,
+    description: String,
+    include: String,
+    dll: String,
+    size: String,
+    tags: String,
+    readme: String,
+    image: Option<SharedPixelBuffer<Rgba8Pixel>>,
+    image_source: String,
+    source: String,
+    source_url: String,
+}
+
+impl From<Details> for ModDetails {
+    fn from(value: Details) -> Self {
+        ModDetails {
+            dirname: value.dirname.into(),
+            name: value.name.into(),
+            author: value.author.into(),
+            version: value.version.into(),
+            description: value.description.into(),
+            include: value.include.into(),
+            dll: value.dll.into(),
+            size: value.size.into(),
+            tags: value.tags.into(),
+            readme: value.readme.into(),
+            has_image: value.image.is_some(),
+            image: value
+                .image
+                .map(slint::Image::from_rgba8)
+                .unwrap_or_default(),
+            image_source: value.image_source.into(),
+            source: value.source.into(),
+            source_url: value.source_url.into(),
+            loading: false,
+        }
+    }
+}
+
+/// Files in the root of `dir` as (lowercase name, path)
+fn root_files(dir: &Path) -> Vec<(String, PathBuf)> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|e| e.path().is_file())
+            .map(|e| (e.file_name().to_string_lossy().to_lowercase(), e.path()))
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+pub fn find_preview(mod_dir: &Path) -> Option<PathBuf> {
+    let files = root_files(mod_dir);
+    for stem in PREVIEW_STEMS {
+        for ext in IMAGE_EXTENSIONS {
+            let name = format!("{stem}.{ext}");
+            if let Some((_, path)) = files.iter().find(|(n, _)| *n == name) {
+                return Some(path.clone());
+            }
+        }
+    }
+    None
+}
+
+pub fn find_readme(mod_dir: &Path) -> Option<PathBuf> {
+    let files = root_files(mod_dir);
+    README_NAMES.iter().find_map(|name| {
+        files
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, path)| path.clone())
+    })
+}
+
+fn decode_image(path: &Path) -> Option<SharedPixelBuffer<Rgba8Pixel>> {
+    let image = match image::open(path) {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("Unable to load {}: {e}", path.display());
+            return None;
+        }
+    };
+    let image = image
+        .resize(880, 496, image::imageops::FilterType::Triangle)
+        .into_rgba8();
+    Some(SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+    ))
+}
+
+/// `decode_image` on the blocking pool, big previews take a while to decode and scale
+async fn load_image(path: PathBuf) -> Option<SharedPixelBuffer<Rgba8Pixel>> {
+    tokio::task::spawn_blocking(move || decode_image(&path))
+        .await
+        .ok()
+        .flatten()
+}
+
+fn config_str(module: &DivaMod, key: &str) -> String {
+    module
+        .config
+        .get(key)
+        .and_then(|i| i.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+fn config_list(module: &DivaMod, key: &str) -> String {
+    match module.config.get(key).and_then(|i| i.as_array()) {
+        Some(list) => list
+            .iter()
+            .filter_map(|i| i.as_str())
+            .collect::<Vec<&str>>()
+            .join(", "),
+        None => String::new(),
+    }
+}
+
+async fn load_details(module: DivaMod) -> Details {
+    let dirname = module.dir_name().unwrap_or_default();
+    let mut mod_dir = PathBuf::from(module.path.clone());
+    mod_dir.pop();
+
+    let mut details = Details {
+        dirname: dirname.clone(),
+        name: config_str(&module, "name"),
+        author: config_str(&module, "author"),
+        version: config_str(&module, "version"),
+        description: config_str(&module, "description"),
+        include: config_list(&module, "include"),
+        dll: config_list(&module, "dll"),
+        size: format!(
+            "{} in {} files",
+            format_size(module.size),
+            module.file_count
+        ),
+        ..Default::default()
+    };
+
+    if let Some(readme) = find_readme(&mod_dir) {
+        if let Ok(bytes) = fs::read(readme) {
+            let mut text = String::from_utf8_lossy(&bytes).to_string();
+            if text.len() > README_MAX_LEN {
+                let mut end = README_MAX_LEN;
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+                text.truncate(end);
+                text.push_str("\n...");
+            }
+            details.readme = text;
+        }
+    }
+
+    let meta = get_meta(&dirname);
+    details.tags = meta.tags.join(", ");
+    if let Some(source) = &meta.source {
+        let provider = match source.provider {
+            Provider::GameBanana => "GameBanana",
+            Provider::DivaModArchive => "DivaModArchive",
+        };
+        details.source = match source.category.is_empty() {
+            true => provider.to_string(),
+            false => format!("{provider} - {}", source.category),
+        };
+        details.source_url = source.url();
+    }
+
+    if let Some(preview) = find_preview(&mod_dir) {
+        details.image = load_image(preview).await;
+        details.image_source = "Mod folder".to_string();
+    }
+    if details.image.is_some() {
+        return details;
+    }
+
+    // fall back to the preview from wherever it was downloaded from
+    let Some(source) = meta.source else {
+        return details;
+    };
+    let cached = match get_cached_preview_path(&dirname) {
+        Ok(cached) => cached,
+        Err(_) => return details,
+    };
+    let cached = if cached.exists() {
+        Some(cached)
+    } else if !source.image_url.is_empty() {
+        cache_preview(&dirname, source.image_url.clone()).await.ok()
+    } else {
+        None
+    };
+    if let Some(cached) = cached {
+        details.image = load_image(cached).await;
+        details.image_source = format!("{} preview", details.source);
+    }
+    details
+}
+
+pub async fn init(ui: &App) {
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_select_mod(move |module| {
+        let ui = weak.unwrap();
+        let dirname = module.dirname.to_string();
+        let found = match MODS.try_lock() {
+            Ok(mods) => mods.get(&dirname).cloned(),
+            Err(_) => None,
+        };
+        let Some(found) = found else {
+            return;
+        };
+        ui.set_mod_details(ModDetails {
+            dirname: module.dirname.clone(),
+            name: module.name.clone(),
+            author: module.author.clone(),
+            version: module.version.clone(),
+            description: module.description.clone(),
+            loading: true,
+            ..Default::default()
+        });
+        let weak: Weak<App> = weak.clone();
+        tokio::spawn(async move {
+            let details = load_details(found).await;
+            let _ = weak.upgrade_in_event_loop(move |ui| {
+                // the selection could have moved on while we were loading
+                if ui.get_mod_details().dirname == details.dirname {
+                    ui.set_mod_details(details.into());
+                }
+            });
+        });
+    });
+}
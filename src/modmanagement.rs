@@ -1,4 +1,4 @@
-use std::cmp::{max, min};
+use std::cmp::max;
 use std::collections::HashMap;
 use std::error::Error;
 use std::ffi::OsStr;
@@ -10,20 +10,30 @@ use std::time::Duration;
 use std::{fs, io};
 
 use compress_tools::{list_archive_files, uncompress_archive, Ownership};
+use filenamify::filenamify;
 use rfd::AsyncFileDialog;
 use serde::{Deserialize, Serialize};
 use slint::private_unstable_api::re_exports::ColorScheme;
-use slint::{ComponentHandle, EventLoopError, ModelRc, VecModel, Weak};
+use slint::{ComponentHandle, EventLoopError, ModelRc, SharedString, VecModel, Weak};
 use toml_edit::{value, DocumentMut};
 
-use crate::config::{write_config, write_config_sync, write_dml_config};
+use crate::config::{write_config, write_config_sync, write_dml_config, DivaConfig};
 use crate::diva::{find_diva_folder, get_diva_folder, get_temp_folder, open_error_window};
-use crate::modpacks::{apply_mod_priority, save_modpack, save_modpack_sync, ModPackMod};
+use crate::groups::{move_in_view, prune_missing};
+use crate::history::{
+    clear_history, get_trash_folder, installed_change, order_key, record, rename_history,
+    trash_mod, Change,
+};
+use crate::metadata::{assign_ids, mod_id, rename_meta};
+use crate::modpacks::{apply_mod_priority, save_modpack, save_modpack_sync, ModPack, ModPackMod};
+use crate::query::Query;
+use crate::selection::{mark_selected, INSTALLED};
 use crate::slint_generatedApp::App;
-use crate::util::reqwest_client;
+use crate::tags::{get_tags, mod_author, pack_counts, set_filter_options, ModFilter};
+use crate::util::{cached_dir_usage, copy_dir_all, format_size, is_folder_name, reqwest_client};
 use crate::{
-    ConfirmDelete, DivaLogic, DivaModElement, EditModDialog, ModLogic, ModpackLogic, WindowLogic,
-    DIVA_DIR, MOD_PACKS,
+    ConfirmDelete, DivaLogic, DivaModElement, EditModDialog, ModLogic, ModpackLogic, NewModDialog,
+    WindowLogic, MOD_PACKS,
 };
 use crate::{DML_CFG, MODS, R4D_CFG};
 
@@ -50,9 +60,13 @@ pub struct DivaModConfig {
 pub struct DivaMod {
     pub path: String,
     pub config: DocumentMut,
+    /// total size on disk in bytes, cached between loads while the folder is unchanged
+    pub size: u64,
+    pub file_count: usize,
+    /// stable id from metadata, filled in by `assign_ids`
+    pub id: String,
 }
 
-
 /// Data Object for Diva Mod Loader's config.toml
 #[derive(Clone, Deserialize, Serialize)]
 pub struct DivaModLoader {
@@ -104,6 +118,11 @@ impl From<DivaMod> for DivaModElement {
             None => "".to_string(),
         };
 
+        let has_dll = match value.config.get("dll").and_then(|d| d.as_array()) {
+            Some(dlls) => !dlls.is_empty(),
+            None => false,
+        };
+
         DivaModElement {
             name: value.config["name"].as_str().unwrap().into(),
             author: author.into(),
@@ -112,6 +131,11 @@ impl From<DivaMod> for DivaModElement {
             enabled: value.config["enabled"].as_bool().unwrap_or(true).clone(),
             path: value.path.clone().into(),
             dirname: value.dir_name().unwrap_or("".to_string()).into(),
+            has_dll,
+            size: format_size(value.size).into(),
+            separator: false,
+            collapsed: false,
+            selected: false,
         }
     }
 }
@@ -122,28 +146,14 @@ impl From<DivaMod> for ModPackMod {
             name: value.config["name"].as_str().unwrap().into(),
             enabled: value.config["enabled"].as_bool().unwrap_or(true),
             path: value.path.clone(),
+            separator: false,
+            collapsed: false,
+            id: value.id,
         }
     }
 }
 
 impl DivaMod {
-    pub fn search(self: &Self, term: &String) -> bool {
-        let right = match self.dir_name() {
-            Some(name) => name,
-            None => self.config["name"]
-                .as_str()
-                .unwrap_or("")
-                .to_string()
-                .clone(),
-        };
-        let name = self.config["name"]
-            .as_str()
-            .unwrap_or("")
-            .to_string()
-            .to_lowercase();
-        right.to_lowercase().contains(term) || name.contains(term)
-    }
-
     #[deprecated]
     pub fn to_packmod(self: &Self) -> ModPackMod {
         ModPackMod::from(self.clone())
@@ -175,9 +185,15 @@ impl DivaModElement {
             name: self.name.to_string(),
             enabled: self.enabled,
             path: self.path.to_string(),
+            separator: self.separator,
+            collapsed: self.collapsed,
+            id: self.dir_name().map(|d| mod_id(&d)).unwrap_or_default(),
         }
     }
     pub fn is_same_as(&self, other: &Self) -> bool {
+        if self.separator || other.separator {
+            return self.separator == other.separator && self.name == other.name;
+        }
         let left = match self.dir_name() {
             Some(name) => name,
             None => self.name.to_string(),
@@ -190,6 +206,9 @@ impl DivaModElement {
     }
 
     pub fn dir_name(self: &Self) -> Option<String> {
+        if self.separator {
+            return None;
+        }
         let mut buf = PathBuf::from(self.path.to_string().clone());
         buf.pop();
         if buf.exists() {
@@ -213,11 +232,11 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
 
     let weak = ui.as_weak();
     ui.global::<ModLogic>().on_set_search(move |term| {
-        let term = term.to_string().to_lowercase();
+        let query = Query::parse(&term);
         let mods = get_mods()
             .iter()
             .cloned()
-            .filter(|m| m.search(&term))
+            .filter(|m| query.matches(m))
             .collect();
         let _ = set_mods_table(&mods, weak.clone());
     });
@@ -310,6 +329,7 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
         // let mut m
         #[allow(unused_assignments)]
         let mut module_opt = None;
+        let mut changes = vec![];
         {
             let mut gmods = match MODS.try_lock() {
                 Ok(mods) => mods,
@@ -319,7 +339,13 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
                 Some(m) => m,
                 None => return,
             };
-            m.config["enabled"] = value(!m.config["enabled"].as_bool().unwrap());
+            let was = m.config["enabled"].as_bool().unwrap();
+            m.config["enabled"] = value(!was);
+            changes.push(Change::Enabled {
+                dir: module.dir_name().unwrap(),
+                before: was,
+                after: !was,
+            });
             let buf = PathBuf::from(m.path.clone());
             #[cfg(debug_assertions)]
             println!("{}", buf.display());
@@ -354,10 +380,16 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
                 let idx = pack
                     .mods
                     .iter()
-                    .position(|m| m.name == module.name.to_string());
+                    .position(|m| !m.separator && m.name == module.name.to_string());
+                let before = pack.mods.clone();
                 if let Some(idx) = idx {
                     pack.mods[idx].enabled = m.config["enabled"].as_bool().unwrap();
                 }
+                changes.push(Change::Order {
+                    pack: order_key(&applied),
+                    before,
+                    after: pack.mods.clone(),
+                });
                 let pack = pack.clone();
                 match save_modpack_sync(pack) {
                     Ok(_) => {}
@@ -370,10 +402,16 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
                 let idx = cfg
                     .priority
                     .iter()
-                    .position(|m| m.name == module.name.to_string());
+                    .position(|m| !m.separator && m.name == module.name.to_string());
+                let before = cfg.priority.clone();
                 if let Some(idx) = idx {
                     cfg.priority[idx].enabled = m.config["enabled"].as_bool().unwrap();
                 }
+                changes.push(Change::Order {
+                    pack: "".to_owned(),
+                    before,
+                    after: cfg.priority.clone(),
+                });
                 match write_config_sync(cfg.clone()) {
                     Ok(_) => {}
                     Err(e) => {
@@ -385,6 +423,11 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
         }
         #[cfg(debug_assertions)]
         println!("Unlocked CFG @ modmanagement.rs::on_toggle_mod()");
+        let label = match m.config["enabled"].as_bool().unwrap() {
+            true => format!("Enable {}", module.name),
+            false => format!("Disable {}", module.name),
+        };
+        record(label, changes);
         ui_toggle_handle
             .unwrap()
             .global::<ModpackLogic>()
@@ -417,12 +460,22 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
     });
 
     ui.global::<ModLogic>().on_set_priority(move |old, new| {
+        // rows from the table, groups can be collapsed so these aren't list indexes
+        let old = max(old, 0) as usize;
+        let new = max(new, 0) as usize;
         if let Ok(mut cfg) = R4D_CFG.lock() {
+            let applied = cfg.applied_pack.clone();
             if cfg.applied_pack == "" || cfg.applied_pack == "All Mods" {
-                let old = min(old as usize, cfg.priority.len() - 1);
-                let item = cfg.priority.remove(old);
-                let new = max(0, min(new as usize, cfg.priority.len()));
-                cfg.priority.insert(new, item);
+                let before = cfg.priority.clone();
+                move_in_view(&mut cfg.priority, old, new);
+                record(
+                    "Change priority",
+                    vec![Change::Order {
+                        pack: "".to_owned(),
+                        before,
+                        after: cfg.priority.clone(),
+                    }],
+                );
                 let lcfg = cfg.clone();
                 let ui_priority_handle = ui_priority_handle.clone();
                 tokio::spawn(async move {
@@ -431,12 +484,8 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
                             let mods = get_mods_in_order();
                             let _ = set_mods_table(&mods, ui_priority_handle.clone());
                             let _ = ui_priority_handle.upgrade_in_event_loop(move |ui| {
-                                let mods_model: VecModel<DivaModElement> = VecModel::default();
-                                for diva_mod in mods.clone() {
-                                    mods_model.push(diva_mod.into());
-                                }
-                                let model = ModelRc::new(mods_model);
-                                ui.set_pack_mods(model);
+                                ui.global::<ModpackLogic>()
+                                    .invoke_change_modpack(applied.into());
                             });
                         }
                         Err(e) => {
@@ -447,12 +496,17 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
                     }
                 });
             } else if let Ok(mut packs) = MOD_PACKS.try_lock() {
-                let applied = cfg.applied_pack.clone();
                 if let Some(pack) = packs.get_mut(&cfg.applied_pack) {
-                    let old = min(old as usize, pack.mods.len() - 1);
-                    let item = pack.mods.remove(old as usize);
-                    let new = max(0, min(new as usize, pack.mods.len()));
-                    pack.mods.insert(new, item);
+                    let before = pack.mods.clone();
+                    move_in_view(&mut pack.mods, old, new);
+                    record(
+                        format!("Change priority in {}", pack.name),
+                        vec![Change::Order {
+                            pack: pack.name.clone(),
+                            before,
+                            after: pack.mods.clone(),
+                        }],
+                    );
                     let pack = pack.clone();
                     let ui_priority_handle = ui_priority_handle.clone();
                     tokio::spawn(async move {
@@ -530,7 +584,7 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
         let ui = weak.upgrade().unwrap();
         let confirm = ConfirmDelete::new().unwrap();
         confirm.invoke_set_color_scheme(ui.get_color_scheme());
-        confirm.set_item(module.name);
+        confirm.set_item(module.name.clone());
         let cweak = confirm.as_weak();
         confirm.on_close(move || {
             cweak.unwrap().hide().unwrap();
@@ -539,26 +593,460 @@ pub async fn init(ui: &App, dark_rx: tokio::sync::broadcast::Receiver<ColorSchem
 
         confirm.on_confirm(move || {
             cweak.unwrap().hide().unwrap();
-            let mut buf = PathBuf::from(module.path.to_string());
-            buf.pop();
-            if let Ok(buf) = buf.canonicalize() {
-                if buf.eq(&PathBuf::from("/").canonicalize().unwrap()) {
-                    return;
-                }
-                match fs::remove_dir_all(buf) {
-                    Ok(_) => {
-                        if let Ok(_) = load_mods() {
-                            if let Err(e) = set_mods_table(&get_mods(), ui_weak.clone()) {
-                                open_error_window(e.to_string());
-                            }
+            let Some(dir) = module.dir_name() else {
+                return;
+            };
+            let name = module.name.to_string();
+            let ui_weak = ui_weak.clone();
+            // goes into the trash so it can be undone
+            tokio::task::spawn_blocking(move || match trash_mod(&dir) {
+                Ok(change) => {
+                    record(format!("Delete {name}"), change.into_iter().collect());
+                    if let Ok(_) = load_mods() {
+                        if let Err(e) = set_mods_table(&get_mods(), ui_weak) {
+                            open_error_window(e.to_string());
                         }
                     }
-                    Err(e) => open_error_window(e.to_string()),
                 }
-            }
+                Err(e) => open_error_window(e),
+            });
         });
         confirm.show().unwrap();
     });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_rename_folder(move |from, to| {
+        let (from, to) = (from.to_string(), to.trim().to_owned());
+        if from.is_empty() || from == to {
+            return;
+        }
+        match rename_mod_folder(&from, &to) {
+            Ok(_) => {
+                record(
+                    format!("Rename {from} to {to}"),
+                    vec![Change::Renamed { from, to }],
+                );
+                let ui = weak.unwrap();
+                if let Err(e) = set_mods_table(&get_mods(), ui.as_weak()) {
+                    eprintln!("{e}");
+                }
+                let applied = ui.get_active_pack();
+                ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+            }
+            Err(e) => open_error_window(e.to_string()),
+        }
+    });
+
+    let weak = ui.as_weak();
+    let scheme_rx = dark_rx.resubscribe();
+    ui.global::<WindowLogic>().on_open_new_mod(move || {
+        let ui = weak.unwrap();
+        let dialog = match NewModDialog::new() {
+            Ok(dialog) => dialog,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        dialog.invoke_set_color_scheme(ui.get_color_scheme());
+
+        let dweak = dialog.as_weak();
+        dialog.on_cancel(move || {
+            dweak.unwrap().hide().unwrap();
+        });
+
+        let dweak = dialog.as_weak();
+        let ui_weak = weak.clone();
+        dialog.on_create(move || {
+            let dialog = dweak.unwrap();
+            let split = |s: SharedString| -> Vec<String> {
+                s.split(',')
+                    .map(|e| e.trim().to_string())
+                    .filter(|e| !e.is_empty())
+                    .collect()
+            };
+            let config = DivaModConfig {
+                enabled: true,
+                include: split(dialog.get_include()),
+                dll: split(dialog.get_dll()),
+                name: dialog.get_name().trim().to_string(),
+                description: dialog.get_description().to_string(),
+                version: dialog.get_version().trim().to_string(),
+                date: String::new(),
+                author: dialog.get_author().trim().to_string(),
+            };
+            if config.name.is_empty() {
+                open_error_window("A new mod needs a name".to_string());
+                return;
+            }
+            let folder = match dialog.get_folder().trim() {
+                "" => config.name.clone(),
+                folder => folder.to_string(),
+            };
+            match create_mod(config, folder, dialog.get_rom_skeleton()) {
+                Ok(_) => {
+                    dialog.hide().unwrap();
+                    let _ = set_mods_table(&get_mods(), ui_weak.clone());
+                    let applied = match R4D_CFG.try_lock() {
+                        Ok(cfg) if !cfg.applied_pack.is_empty() => cfg.applied_pack.clone(),
+                        _ => "All Mods".to_string(),
+                    };
+                    let _ = ui_weak.upgrade_in_event_loop(move |ui| {
+                        ui.global::<ModpackLogic>()
+                            .invoke_change_modpack(applied.into());
+                    });
+                }
+                Err(e) => open_error_window(format!("Unable to create mod: {e}")),
+            }
+        });
+
+        let dweak = dialog.as_weak();
+        let mut scheme_rx = scheme_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = dweak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        dialog.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        dialog.show().unwrap();
+    });
+}
+
+/// Folder DML loads mods from, this is `mods` in the game directory unless DML's config says
+/// otherwise. DML takes either a path relative to the game or an absolute one
+pub fn get_mods_folder() -> Option<PathBuf> {
+    let diva = get_diva_folder()
+        .filter(|d| !d.is_empty())
+        .or_else(find_diva_folder)?;
+    let mut buf = PathBuf::from(diva);
+    let dml = DML_CFG.lock().ok()?;
+    match dml.mods.is_empty() {
+        true => buf.push("mods"),
+        false => buf.push(dml.mods.clone()),
+    }
+    Some(buf)
+}
+
+/// What goes into DML's `mods` setting for `folder`, kept relative if it's inside the game
+fn dml_mods_value(folder: &Path) -> String {
+    if let Some(diva) = get_diva_folder().and_then(|d| PathBuf::from(d).canonicalize().ok()) {
+        if let Ok(rel) = folder.strip_prefix(&diva) {
+            if !rel.as_os_str().is_empty() {
+                return rel.display().to_string();
+            }
+        }
+    }
+    folder.display().to_string()
+}
+
+/// Points a stored `config.toml` path at the new mods folder, None if it wasn't in the old one
+fn relocate_path(path: &str, old: &[PathBuf], new: &Path) -> Option<String> {
+    let path = Path::new(path);
+    let rel = old.iter().find_map(|o| path.strip_prefix(o).ok())?;
+    Some(new.join(rel).display().to_string())
+}
+
+/// Moves the whole mods folder to `dest`, points DML at it and rewrites the mod paths in the
+/// global priority and every pack. `dest` has to be empty or not exist yet.
+/// Blocks for a while when it's on another drive since everything gets copied
+pub fn move_mods_folder(dest: PathBuf) -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    let old = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    let src = old.canonicalize()?;
+    if dest.exists() {
+        if fs::read_dir(&dest)?.next().is_some() {
+            return Err(format!("{} isn't empty", dest.display()).into());
+        }
+        // an empty folder would get in the way of the rename
+        fs::remove_dir(&dest)?;
+    }
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let dest = match dest.parent().and_then(|p| p.canonicalize().ok()) {
+        Some(parent) => parent.join(dest.file_name().ok_or("Invalid folder")?),
+        None => dest,
+    };
+    if dest.starts_with(&src) {
+        return Err("Can't move the mods folder into itself".into());
+    }
+    let snapshot = RenameSnapshot::take()?;
+    let old_trash = get_trash_folder();
+    // on another drive the old folder stays until everything points at the copy
+    let copied = fs::rename(&src, &dest).is_err();
+    if copied {
+        if let Err(e) = copy_dir_all(&src, &dest) {
+            let _ = fs::remove_dir_all(&dest);
+            return Err(format!("Unable to copy the mods: {e}").into());
+        }
+    }
+
+    let old = vec![src.clone(), old];
+    if let Err(e) = relocate_references(&old, &dest) {
+        let mut errors = vec![];
+        if let Err(e) = snapshot.restore() {
+            errors.push(e.to_string());
+        }
+        let moved_back = match copied {
+            true => fs::remove_dir_all(&dest),
+            false => fs::rename(&dest, &src),
+        };
+        if let Err(e) = moved_back {
+            errors.push(e.to_string());
+        }
+        return match errors.is_empty() {
+            true => {
+                Err(format!("Unable to move the mods folder, nothing was changed: \n{e}").into())
+            }
+            false => Err(format!(
+                "Unable to move the mods folder: \n{e}\nPutting things back failed too: \n{}",
+                errors.join("\n")
+            )
+            .into()),
+        };
+    }
+    if copied {
+        if let Err(e) = fs::remove_dir_all(&src) {
+            eprintln!("Unable to remove the old mods folder: {e}");
+        }
+    }
+    // what the history would put back still points into the old folder, and without the history
+    // nothing in the old trash can be brought back
+    clear_history();
+    if let Some(trash) = old_trash.filter(|t| t.exists()) {
+        if let Err(e) = fs::remove_dir_all(&trash) {
+            eprintln!("Unable to remove the old trash: {e}");
+        }
+    }
+    load_mods()?;
+    Ok(dest)
+}
+
+/// Points DML, the global priority and every pack at the moved mods folder
+fn relocate_references(old: &[PathBuf], dest: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    {
+        let mut dml = DML_CFG.lock().map_err(|e| e.to_string())?;
+        dml.mods = dml_mods_value(dest);
+        write_dml_config(dml.clone())?;
+    }
+    let fix = |list: &mut Vec<ModPackMod>| {
+        for entry in list.iter_mut().filter(|m| !m.separator) {
+            if let Some(path) = relocate_path(&entry.path, old, dest) {
+                entry.path = path;
+            }
+        }
+    };
+    {
+        let mut cfg = R4D_CFG.lock().map_err(|e| e.to_string())?;
+        fix(&mut cfg.priority);
+        write_config_sync(cfg.clone())?;
+    }
+    {
+        let mut packs = MOD_PACKS.lock().map_err(|e| e.to_string())?;
+        for pack in packs.values_mut() {
+            fix(&mut pack.mods);
+            save_modpack_sync(pack.clone())?;
+        }
+    }
+    Ok(())
+}
+
+/// Everything that refers to a mod folder by name or path, kept so a failed rename can put it
+/// all back. The config has the sort pins in it.
+/// Moving and renaming wait for the locks here rather than giving up, half of it failing because
+/// something else was busy would leave paths pointing at the old folder
+struct RenameSnapshot {
+    cfg: DivaConfig,
+    packs: HashMap<String, ModPack>,
+    dml: DivaModLoader,
+}
+
+impl RenameSnapshot {
+    fn take() -> Result<Self, String> {
+        Ok(Self {
+            cfg: R4D_CFG.lock().map_err(|e| e.to_string())?.clone(),
+            packs: MOD_PACKS.lock().map_err(|e| e.to_string())?.clone(),
+            dml: DML_CFG.lock().map_err(|e| e.to_string())?.clone(),
+        })
+    }
+
+    fn restore(self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        write_config_sync(self.cfg.clone())?;
+        *R4D_CFG.lock().map_err(|e| e.to_string())? = self.cfg;
+        for pack in self.packs.values() {
+            save_modpack_sync(pack.clone())?;
+        }
+        *MOD_PACKS.lock().map_err(|e| e.to_string())? = self.packs;
+        write_dml_config(self.dml.clone())?;
+        *DML_CFG.lock().map_err(|e| e.to_string())? = self.dml;
+        Ok(())
+    }
+}
+
+/// Points the global priority, the sort pins, every pack, DML's priority and the metadata at the
+/// renamed folder
+fn rename_references(
+    from: &str,
+    to: &str,
+    old: &[PathBuf],
+    new: &Path,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let fix = |list: &mut Vec<ModPackMod>| {
+        let mut changed = false;
+        for entry in list.iter_mut().filter(|m| !m.separator) {
+            if let Some(path) = relocate_path(&entry.path, old, new) {
+                entry.path = path;
+                changed = true;
+            }
+        }
+        changed
+    };
+    {
+        let mut cfg = R4D_CFG.lock().map_err(|e| e.to_string())?;
+        fix(&mut cfg.priority);
+        for pin in cfg.sort_rules.pins.iter_mut() {
+            for dir in [&mut pin.above, &mut pin.below] {
+                if *dir == from {
+                    *dir = to.to_owned();
+                }
+            }
+        }
+        write_config_sync(cfg.clone())?;
+    }
+    {
+        let mut packs = MOD_PACKS.lock().map_err(|e| e.to_string())?;
+        for pack in packs.values_mut() {
+            if fix(&mut pack.mods) {
+                save_modpack_sync(pack.clone())?;
+            }
+        }
+    }
+    {
+        let mut dml = DML_CFG.lock().map_err(|e| e.to_string())?;
+        for dir in dml.priority.iter_mut().filter(|d| *d == from) {
+            *dir = to.to_owned();
+        }
+        write_dml_config(dml.clone())?;
+    }
+    rename_meta(from, to)?;
+    Ok(())
+}
+
+/// Renames a mod's folder and everything that points at it. Either all of it happens or, if
+/// anything fails on the way, the folder and every file we touched go back to how they were
+pub fn rename_mod_folder(from: &str, to: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let to = to.trim();
+    if !is_folder_name(to) {
+        return Err(format!("\"{to}\" can't be used as a folder name").into());
+    }
+    if from == to {
+        return Ok(());
+    }
+    let folder = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    let canonical = folder.canonicalize()?;
+    let src = canonical.join(from);
+    let dest = canonical.join(to);
+    if !src.is_dir() {
+        return Err(format!("{from} isn't in the mods folder").into());
+    }
+    // only a case change is the same folder as far as windows is concerned
+    if dest.exists() && from.to_lowercase() != to.to_lowercase() {
+        return Err(format!("There's already a mod folder called {to}").into());
+    }
+
+    let snapshot = RenameSnapshot::take()?;
+    fs::rename(&src, &dest)?;
+    let old = vec![src.clone(), folder.join(from)];
+    if let Err(e) = rename_references(from, to, &old, &dest) {
+        let mut errors = vec![];
+        if let Err(e) = rename_meta(to, from) {
+            errors.push(e.to_string());
+        }
+        if let Err(e) = snapshot.restore() {
+            errors.push(e.to_string());
+        }
+        if let Err(e) = fs::rename(&dest, &src) {
+            errors.push(e.to_string());
+        }
+        return match errors.is_empty() {
+            true => Err(format!("Unable to rename {from}, nothing was changed: \n{e}").into()),
+            false => Err(format!(
+                "Unable to rename {from}: \n{e}\nPutting things back failed too: \n{}",
+                errors.join("\n")
+            )
+            .into()),
+        };
+    }
+    rename_history(from, to);
+    load_mods()?;
+    Ok(())
+}
+
+/// Creates a new mod folder with a config.toml made from `config` and registers it with the
+/// global priority and the currently applied pack
+pub fn create_mod(
+    mut config: DivaModConfig,
+    folder: String,
+    rom_skeleton: bool,
+) -> Result<DivaMod, Box<dyn Error + Send + Sync>> {
+    let folder = filenamify(folder);
+    if folder.is_empty() {
+        return Err("Invalid folder name".into());
+    }
+    let mut buf = match get_mods_folder() {
+        Some(buf) => buf,
+        None => return Err("Unable to find the mods folder".into()),
+    };
+    buf.push(&folder);
+    if buf.exists() {
+        return Err(format!("{} already exists", buf.display()).into());
+    }
+    if config.include.is_empty() {
+        config.include.push(".".to_string());
+    }
+    let config_str = toml::to_string(&config)?;
+
+    fs::create_dir_all(&buf)?;
+    if rom_skeleton {
+        for sub in ["rom", "rom/2d", "rom/objset"] {
+            fs::create_dir_all(buf.join(sub))?;
+        }
+    }
+    buf.push("config.toml");
+    fs::write(&buf, config_str)?;
+
+    load_mods()?;
+    let module = match MODS.try_lock().map_err(|e| e.to_string())?.get(&folder) {
+        Some(module) => module.clone(),
+        None => return Err("New mod was not loaded".into()),
+    };
+
+    let applied = R4D_CFG
+        .try_lock()
+        .map_err(|e| e.to_string())?
+        .applied_pack
+        .clone();
+    if applied != "All Mods" && !applied.is_empty() {
+        let pack = match MOD_PACKS
+            .try_lock()
+            .map_err(|e| e.to_string())?
+            .get_mut(&applied)
+        {
+            Some(pack) => {
+                pack.mods.push(module.clone().into());
+                pack.clone()
+            }
+            None => return Ok(module),
+        };
+        save_modpack_sync(pack)?;
+    }
+    Ok(module)
 }
 
 pub fn load_mods_from_dir(dir: String) -> Vec<DivaMod> {
@@ -604,9 +1092,14 @@ pub fn load_mods_from_dir(dir: String) -> Vec<DivaMod> {
                     Ok(cfg) => cfg,
                     Err(_) => continue,
                 };
+                let (size, file_count) =
+                    cached_dir_usage(mod_path.parent().unwrap_or(Path::new("")));
                 mods.push(DivaMod {
                     path: mod_p_str,
                     config,
+                    size,
+                    file_count,
+                    id: String::new(),
                 });
             }
             Err(_) => {
@@ -634,11 +1127,9 @@ pub fn save_mod_config(config_path: PathBuf, config: &DocumentMut) -> std::io::R
     };
 }
 
-pub async fn unpack_mod_path(archive: PathBuf) -> compress_tools::Result<()> {
-    let mut buf = PathBuf::from(find_diva_folder().unwrap_or("./mods".to_string()));
-    // DIVA_CFG.lock().unwrap().
-    buf.push(DML_CFG.lock().unwrap().mods.clone());
-    // buf.push(diva.clone().dml.unwrap().mods);
+/// Installs the mod(s) in `archive`, returning the folders that were installed
+pub async fn unpack_mod_path(archive: PathBuf) -> compress_tools::Result<Vec<PathBuf>> {
+    let mut buf = get_mods_folder().unwrap_or(PathBuf::from("./mods"));
     let name = archive
         .file_name()
         .unwrap_or(OsStr::new("missing.zip"))
@@ -646,14 +1137,27 @@ pub async fn unpack_mod_path(archive: PathBuf) -> compress_tools::Result<()> {
         .unwrap()
         .to_string();
     // let name = buf.extension().unwrap_or(OsStr::new("zip")).to_str().unwrap().to_string();
+    let label = format!("Install {name}");
     let valid = check_archive_valid_structure(File::open(archive.clone()).unwrap(), name);
     println!("Good structure? {}", valid);
-    if !valid {
+    let installed: Vec<PathBuf> = if !valid {
         buf.push(archive.file_name().unwrap());
         if !buf.exists() {
             let _ = fs::create_dir(buf.clone());
         }
-    }
+        vec![buf.clone()]
+    } else {
+        archive_root_folders(File::open(archive.clone())?)?
+            .iter()
+            .map(|root| buf.join(root))
+            .collect()
+    };
+    // only folders that weren't there before can be undone, updates just overwrite files
+    let fresh: Vec<String> = installed
+        .iter()
+        .filter(|p| !p.exists() || fs::read_dir(p).is_ok_and(|mut d| d.next().is_none()))
+        .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .collect();
     let mut mod_archive = File::open(archive.clone()).unwrap();
     let res = uncompress_archive(&mut mod_archive, buf.as_path(), Ownership::Preserve);
     // compress tools always gives an error when extracting rar files
@@ -664,13 +1168,37 @@ pub async fn unpack_mod_path(archive: PathBuf) -> compress_tools::Result<()> {
                 == "Extraction error: 'Can't decompress an entry marked as a directory'"
             {
                 println!("Ignoring this error on rar archive");
-                return Ok(());
+                record_install(label, &fresh);
+                return Ok(installed);
             } else {
                 return Err(e.into());
             }
         }
     }
-    return res;
+    res.map(|_| {
+        record_install(label, &fresh);
+        installed
+    })
+}
+
+fn record_install(label: String, fresh: &Vec<String>) {
+    record(
+        label,
+        fresh.iter().filter_map(|d| installed_change(d)).collect(),
+    );
+}
+
+/// The folders at the root of an archive that passed `check_archive_valid_structure`
+pub fn archive_root_folders(archive: File) -> compress_tools::Result<Vec<String>> {
+    let mut roots: Vec<String> = vec![];
+    for file in list_archive_files(archive)? {
+        if let Some(root) = file.split('/').next() {
+            if !root.is_empty() && !roots.iter().any(|r| r == root) {
+                roots.push(root.to_owned());
+            }
+        }
+    }
+    Ok(roots)
 }
 
 pub fn check_archive_valid_structure(archive: File, name: String) -> bool {
@@ -726,7 +1254,6 @@ pub fn load_diva_ml_config(diva_folder: &str) -> Option<DivaModLoader> {
 pub fn set_mods_table(mods: &Vec<DivaMod>, ui_handle: Weak<App>) -> Result<(), EventLoopError> {
     let mods = mods.clone();
     ui_handle.upgrade_in_event_loop(move |ui| {
-        let mods_model: VecModel<DivaModElement> = VecModel::default();
         let mut mods = mods.clone();
         mods.sort_by_key(|m| {
             m.config["name"]
@@ -735,19 +1262,41 @@ pub fn set_mods_table(mods: &Vec<DivaMod>, ui_handle: Weak<App>) -> Result<(), E
                 .to_string()
                 .to_lowercase()
         });
-        for diva_mod in mods {
-            mods_model.push(diva_mod.into());
+        set_filter_options(&ui);
+        let logic = ui.global::<ModLogic>();
+        let filter = ModFilter::from_ui(&logic);
+        mods.retain(|m| filter.matches(m));
+        // stable sorts so ties stay alphabetical
+        match logic.get_sort_column() {
+            1 => mods.sort_by_key(|m| m.size),
+            2 => mods.sort_by_key(|m| mod_author(m).to_lowercase()),
+            3 => mods.sort_by_key(|m| !m.config["enabled"].as_bool().unwrap_or(true)),
+            4 => mods.sort_by_key(|m| {
+                let tags = get_tags(&m.dir_name().unwrap_or_default());
+                (tags.is_empty(), tags.join(", "))
+            }),
+            5 => {
+                let counts = pack_counts();
+                mods.sort_by_key(|m| {
+                    let count = counts.get(&m.dir_name().unwrap_or_default());
+                    std::cmp::Reverse(count.copied().unwrap_or(0))
+                });
+            }
+            _ => {}
+        }
+        if !logic.get_sort_ascending() {
+            mods.reverse();
         }
-        let model = ModelRc::new(mods_model);
+        let mut elements: Vec<DivaModElement> = mods.into_iter().map(|m| m.into()).collect();
+        mark_selected(&ui, INSTALLED, &mut elements);
+        let model = ModelRc::new(VecModel::from(elements));
         ui.set_mods(model);
     })
 }
 //std::io::Result<()>
 pub fn load_mods() -> Result<(), Box<dyn Error + Send + Sync>> {
-    let dir = DIVA_DIR.try_lock().unwrap().clone();
-    let mut buf = PathBuf::from(dir);
-    let mut gconf = R4D_CFG.try_lock().unwrap();
-    buf.push("mods");
+    let buf = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    let mut gconf = R4D_CFG.try_lock().map_err(|e| e.to_string())?;
     let buf = buf.canonicalize()?;
     buf.display().to_string();
     let mods = load_mods_from_dir(buf.display().to_string());
@@ -766,27 +1315,30 @@ pub fn load_mods() -> Result<(), Box<dyn Error + Send + Sync>> {
             module.config["name"] = value(dir_name.clone());
         }
 
-        mod_map.insert(dir_name.clone(), module.clone());
-        if !gconf.priority.contains(&module.clone().into()) {
-            gconf.priority.push(module.into());
-        }
+        mod_map.insert(dir_name, module);
     }
-    *dmods = mod_map.clone();
-    if mod_map.len() != gconf.priority.len() {
-        let mut mods: Vec<DivaMod> = vec![];
-        for p in gconf.priority.clone() {
-            match dmods.get(&p.dir_name().unwrap_or_default()) {
-                Some(m) => {
-                    mods.push(m.clone());
-                }
-                None => {}
-            }
+    assign_ids(&mut mod_map);
+    let mut changed = false;
+    for entry in gconf.priority.iter_mut() {
+        changed |= entry.refresh_identity(Some(&buf));
+    }
+    let mut dirs: Vec<&String> = mod_map.keys().collect();
+    dirs.sort();
+    for dir in dirs {
+        let module: ModPackMod = mod_map[dir].clone().into();
+        if !gconf.priority.contains(&module) {
+            gconf.priority.push(module);
+            changed = true;
         }
-        let mut prio = vec![];
-        for m in mods {
-            prio.push(m.into());
+    }
+    if changed {
+        if let Err(e) = write_config_sync(gconf.clone()) {
+            eprintln!("{e}");
         }
-        gconf.priority = prio.clone();
+    }
+    *dmods = mod_map.clone();
+    if mod_map.len() != gconf.priority.iter().filter(|p| !p.separator).count() {
+        gconf.priority = prune_missing(&gconf.priority, &dmods);
     }
     // clone and drop the mutex instance from here so it can be unlocked
     let gconf = gconf.clone();
@@ -4,14 +4,22 @@ use sha2::{Digest, Sha256};
 use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
 use sonic_rs::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::vec;
 use tokio::fs;
 use toml_edit::value;
 
 use crate::config::{write_config, write_config_sync, write_dml_config};
 use crate::diva::{get_config_dir, get_diva_folder, open_error_window};
-use crate::modmanagement::{get_mods_in_order, save_mod_config, DivaMod};
+use crate::groups::{current_order, expand_view, missing_entries, order_elements, restore_missing};
+use crate::history::{record, Change};
+use crate::layers::{children_of, rebase, set_parent, PackLayer};
+use crate::metadata::{dir_for_id, mod_id};
+use crate::modmanagement::{get_mods_folder, get_mods_in_order, save_mod_config, DivaMod};
+use crate::query::Query;
+use crate::resolve::offer_missing;
+use crate::revisions::snapshot;
+use crate::selection::{mark_selected, PACK};
 use crate::slint_generatedApp::App;
 use crate::{
     ConfirmDeletePack, DivaModElement, ModpackLogic, WindowLogic, DML_CFG, MODS, MOD_PACKS, R4D_CFG,
@@ -21,6 +29,14 @@ use crate::{
 pub struct ModPack {
     pub name: String,
     pub mods: Vec<ModPackMod>,
+    /// pack this one is layered on, on disk `mods` is then empty and `layer` has the changes
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub parent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<PackLayer>,
+    /// the parent's list the last time `mods` was resolved against it
+    #[serde(skip)]
+    pub inherited: Vec<ModPackMod>,
 }
 
 // impl ModPAc
@@ -31,25 +47,47 @@ pub struct ModPackMod {
     pub enabled: bool,
     #[serde(default)]
     pub path: String,
+    /// named divider in the load order, everything below it up to the next one is its group
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub separator: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapsed: bool,
+    /// the mod's id from metadata, `path` goes stale when the folder is renamed but this doesn't
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+/// Ids win when both sides have one, otherwise it's down to the folder (or display) name
+fn same_mod(left: (&str, String), right: (&str, String)) -> bool {
+    match left.0.is_empty() || right.0.is_empty() {
+        true => left.1 == right.1,
+        false => left.0 == right.0,
+    }
 }
 
 impl PartialEq for ModPackMod {
     fn eq(&self, other: &Self) -> bool {
-        let left = match self.dir_name() {
+        if self.separator || other.separator {
+            return self.separator == other.separator && self.name == other.name;
+        }
+        let left = match self.path_dir() {
             Some(name) => name,
             None => self.name.clone(),
         };
-        let right = match other.dir_name() {
+        let right = match other.path_dir() {
             Some(name) => name,
             None => other.name.clone(),
         };
-        left == right
+        same_mod((&self.id, left), (&other.id, right))
     }
 }
 
 impl PartialEq<DivaMod> for ModPackMod {
     fn eq(&self, other: &DivaMod) -> bool {
-        let left = match self.dir_name() {
+        if self.separator {
+            return false;
+        }
+        let left = match self.path_dir() {
             Some(name) => name,
             None => self.name.clone(),
         };
@@ -57,29 +95,23 @@ impl PartialEq<DivaMod> for ModPackMod {
             Some(name) => name,
             None => other.config["name"].as_str().unwrap().to_string(),
         };
-        left == right
+        same_mod((&self.id, left), (&other.id, right))
     }
 }
 
 impl PartialEq<ModPackMod> for DivaMod {
     fn eq(&self, other: &ModPackMod) -> bool {
-        let left = match self.dir_name() {
-            Some(name) => name,
-            None => self.config["name"].as_str().unwrap().to_string(),
-        };
-        let right = match other.dir_name() {
-            Some(name) => name,
-            None => other.name.clone(),
-        };
-        left == right
+        other == self
     }
 }
 
 impl ModPackMod {
     pub fn to_element(self: &Self) -> DivaModElement {
-        if let Ok(mods) = MODS.try_lock() {
-            if let Some(m) = mods.get(&self.dir_name().unwrap_or_default()) {
-                return m.clone().into();
+        if !self.separator {
+            if let Ok(mods) = MODS.try_lock() {
+                if let Some(m) = mods.get(&self.dir_name().unwrap_or_default()) {
+                    return m.clone().into();
+                }
             }
         }
         DivaModElement {
@@ -90,20 +122,64 @@ impl ModPackMod {
             version: SharedString::from(""),
             path: self.path.clone().into(),
             dirname: SharedString::from(self.dir_name().unwrap_or("".to_string())),
+            has_dll: false,
+            size: SharedString::from(""),
+            separator: self.separator,
+            collapsed: self.collapsed,
+            selected: false,
         }
     }
 
     pub fn dir_name(self: &Self) -> Option<String> {
+        if self.separator {
+            return None;
+        }
+        if let Some(dir) = dir_for_id(&self.id) {
+            return Some(dir);
+        }
+        self.path_dir()
+    }
+
+    /// The folder `path` points at if it's still there, unlike `dir_name` this doesn't look the
+    /// id up so comparing entries never waits on the metadata
+    fn path_dir(&self) -> Option<String> {
         let mut buf = PathBuf::from(self.path.to_string().clone());
         buf.pop();
         if buf.exists() {
-            return match buf.file_name() {
-                Some(s) => Some(s.to_str().unwrap().to_string()),
-                None => None,
-            };
+            return buf.file_name().map(|s| s.to_str().unwrap().to_string());
         }
         None
     }
+
+    /// The folder name `path` points at, whether or not it's there
+    pub fn stored_dir(&self) -> String {
+        Path::new(&self.path)
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Fills in the id for entries saved before mods had one and points `path` at wherever the
+    /// mod is now. Returns whether anything changed
+    pub fn refresh_identity(&mut self, mods_folder: Option<&Path>) -> bool {
+        if self.separator {
+            return false;
+        }
+        let mut changed = false;
+        if self.id.is_empty() {
+            self.id = mod_id(&self.stored_dir());
+            changed = !self.id.is_empty();
+        }
+        if let (Some(dir), Some(folder)) = (dir_for_id(&self.id), mods_folder) {
+            let path = folder.join(dir).join("config.toml").display().to_string();
+            if path != self.path {
+                self.path = path;
+                changed = true;
+            }
+        }
+        changed
+    }
 }
 
 impl ModPack {
@@ -111,8 +187,21 @@ impl ModPack {
         Self {
             name: name.clone(),
             mods: Vec::new(),
+            parent: String::new(),
+            layer: None,
+            inherited: Vec::new(),
         }
     }
+
+    /// What gets written out, layered packs only keep what they change about their parent
+    pub fn for_disk(&self) -> ModPack {
+        let mut pack = self.clone();
+        if !pack.parent.is_empty() {
+            pack.layer = Some(PackLayer::between(&self.inherited, &self.mods));
+            pack.mods = vec![];
+        }
+        pack
+    }
 }
 
 pub async fn init(ui: &App) {
@@ -193,16 +282,17 @@ pub async fn init(ui: &App) {
 
     let weak = ui.as_weak();
     ui.global::<ModpackLogic>().on_set_search(move |term| {
-        let term = term.to_string();
+        let query = Query::parse(&term);
         let mods = get_mods_in_order();
-        let filtered: Vec<DivaModElement> = mods
+        let mut filtered: Vec<DivaModElement> = mods
             .iter()
             .cloned()
-            .filter(|m| m.search(&term))
+            .filter(|m| query.matches(m))
             .map(|m| m.into())
             .collect();
-        let vecmod = VecModel::from(filtered);
-        weak.unwrap().set_pack_mods(ModelRc::new(vecmod));
+        let ui = weak.unwrap();
+        mark_selected(&ui, PACK, &mut filtered);
+        ui.set_pack_mods(ModelRc::new(VecModel::from(filtered)));
     });
 
     ui.global::<ModpackLogic>()
@@ -242,23 +332,22 @@ pub async fn init(ui: &App) {
                 };
                 #[cfg(debug_assertions)]
                 println!("Locking MODS @ modpacks.rs::on_change_modpack()");
+                // layered packs pick up whatever changed in the packs under them
+                rebase(&mut packs, &mod_pack);
 
                 let mut gmods = match MODS.try_lock() {
                     Ok(ms) => ms,
                     Err(_) => return,
                 };
-                match packs.get_mut(&mod_pack.to_string()) {
-                    Some(p) => {
-                        if p.mods.len() != mods.len() {
-                            p.mods = mods.iter().map(|m| ModPackMod::from(m.clone())).collect();
-                            match save_modpack_sync(p.clone()) {
-                                Err(e) => eprintln!("{e}"),
-                                _ => {}
-                            }
-                        }
-                        pack = p.clone();
-                    }
-                    None => {}
+                // mods that aren't installed stay in the pack, `offer_missing` deals with them
+                if let Some(p) = packs.get(&mod_pack.to_string()) {
+                    pack = p.clone();
+                }
+                let missing = missing_entries(&pack.mods, &gmods).len();
+                if let Some(ui) = ui_change_handle.upgrade() {
+                    ui.global::<ModpackLogic>().set_missing_mods(missing as i32);
+                    ui.global::<ModpackLogic>()
+                        .set_pack_parent(pack.parent.clone().into());
                 }
                 for m in mods.iter_mut() {
                     if let Some(pm) = pack.mods.iter().find(|p| p.path == m.path) {
@@ -279,11 +368,10 @@ pub async fn init(ui: &App) {
             #[cfg(debug_assertions)]
             println!("Unlocked MODS @ modpacks.rs::on_change_modpack()");
             // actually create the model now
-            let vec: VecModel<DivaModElement> = Default::default();
-            for m in mods {
-                vec.push(m.into());
-            }
             let ui = ui_change_handle.unwrap();
+            let mut elements = order_elements(&pack.mods, &mods);
+            mark_selected(&ui, PACK, &mut elements);
+            let vec = VecModel::from(elements);
             let model = ModelRc::new(vec);
             ui.set_pack_mods(model.clone());
             ui.set_active_pack(mod_pack.clone());
@@ -296,6 +384,7 @@ pub async fn init(ui: &App) {
             }
 
             ui.global::<ModpackLogic>().invoke_apply_modpack(model);
+            offer_missing(&ui, &pack.name);
         });
 
     ui.global::<ModpackLogic>().on_create_new_pack(move |pack| {
@@ -326,20 +415,33 @@ pub async fn init(ui: &App) {
                 Some(mods) => mods,
                 None => &VecModel::default(),
             };
-            let mut vec = vec![];
-            for m in vecmods.iter() {
-                vec.push(m.to_packmod());
-            }
-            let mut packs = match MOD_PACKS.try_lock() {
-                Ok(packs) => packs,
-                Err(_) => return,
-            };
-            let modpack = match packs.get_mut(&pack_name) {
-                Some(pack) => pack,
-                None => return,
+            let saved = {
+                let mut packs = match MOD_PACKS.try_lock() {
+                    Ok(packs) => packs,
+                    Err(_) => return,
+                };
+                let modpack = match packs.get_mut(&pack_name) {
+                    Some(pack) => pack,
+                    None => return,
+                };
+                // collapsed groups aren't in the table, their members come from what's saved
+                let before = modpack.mods.clone();
+                let expanded = expand_view(vecmods.iter(), &modpack.mods);
+                modpack.mods = match MODS.try_lock() {
+                    Ok(mods) => restore_missing(expanded, &modpack.mods, &mods),
+                    Err(_) => return,
+                };
+                record(
+                    format!("Edit {pack_name}"),
+                    vec![Change::Order {
+                        pack: pack_name.clone(),
+                        before,
+                        after: modpack.mods.clone(),
+                    }],
+                );
+                save_modpack_sync(modpack.clone())
             };
-            modpack.mods = vec;
-            match save_modpack_sync(modpack.clone()) {
+            match saved {
                 Ok(_) => {
                     let ui = weak.unwrap();
                     ui.global::<ModpackLogic>().invoke_apply_modpack(mods);
@@ -352,7 +454,8 @@ pub async fn init(ui: &App) {
         match mods.as_any().downcast_ref::<VecModel<DivaModElement>>() {
             Some(mods) => {
                 let mut vec_mods: Vec<String> = Vec::new();
-                for m in mods.iter() {
+                // separators never make it into DML's priority
+                for m in expand_view(mods.iter(), &current_order()) {
                     if let Some(dir) = m.dir_name() {
                         vec_mods.push(dir);
                     }
@@ -428,8 +531,27 @@ pub async fn init(ui: &App) {
                 .on_delete_modpack(move |packname| {
                     println!("{:?}", packname.to_string());
                     if let Ok(mut packs) = MOD_PACKS.lock() {
+                        // packs layered on this one keep their current list as a normal pack
+                        let mut changes = vec![];
+                        for child in children_of(&packs, &packname) {
+                            match set_parent(&mut packs, &child, "") {
+                                Ok(detached) => {
+                                    if let Err(e) = save_modpack_sync(detached) {
+                                        eprintln!("{e}");
+                                    }
+                                    changes.push(Change::PackParent {
+                                        pack: child,
+                                        before: packname.to_string(),
+                                        after: String::new(),
+                                    });
+                                }
+                                Err(e) => eprintln!("{e}"),
+                            }
+                        }
                         match packs.remove(&packname.to_string()) {
                             Some(pack) => {
+                                changes.push(Change::PackDeleted { pack: pack.clone() });
+                                record(format!("Delete pack {}", pack.name), changes);
                                 let mut packsvec: Vec<ModPack> = vec![];
                                 for p in packs.values() {
                                     packsvec.push(p.clone());
@@ -491,45 +613,72 @@ pub async fn load_mod_packs() -> std::io::Result<HashMap<String, ModPack>> {
     }
     let mut packs: HashMap<String, ModPack> = HashMap::new();
     let packs_dir = packs_dir.unwrap();
+    let mods_folder = get_mods_folder().and_then(|f| f.canonicalize().ok());
     for entry in std::fs::read_dir(packs_dir.clone())? {
         let entry = entry?;
         if entry.path().is_dir() {
             continue;
         }
         let lines = fs::read_to_string(entry.path()).await?;
-        let pack: ModPack = sonic_rs::from_str(lines.as_str())?;
+        let mut pack: ModPack = sonic_rs::from_str(lines.as_str())?;
+        // packs from before mods had ids get them the first time they're loaded
+        let mut changed = false;
+        for m in pack.mods.iter_mut() {
+            changed |= m.refresh_identity(mods_folder.as_deref());
+        }
+        if let Some(layer) = pack.layer.as_mut() {
+            changed |= layer.refresh_identity(mods_folder.as_deref());
+        }
+        if changed {
+            // written as loaded, the layer can't be worked out again until the parents are in
+            match sonic_rs::to_string_pretty(&pack) {
+                Ok(s) => {
+                    if let Err(e) = std::fs::write(entry.path(), s) {
+                        eprintln!("Unable to update {}: {e}", pack.name);
+                    }
+                }
+                Err(e) => eprintln!("{e}"),
+            }
+        }
         packs.insert(pack.name.clone(), pack);
     }
+    let names: Vec<String> = packs.keys().cloned().collect();
+    for name in names {
+        rebase(&mut packs, &name);
+    }
 
     Ok(packs)
 }
 
+/// Folder in rust4diva's config dir that holds everything specific to the current game install
+pub fn get_install_config_dir() -> std::io::Result<PathBuf> {
+    let mut config_dir = get_config_dir()?;
+    let diva_dir = match get_diva_folder() {
+        Some(diva_dir) => diva_dir,
+        None => "DefaultPath".to_owned(),
+    };
+    config_dir.push(filenamify(hash_dir_name(diva_dir.clone())));
+    if !config_dir.try_exists()? {
+        std::fs::create_dir(config_dir.clone())?
+    }
+    Ok(config_dir)
+}
+
 pub fn get_modpacks_folder() -> std::io::Result<PathBuf> {
-    match get_config_dir() {
-        Ok(mut config_dir) => {
-            let diva_dir = match get_diva_folder() {
-                Some(diva_dir) => diva_dir,
-                None => "DefaultPath".to_owned(),
-            };
-            config_dir.push(filenamify(hash_dir_name(diva_dir.clone())));
-            if !config_dir.try_exists()? {
-                std::fs::create_dir(config_dir.clone())?
-            }
-            config_dir.push("modpacks");
-            if !config_dir.try_exists()? {
-                std::fs::create_dir(config_dir.clone())?
-            }
-            Ok(config_dir)
-        }
-        Err(e) => Err(e.into()),
+    let mut config_dir = get_install_config_dir()?;
+    config_dir.push("modpacks");
+    if !config_dir.try_exists()? {
+        std::fs::create_dir(config_dir.clone())?
     }
+    Ok(config_dir)
 }
 
 pub async fn save_modpack(pack: ModPack) -> std::io::Result<()> {
     match get_modpacks_folder() {
         Ok(mut packs_dir) => {
             packs_dir.push(filenamify(pack.name.clone()) + ".json");
-            if let Ok(pckstr) = sonic_rs::to_string_pretty(&pack.clone()) {
+            if let Ok(pckstr) = sonic_rs::to_string_pretty(&pack.for_disk()) {
+                snapshot(&pack, &packs_dir, &pckstr);
                 return fs::write(packs_dir, pckstr).await;
             }
             Ok(())
@@ -541,7 +690,8 @@ pub fn save_modpack_sync(pack: ModPack) -> std::io::Result<()> {
     match get_modpacks_folder() {
         Ok(mut packs_dir) => {
             packs_dir.push(filenamify(pack.name.clone()) + ".json");
-            if let Ok(pckstr) = sonic_rs::to_string_pretty(&pack.clone()) {
+            if let Ok(pckstr) = sonic_rs::to_string_pretty(&pack.for_disk()) {
+                snapshot(&pack, &packs_dir, &pckstr);
                 return std::fs::write(packs_dir, pckstr);
             }
             Ok(())
@@ -554,9 +704,13 @@ pub async fn apply_mod_priority() -> Result<(), Box<dyn std::error::Error + Send
     if let Ok(cfg) = R4D_CFG.try_lock() {
         let mut prio = vec![];
         if cfg.applied_pack != "".to_owned() {
-            if let Ok(packs) = MOD_PACKS.try_lock() {
+            if let Ok(mut packs) = MOD_PACKS.try_lock() {
+                rebase(&mut packs, &cfg.applied_pack);
                 if let Some(current_pack) = packs.get(&cfg.applied_pack) {
                     for m in current_pack.clone().mods {
+                        if m.separator {
+                            continue;
+                        }
                         prio.push(m.clone().dir_name().unwrap_or(m.name));
                     }
                 }
@@ -578,6 +732,17 @@ pub async fn apply_mod_priority() -> Result<(), Box<dyn std::error::Error + Send
     Ok(())
 }
 
+/// Rebuilds the pack dropdown from `MOD_PACKS`
+pub fn refresh_pack_list(ui: &App) {
+    let mut names: Vec<SharedString> = match MOD_PACKS.try_lock() {
+        Ok(packs) => packs.keys().map(|k| k.into()).collect(),
+        Err(_) => return,
+    };
+    names.sort_by_key(|s| s.to_lowercase());
+    names.insert(0, "All Mods".into());
+    ui.set_modpacks(ModelRc::new(VecModel::from(names)));
+}
+
 pub fn hash_dir_name(dir: String) -> String {
     let hash = Sha256::digest(dir);
     Base64::encode_string(&hash)
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, Model, ModelRc, VecModel};
+use tokio::sync::broadcast;
+
+use crate::config::write_dml_config;
+use crate::diva::{get_diva_folder, open_error_window};
+use crate::history::{record, Change};
+use crate::layers::rebase;
+use crate::metadata::{installed_with_fingerprint, known_fingerprint, mod_id};
+use crate::modmanagement::{get_mods_folder, load_diva_ml_config, DivaMod};
+use crate::modpacks::{save_modpack_sync, ModPack, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::{
+    ModpackLogic, PackHealthWindow, PackIssueRow, WindowLogic, DML_CFG, MODS, MOD_PACKS, R4D_CFG,
+};
+
+/// How many fixes "Fix all" makes before giving up, each one needs a fresh scan
+const MAX_FIXES: usize = 1000;
+
+enum Issue {
+    /// entry that doesn't point at an installed mod, `relink` is the folder it most likely is now
+    Stale {
+        pack: String,
+        idx: usize,
+        entry: ModPackMod,
+        relink: Option<(String, &'static str)>,
+    },
+    /// the same mod a second time, `idx` is the later one
+    Duplicate {
+        pack: String,
+        idx: usize,
+        entry: ModPackMod,
+    },
+    /// installed but not in any pack
+    Unpacked { dir: String, name: String },
+    /// DML's priority on disk isn't what the applied pack would write
+    Drift {
+        pack: String,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+}
+
+impl Issue {
+    /// What "Fix all" does, mods that aren't installed stay in the packs and mods in no pack are
+    /// left alone since both can be on purpose
+    fn safe(&self) -> bool {
+        match self {
+            Issue::Stale { relink, .. } => relink.is_some(),
+            Issue::Unpacked { .. } => false,
+            Issue::Duplicate { .. } | Issue::Drift { .. } => true,
+        }
+    }
+
+    /// Tells issues apart across scans
+    fn label(&self) -> String {
+        row_label(&self.row(""))
+    }
+
+    fn row(&self, applied: &str) -> PackIssueRow {
+        let (pack, problem, detail, fix) = match self {
+            Issue::Stale {
+                pack,
+                entry,
+                relink,
+                ..
+            } => (
+                pack.clone(),
+                format!("{} isn't installed where the pack says", entry.name),
+                match relink {
+                    Some((dir, how)) => format!("Found it in {dir} by {how}"),
+                    None => format!("Nothing matches {}", entry.stored_dir()),
+                },
+                match relink {
+                    Some(_) => "Relink",
+                    None => "Remove",
+                },
+            ),
+            Issue::Duplicate { pack, idx, entry } => (
+                pack.clone(),
+                format!("{} is in the pack twice", entry.name),
+                format!("The copy at #{} goes", idx + 1),
+                "Remove",
+            ),
+            Issue::Unpacked { dir, name } => (
+                String::new(),
+                format!("{name} isn't in any pack"),
+                dir.clone(),
+                "Add",
+            ),
+            Issue::Drift {
+                pack,
+                expected,
+                actual,
+            } => {
+                let missing = expected.iter().filter(|d| !actual.contains(d)).count();
+                let extra = actual.iter().filter(|d| !expected.contains(d)).count();
+                (
+                    pack.clone(),
+                    "DivaModLoader's priority doesn't match the applied pack".to_owned(),
+                    format!(
+                        "{missing} missing, {extra} extra{}",
+                        match missing + extra == 0 {
+                            true => ", the order differs",
+                            false => "",
+                        }
+                    ),
+                    "Reapply",
+                )
+            }
+        };
+        let can_fix = match self {
+            Issue::Unpacked { .. } => !applied.is_empty(),
+            _ => true,
+        };
+        PackIssueRow {
+            pack: pack.into(),
+            problem: problem.into(),
+            detail: detail.into(),
+            fix: fix.into(),
+            can_fix,
+            safe: self.safe(),
+        }
+    }
+}
+
+/// `Issue::label` for a row that's already in the window
+fn row_label(row: &PackIssueRow) -> String {
+    match row.pack.is_empty() {
+        true => row.problem.to_string(),
+        false => format!("{}: {}", row.pack, row.problem),
+    }
+}
+
+/// The applied pack, empty when it's "All Mods"
+fn applied_pack() -> String {
+    match R4D_CFG.try_lock() {
+        Ok(cfg) if cfg.applied_pack != "All Mods" => cfg.applied_pack.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Folder a stale entry's mod most likely went to, and how we know
+fn find_relink(
+    entry: &ModPackMod,
+    mods: &HashMap<String, DivaMod>,
+) -> Option<(String, &'static str)> {
+    let dir = entry.stored_dir();
+    if mods.contains_key(&dir) {
+        return Some((dir, "folder name"));
+    }
+    installed_with_fingerprint(&known_fingerprint(&dir, &entry.id))
+        .filter(|d| mods.contains_key(d))
+        .map(|d| (d, "matching files"))
+}
+
+/// What DML would load for the applied pack, and what it's actually set to on disk
+fn priority_drift(packs: &HashMap<String, ModPack>, applied: &str) -> Option<Issue> {
+    let list = match applied.is_empty() {
+        true => R4D_CFG.try_lock().ok()?.priority.clone(),
+        false => packs.get(applied)?.mods.clone(),
+    };
+    let expected: Vec<String> = list.iter().filter_map(|m| m.dir_name()).collect();
+    let actual = load_diva_ml_config(&get_diva_folder()?)?.priority;
+    match expected == actual {
+        true => None,
+        false => Some(Issue::Drift {
+            pack: match applied.is_empty() {
+                true => "All Mods".to_owned(),
+                false => applied.to_owned(),
+            },
+            expected,
+            actual,
+        }),
+    }
+}
+
+fn scan() -> Result<Vec<Issue>, String> {
+    let applied = applied_pack();
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let names: Vec<String> = packs.keys().cloned().collect();
+    for name in names.iter() {
+        rebase(&mut packs, name);
+    }
+    let mods = MODS.try_lock().map_err(|e| e.to_string())?;
+    let mut issues = vec![];
+
+    let mut sorted: Vec<&ModPack> = packs.values().collect();
+    sorted.sort_by_key(|p| p.name.to_lowercase());
+    for pack in sorted {
+        for (idx, entry) in pack.mods.iter().enumerate() {
+            // problems a layered pack gets from its parent are the parent's to fix
+            if entry.separator || pack.inherited.contains(entry) {
+                continue;
+            }
+            if pack.mods[..idx].contains(entry) {
+                issues.push(Issue::Duplicate {
+                    pack: pack.name.clone(),
+                    idx,
+                    entry: entry.clone(),
+                });
+                continue;
+            }
+            let installed = entry.dir_name().is_some_and(|d| mods.contains_key(&d));
+            if !installed {
+                issues.push(Issue::Stale {
+                    pack: pack.name.clone(),
+                    idx,
+                    entry: entry.clone(),
+                    relink: find_relink(entry, &mods),
+                });
+            }
+        }
+    }
+
+    if !packs.is_empty() {
+        let mut unpacked: Vec<&DivaMod> = mods
+            .values()
+            .filter(|m| !packs.values().any(|p| p.mods.iter().any(|e| e == *m)))
+            .collect();
+        unpacked.sort_by_key(|m| m.dir_name().unwrap_or_default().to_lowercase());
+        for m in unpacked {
+            issues.push(Issue::Unpacked {
+                dir: m.dir_name().unwrap_or_default(),
+                name: m.config["name"].as_str().unwrap_or_default().to_owned(),
+            });
+        }
+    }
+    drop(mods);
+
+    if let Some(drift) = priority_drift(&packs, &applied) {
+        issues.push(drift);
+    }
+    Ok(issues)
+}
+
+/// Swaps a pack's list, returns the change for the history
+fn edit_pack<F: FnOnce(&mut Vec<ModPackMod>)>(pack_name: &str, f: F) -> Result<Change, String> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let pack = packs
+        .get_mut(pack_name)
+        .ok_or(format!("There's no pack called {pack_name}"))?;
+    let before = pack.mods.clone();
+    f(&mut pack.mods);
+    save_modpack_sync(pack.clone()).map_err(|e| e.to_string())?;
+    Ok(Change::Order {
+        pack: pack_name.to_owned(),
+        before,
+        after: pack.mods.clone(),
+    })
+}
+
+/// Returns the history change if it made one, DML's priority isn't part of the history
+fn fix(issue: &Issue, applied: &str) -> Result<Option<Change>, String> {
+    match issue {
+        Issue::Stale {
+            pack,
+            idx,
+            relink: Some((dir, _)),
+            ..
+        } => {
+            let folder = get_mods_folder().ok_or("Unable to find the mods folder")?;
+            let path = folder.join(dir).join("config.toml").display().to_string();
+            let id = mod_id(dir);
+            edit_pack(pack, |list| {
+                if let Some(entry) = list.get_mut(*idx) {
+                    entry.path = path;
+                    entry.id = id;
+                }
+            })
+            .map(Some)
+        }
+        Issue::Stale { pack, idx, .. } | Issue::Duplicate { pack, idx, .. } => {
+            edit_pack(pack, |list| {
+                if *idx < list.len() {
+                    list.remove(*idx);
+                }
+            })
+            .map(Some)
+        }
+        Issue::Unpacked { dir, .. } => {
+            if applied.is_empty() {
+                return Err("Apply a pack first, that's where the mod gets added".to_owned());
+            }
+            let entry = match MODS.try_lock() {
+                Ok(mods) => mods.get(dir).cloned().map(ModPackMod::from),
+                Err(e) => return Err(e.to_string()),
+            }
+            .ok_or(format!("{dir} isn't installed anymore"))?;
+            edit_pack(applied, |list| list.push(entry)).map(Some)
+        }
+        Issue::Drift { expected, .. } => {
+            let diva = get_diva_folder().ok_or("Unable to find the game folder")?;
+            let mut dml = DML_CFG.try_lock().map_err(|e| e.to_string())?;
+            // keep whatever else was edited by hand in there
+            if let Some(on_disk) = load_diva_ml_config(&diva) {
+                *dml = on_disk;
+            }
+            dml.priority = expected.clone();
+            write_dml_config(dml.clone()).map_err(|e| e.to_string())?;
+            Ok(None)
+        }
+    }
+}
+
+fn fill_window(window: &PackHealthWindow) {
+    let applied = applied_pack();
+    match scan() {
+        Ok(issues) => {
+            let rows: Vec<PackIssueRow> = issues.iter().map(|i| i.row(&applied)).collect();
+            window.set_summary(
+                match rows.is_empty() {
+                    true => "Every pack checks out.".to_owned(),
+                    false => format!(
+                        "{} problems, {} of them get fixed by Fix all.",
+                        rows.len(),
+                        rows.iter().filter(|r| r.safe && r.can_fix).count()
+                    ),
+                }
+                .into(),
+            );
+            window.set_rows(ModelRc::new(VecModel::from(rows)));
+        }
+        Err(e) => open_error_window(format!("Unable to check the packs: \n{e}")),
+    }
+}
+
+fn reapply(ui: &App) {
+    let applied = match applied_pack() {
+        applied if applied.is_empty() => "All Mods".to_owned(),
+        applied => applied,
+    };
+    ui.global::<ModpackLogic>()
+        .invoke_change_modpack(applied.into());
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_pack_check(move || {
+        let ui = weak.unwrap();
+        let window = match PackHealthWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        fill_window(&window);
+
+        let window_weak = window.as_weak();
+        window.on_rescan(move || {
+            fill_window(&window_weak.unwrap());
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_fix(move |idx| {
+            let window = window_weak.unwrap();
+            let applied = applied_pack();
+            // the rows are from the last scan, the issue is looked up again by what it says
+            let shown = window
+                .get_rows()
+                .row_data(idx as usize)
+                .map(|r| row_label(&r));
+            let res = scan().and_then(|issues| {
+                let issue = issues
+                    .iter()
+                    .find(|i| shown.as_ref() == Some(&i.label()))
+                    .ok_or("The list is out of date, check again")?;
+                Ok((issue.label(), fix(issue, &applied)?))
+            });
+            match res {
+                Ok((label, change)) => {
+                    if let Some(change) = change {
+                        record(format!("Fix: {label}"), vec![change]);
+                        reapply(&main_weak.unwrap());
+                    }
+                }
+                Err(e) => open_error_window(format!("Unable to fix that: \n{e}")),
+            }
+            fill_window(&window);
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_fix_all(move || {
+            let applied = applied_pack();
+            let mut changes = vec![];
+            let mut failed = vec![];
+            let mut errors = vec![];
+            let mut priority_written = false;
+            // fixes shift the indexes around so every one gets a fresh scan, failed ones are
+            // skipped the next time around
+            for _ in 0..MAX_FIXES {
+                let issues = match scan() {
+                    Ok(issues) => issues,
+                    Err(e) => {
+                        errors.push(e);
+                        break;
+                    }
+                };
+                let next = issues.iter().find(|i| {
+                    i.safe()
+                        && !failed.contains(&i.label())
+                        && !(priority_written && matches!(i, Issue::Drift { .. }))
+                });
+                let Some(issue) = next else {
+                    break;
+                };
+                match fix(issue, &applied) {
+                    Ok(Some(change)) => changes.push(change),
+                    Ok(None) => priority_written = true,
+                    Err(e) => {
+                        errors.push(format!("{}: {e}", issue.label()));
+                        failed.push(issue.label());
+                    }
+                }
+            }
+            if !changes.is_empty() {
+                record("Fix pack problems".to_owned(), changes);
+                reapply(&main_weak.unwrap());
+            }
+            if !errors.is_empty() {
+                open_error_window(format!(
+                    "Some things couldn't be fixed: \n{}",
+                    errors.join("\n")
+                ));
+            }
+            fill_window(&window_weak.unwrap());
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let window_weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = window_weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
@@ -0,0 +1,408 @@
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+use tokio::sync::broadcast;
+
+use crate::diva::open_error_window;
+use crate::history::{record, Change};
+use crate::layers::{rebase, PackLayer};
+use crate::modmanagement::get_mods_folder;
+use crate::modpacks::{save_modpack_sync, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::{ModpackLogic, PackDiffRow, PackDiffWindow, DML_CFG, MODS, MOD_PACKS, R4D_CFG};
+
+/// The side that's whatever DML loads right now, it can only be copied from
+const LOAD_ORDER: &str = "Current load order";
+
+const ONLY_LEFT: i32 = 0;
+const ONLY_RIGHT: i32 = 1;
+const ENABLED: i32 = 2;
+const MOVED: i32 = 3;
+
+/// DML's priority as pack entries, enabled comes from each mod's own config
+fn load_order() -> Result<Vec<ModPackMod>, String> {
+    let dirs = DML_CFG
+        .try_lock()
+        .map_err(|e| e.to_string())?
+        .priority
+        .clone();
+    let mods = MODS.try_lock().map_err(|e| e.to_string())?;
+    let folder = get_mods_folder().unwrap_or_default();
+    Ok(dirs
+        .iter()
+        .map(|dir| match mods.get(dir) {
+            Some(m) => ModPackMod::from(m.clone()),
+            None => ModPackMod {
+                name: dir.clone(),
+                enabled: true,
+                path: folder.join(dir).join("config.toml").display().to_string(),
+                separator: false,
+                collapsed: false,
+                id: String::new(),
+            },
+        })
+        .collect())
+}
+
+fn side_list(side: &str) -> Result<Vec<ModPackMod>, String> {
+    if side == LOAD_ORDER {
+        return load_order();
+    }
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    rebase(&mut packs, side);
+    match packs.get(side) {
+        Some(pack) => Ok(pack.mods.clone()),
+        None => Err(format!("There's no pack called {side}")),
+    }
+}
+
+fn write_side(side: &str, list: Vec<ModPackMod>, label: String) -> Result<(), String> {
+    if side == LOAD_ORDER {
+        return Err("The current load order can't be edited, apply a pack instead".to_owned());
+    }
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let pack = packs
+        .get_mut(side)
+        .ok_or(format!("There's no pack called {side}"))?;
+    let before = pack.mods.clone();
+    pack.mods = list;
+    save_modpack_sync(pack.clone()).map_err(|e| e.to_string())?;
+    record(
+        label,
+        vec![Change::Order {
+            pack: side.to_owned(),
+            before,
+            after: pack.mods.clone(),
+        }],
+    );
+    Ok(())
+}
+
+/// Everything the two lists disagree on, an entry that's both moved and toggled gets two rows
+fn diff(left: &[ModPackMod], right: &[ModPackMod]) -> Vec<(ModPackMod, i32)> {
+    let layer = PackLayer::between(left, right);
+    let mut rows: Vec<(ModPackMod, i32)> =
+        layer.removed.into_iter().map(|m| (m, ONLY_LEFT)).collect();
+    for change in layer.changed {
+        let Some(ours) = left.iter().find(|m| **m == change.entry) else {
+            rows.push((change.entry, ONLY_RIGHT));
+            continue;
+        };
+        if ours.enabled != change.entry.enabled {
+            rows.push((change.entry.clone(), ENABLED));
+        }
+        if change.placed {
+            rows.push((change.entry, MOVED));
+        }
+    }
+    rows
+}
+
+fn spot(list: &[ModPackMod], entry: &ModPackMod) -> String {
+    match list.iter().position(|m| m == entry) {
+        Some(idx) if list[idx].separator => format!("#{}", idx + 1),
+        Some(idx) => format!(
+            "#{} {}",
+            idx + 1,
+            if list[idx].enabled { "on" } else { "off" }
+        ),
+        None => "-".to_owned(),
+    }
+}
+
+/// Makes `to` agree with `from` about one entry
+fn copy_entry(from: &[ModPackMod], to: &mut Vec<ModPackMod>, entry: &ModPackMod, kind: i32) {
+    let src = from.iter().position(|m| m == entry);
+    let dst = to.iter().position(|m| m == entry);
+    let Some(src) = src else {
+        if let Some(dst) = dst {
+            to.remove(dst);
+        }
+        return;
+    };
+    if kind == ENABLED {
+        if let Some(dst) = dst {
+            to[dst].enabled = from[src].enabled;
+        }
+        return;
+    }
+    let item = match dst {
+        Some(dst) => to.remove(dst),
+        None => from[src].clone(),
+    };
+    // right after the closest thing above it that both have
+    let at = from[..src]
+        .iter()
+        .rev()
+        .find_map(|m| to.iter().position(|t| t == m))
+        .map(|p| p + 1)
+        .unwrap_or(0);
+    to.insert(at, item);
+}
+
+fn compare(window: &PackDiffWindow, left: &str, right: &str) {
+    let (ours, theirs) = match (side_list(left), side_list(right)) {
+        (Ok(ours), Ok(theirs)) => (ours, theirs),
+        (Err(e), _) | (_, Err(e)) => {
+            open_error_window(e);
+            return;
+        }
+    };
+    let rows = match left == right {
+        true => vec![],
+        false => diff(&ours, &theirs),
+    };
+    let count = |kind: i32| rows.iter().filter(|r| r.1 == kind).count();
+    window.set_summary(
+        format!(
+            "{} only in {left}, {} only in {right}, {} enabled differently and {} in a different \
+             spot.",
+            count(ONLY_LEFT),
+            count(ONLY_RIGHT),
+            count(ENABLED),
+            count(MOVED)
+        )
+        .into(),
+    );
+    let rows: Vec<PackDiffRow> = rows
+        .iter()
+        .map(|(entry, kind)| PackDiffRow {
+            name: match entry.separator {
+                true => format!("── {}", entry.name),
+                false => entry.name.clone(),
+            }
+            .into(),
+            kind: *kind,
+            left: spot(&ours, entry).into(),
+            right: spot(&theirs, entry).into(),
+        })
+        .collect();
+    window.set_rows(ModelRc::new(VecModel::from(rows)));
+}
+
+/// Copies one row (or everything when `row` is None) across and reapplies the active pack
+fn copy(ui: &App, left: &str, right: &str, row: Option<usize>, to_right: bool) {
+    let res = (|| -> Result<(), String> {
+        let (ours, theirs) = (side_list(left)?, side_list(right)?);
+        let (from, from_name, mut target, target_name) = match to_right {
+            true => (&ours, left, theirs.clone(), right),
+            false => (&theirs, right, ours.clone(), left),
+        };
+        match row {
+            Some(row) => {
+                let rows = diff(&ours, &theirs);
+                let (entry, kind) = rows.get(row).ok_or("The comparison is out of date")?;
+                copy_entry(from, &mut target, entry, *kind);
+                let label = format!("Copy {} from {from_name} to {target_name}", entry.name);
+                write_side(target_name, target, label)
+            }
+            None => write_side(
+                target_name,
+                from.clone(),
+                format!("Make {target_name} match {from_name}"),
+            ),
+        }
+    })();
+    match res {
+        Ok(_) => {
+            let applied = match R4D_CFG.try_lock() {
+                Ok(cfg) if !cfg.applied_pack.is_empty() => cfg.applied_pack.clone().into(),
+                _ => ui.get_active_pack(),
+            };
+            ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+        }
+        Err(e) => open_error_window(format!("Unable to copy the difference: \n{e}")),
+    }
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>().on_compare_packs(move |pack| {
+        let ui = weak.unwrap();
+        let window = match PackDiffWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+
+        let mut sides: Vec<SharedString> = match MOD_PACKS.try_lock() {
+            Ok(packs) => packs.keys().map(|k| k.into()).collect(),
+            Err(_) => return,
+        };
+        sides.sort_by_key(|s| s.to_lowercase());
+        sides.insert(0, LOAD_ORDER.into());
+        let left = sides.iter().position(|s| *s == pack).unwrap_or(0);
+        // a pack starts out compared against what's loaded, unless it's the one loaded
+        let applied = match R4D_CFG.try_lock() {
+            Ok(cfg) => cfg.applied_pack.clone(),
+            Err(_) => String::new(),
+        };
+        let right = match applied == pack.as_str() {
+            true => (1..sides.len()).find(|i| *i != left).unwrap_or(0),
+            false => 0,
+        };
+        window.set_load_order(LOAD_ORDER.into());
+        window.set_left_index(left as i32);
+        window.set_right_index(right as i32);
+        window.set_sides(ModelRc::new(VecModel::from(sides.clone())));
+        compare(&window, &sides[left], &sides[right]);
+
+        let window_weak = window.as_weak();
+        window.on_compare(move |left, right| {
+            compare(&window_weak.unwrap(), &left, &right);
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_copy_row(move |left, right, row, to_right| {
+            copy(
+                &main_weak.unwrap(),
+                &left,
+                &right,
+                Some(row as usize),
+                to_right,
+            );
+            compare(&window_weak.unwrap(), &left, &right);
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_copy_all(move |left, right, to_right| {
+            copy(&main_weak.unwrap(), &left, &right, None, to_right);
+            compare(&window_weak.unwrap(), &left, &right);
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let window_weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = window_weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::new_separator;
+
+    /// A leading `-` means disabled, capitalised names are separators
+    fn list(names: &[&str]) -> Vec<ModPackMod> {
+        names
+            .iter()
+            .map(|name| match name.starts_with(char::is_uppercase) {
+                true => new_separator(name),
+                false => ModPackMod {
+                    name: name.trim_start_matches('-').to_owned(),
+                    enabled: !name.starts_with('-'),
+                    path: String::new(),
+                    separator: false,
+                    collapsed: false,
+                    id: String::new(),
+                },
+            })
+            .collect()
+    }
+
+    fn names(list: &[ModPackMod]) -> Vec<String> {
+        list.iter()
+            .map(|m| match m.enabled {
+                true => m.name.clone(),
+                false => format!("-{}", m.name),
+            })
+            .collect()
+    }
+
+    fn rows(left: &[&str], right: &[&str]) -> Vec<(String, i32)> {
+        diff(&list(left), &list(right))
+            .into_iter()
+            .map(|(m, kind)| (m.name, kind))
+            .collect()
+    }
+
+    #[test]
+    fn same_lists_agree() {
+        assert!(rows(&["a", "Songs", "-b"], &["a", "Songs", "-b"]).is_empty());
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(
+            rows(&["a", "b", "c"], &["a", "-b", "c", "d"]),
+            [("b".to_owned(), ENABLED), ("d".to_owned(), ONLY_RIGHT)]
+        );
+        assert_eq!(rows(&["a", "b"], &["a"]), [("b".to_owned(), ONLY_LEFT)]);
+        // a, b stays in order, c is what moved
+        assert_eq!(
+            rows(&["a", "b", "c"], &["a", "c", "b"]),
+            [("c".to_owned(), MOVED)]
+        );
+        // moved and toggled is two rows
+        assert_eq!(
+            rows(&["a", "b", "c"], &["-c", "a", "b"]),
+            [("c".to_owned(), ENABLED), ("c".to_owned(), MOVED)]
+        );
+    }
+
+    #[test]
+    fn spots() {
+        let list = list(&["Songs", "a", "-b"]);
+        assert_eq!(spot(&list, &list[0]), "#1");
+        assert_eq!(spot(&list, &list[1]), "#2 on");
+        assert_eq!(spot(&list, &list[2]), "#3 off");
+        assert_eq!(spot(&list[..1], &list[2]), "-");
+    }
+
+    #[test]
+    fn copying_one_entry() {
+        let from = list(&["a", "b", "-c", "d"]);
+        let copied = |to: &[&str], name: &str, kind: i32| {
+            let mut to = list(to);
+            let entry = list(&[name]).remove(0);
+            copy_entry(&from, &mut to, &entry, kind);
+            names(&to)
+        };
+        // goes in right below the closest entry above it that both have
+        assert_eq!(copied(&["a", "d"], "c", ONLY_LEFT), ["a", "-c", "d"]);
+        assert_eq!(copied(&["d"], "a", ONLY_LEFT), ["a", "d"]);
+        assert_eq!(copied(&["a", "x", "d"], "x", ONLY_RIGHT), ["a", "d"]);
+        assert_eq!(
+            copied(&["a", "b", "c", "d"], "c", ENABLED),
+            ["a", "b", "-c", "d"]
+        );
+        assert_eq!(
+            copied(&["b", "a", "-c", "d"], "b", MOVED),
+            ["a", "b", "-c", "d"]
+        );
+    }
+
+    #[test]
+    fn copying_every_row_makes_them_equal() {
+        let (left, right) = (
+            list(&["a", "Songs", "b", "-c", "d"]),
+            list(&["x", "a", "-d", "Songs", "c"]),
+        );
+        let mut to = right.clone();
+        for (entry, kind) in diff(&right, &left) {
+            copy_entry(&left, &mut to, &entry, kind);
+        }
+        assert_eq!(names(&to), names(&left));
+    }
+}
@@ -0,0 +1,658 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use compress_tools::{uncompress_archive, Ownership};
+
+use filenamify::filenamify;
+use rfd::AsyncFileDialog;
+use serde::{Deserialize, Serialize};
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::sync::broadcast;
+
+use crate::diva::open_error_window;
+use crate::duplicates::hash_mod;
+use crate::export::{export_bundle, BUNDLE_MODS_DIR, BUNDLE_PACK_FILE};
+use crate::groups::new_separator;
+use crate::history::{installed_change, record, trash_mod, Change};
+use crate::metadata::{dir_for_id, get_meta, update_meta, ModSource};
+use crate::modmanagement::{get_mods, get_mods_folder, load_mods, set_mods_table, DivaMod};
+use crate::modpacks::{refresh_pack_list, save_modpack_sync, ModPack, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::util::{copy_dir_all, is_folder_name};
+use crate::{ModpackLogic, PackExportDialog, PackImportRow, PackImportWindow, MODS, MOD_PACKS};
+
+pub const PACK_EXTENSION: &str = "r4dpack";
+/// A zip with the pack and every mod in it, see `export_bundle`
+pub const BUNDLE_EXTENSION: &str = "r4dbundle";
+const FORMAT_VERSION: u32 = 1;
+
+/// A pack that makes sense on someone else's machine, mods are referenced by folder name,
+/// content hash and where they can be downloaded instead of by path
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PortablePack {
+    #[serde(default)]
+    pub format: u32,
+    pub name: String,
+    pub mods: Vec<PortableMod>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PortableMod {
+    pub name: String,
+    #[serde(default)]
+    pub dir: String,
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    /// `hash_mod` of the folder, config.toml isn't part of it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<ModSource>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub separator: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collapsed: bool,
+    /// The mod's id on the machine it was exported from
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+/// How an entry in a portable pack lines up with what's installed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Match {
+    Installed(String),
+    /// same mod but the files aren't the same, usually another version
+    Different(String),
+    Missing,
+}
+
+fn mod_version(module: &DivaMod) -> String {
+    module
+        .config
+        .get("version")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Hashes every mod so this takes a while on big packs, keep it off the ui thread
+pub fn to_portable(pack: &ModPack) -> PortablePack {
+    let mods = match MODS.try_lock() {
+        Ok(mods) => mods.clone(),
+        Err(_) => HashMap::new(),
+    };
+    let entries = pack
+        .mods
+        .iter()
+        .map(|entry| {
+            if entry.separator {
+                return PortableMod {
+                    name: entry.name.clone(),
+                    enabled: true,
+                    separator: true,
+                    collapsed: entry.collapsed,
+                    ..Default::default()
+                };
+            }
+            let dir = entry.dir_name().unwrap_or_default();
+            let module = mods.get(&dir);
+            PortableMod {
+                name: entry.name.clone(),
+                enabled: entry.enabled,
+                version: module.map(mod_version).unwrap_or_default(),
+                hash: module.and_then(|m| hash_mod(m).ok()).unwrap_or_default(),
+                source: get_meta(&dir).source,
+                id: entry.id.clone(),
+                dir,
+                ..Default::default()
+            }
+        })
+        .collect();
+    PortablePack {
+        format: FORMAT_VERSION,
+        name: pack.name.clone(),
+        mods: entries,
+    }
+}
+
+/// The folder an entry goes in, packs come from anywhere so it has to be a plain folder name
+fn entry_dir(entry: &PortableMod) -> Result<&str, String> {
+    match entry.dir.is_empty() || is_folder_name(&entry.dir) {
+        true => Ok(&entry.dir),
+        false => Err(format!(
+            "\"{}\" isn't a valid folder name for {}",
+            entry.dir, entry.name
+        )),
+    }
+}
+
+pub fn read_pack_file(path: &PathBuf) -> Result<PortablePack, Box<dyn Error + Send + Sync>> {
+    let pack: PortablePack = sonic_rs::from_str(&fs::read_to_string(path)?)?;
+    if pack.format > FORMAT_VERSION {
+        return Err("This pack was made by a newer version of Rust4Diva".into());
+    }
+    for entry in pack.mods.iter().filter(|m| !m.separator) {
+        entry_dir(entry)?;
+    }
+    Ok(pack)
+}
+
+fn same_files(module: &DivaMod, entry: &PortableMod) -> bool {
+    entry.hash.is_empty() || hash_mod(module).is_ok_and(|h| h == entry.hash)
+}
+
+/// Finds the installed mod an entry means, by folder name first then by where it was
+/// downloaded from and last by name with identical files
+pub fn match_entry(entry: &PortableMod, mods: &HashMap<String, DivaMod>) -> Match {
+    let checked = |dir: &String, module: &DivaMod| match same_files(module, entry) {
+        true => Match::Installed(dir.clone()),
+        false => Match::Different(dir.clone()),
+    };
+    if let Some(module) = mods.get(&entry.dir) {
+        return checked(&entry.dir, module);
+    }
+    if let Some(dir) = dir_for_id(&entry.id) {
+        if let Some(module) = mods.get(&dir) {
+            return checked(&dir, module);
+        }
+    }
+    if let Some(source) = &entry.source {
+        let found = mods.iter().find(|(dir, _)| {
+            get_meta(dir)
+                .source
+                .is_some_and(|s| s.provider == source.provider && s.id == source.id)
+        });
+        if let Some((dir, module)) = found {
+            return checked(dir, module);
+        }
+    }
+    if !entry.hash.is_empty() {
+        let found = mods.iter().find(|(_, m)| {
+            m.config["name"].as_str() == Some(entry.name.as_str()) && same_files(m, entry)
+        });
+        if let Some((dir, _)) = found {
+            return Match::Installed(dir.clone());
+        }
+    }
+    Match::Missing
+}
+
+/// `name` or the first "name (n)" that isn't taken
+pub fn unique_pack_name(name: &str) -> String {
+    let packs = match MOD_PACKS.try_lock() {
+        Ok(packs) => packs.keys().cloned().collect::<Vec<String>>(),
+        Err(_) => return name.to_owned(),
+    };
+    let name = filenamify(name);
+    if !packs.contains(&name) && name != "All Mods" {
+        return name;
+    }
+    (2..)
+        .map(|n| format!("{name} ({n})"))
+        .find(|n| !packs.contains(n))
+        .unwrap()
+}
+
+/// Turns a portable pack into a real one. Missing mods keep an entry pointing where they'd be
+/// installed so the pack picks them up once they are, and their download source is remembered
+pub fn to_modpack(
+    pack: &PortablePack,
+    name: String,
+    matches: &[Match],
+) -> Result<ModPack, Box<dyn Error + Send + Sync>> {
+    let mods = MODS.try_lock().map_err(|e| e.to_string())?.clone();
+    let mods_folder = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    let mut modpack = ModPack::new(name);
+    for (entry, found) in pack.mods.iter().zip(matches.iter()) {
+        if entry.separator {
+            let mut sep = new_separator(&entry.name);
+            sep.collapsed = entry.collapsed;
+            modpack.mods.push(sep);
+            continue;
+        }
+        let mut packmod: ModPackMod = match found {
+            Match::Installed(dir) | Match::Different(dir) => match mods.get(dir) {
+                Some(module) => module.clone().into(),
+                None => continue,
+            },
+            Match::Missing => {
+                let dir = entry_dir(entry)?;
+                // whatever gets installed in that folder later picks up the id and source
+                if !dir.is_empty() {
+                    let (source, id) = (entry.source.clone(), entry.id.clone());
+                    update_meta(dir, |meta| {
+                        if source.is_some() {
+                            meta.source = source;
+                        }
+                        if meta.id.is_empty() {
+                            meta.id = id;
+                        }
+                    })?;
+                }
+                ModPackMod {
+                    name: entry.name.clone(),
+                    enabled: entry.enabled,
+                    path: mods_folder
+                        .join(dir)
+                        .join("config.toml")
+                        .display()
+                        .to_string(),
+                    separator: false,
+                    collapsed: false,
+                    id: entry.id.clone(),
+                }
+            }
+        };
+        packmod.enabled = entry.enabled;
+        modpack.mods.push(packmod);
+    }
+    Ok(modpack)
+}
+
+/// Adds the pack to `MOD_PACKS`, saves it and shows it in the dropdown
+pub fn add_modpack(ui: &App, modpack: ModPack) -> Result<(), Box<dyn Error + Send + Sync>> {
+    save_modpack_sync(modpack.clone())?;
+    MOD_PACKS
+        .try_lock()
+        .map_err(|e| e.to_string())?
+        .insert(modpack.name.clone(), modpack);
+    refresh_pack_list(ui);
+    Ok(())
+}
+
+fn import_rows(pack: &PortablePack, matches: &[Match], bundled: &[String]) -> Vec<PackImportRow> {
+    pack.mods
+        .iter()
+        .zip(matches.iter())
+        .filter(|(entry, _)| !entry.separator)
+        .map(|(entry, found)| {
+            let version = match entry.version.is_empty() {
+                true => "".to_owned(),
+                false => format!("v{} ", entry.version),
+            };
+            let in_bundle = bundled.contains(&entry.dir);
+            let (status, detail) = match found {
+                Match::Installed(dir) if *dir == entry.dir => (0, "Installed".to_owned()),
+                Match::Installed(dir) => (0, format!("Installed as {dir}")),
+                Match::Different(dir) if in_bundle && *dir == entry.dir => {
+                    (1, format!("{version}replaces the installed one"))
+                }
+                Match::Different(dir) => (1, format!("{dir} has different files")),
+                Match::Missing if in_bundle => (1, format!("{version}installs from the bundle")),
+                Match::Missing => (2, format!("{version}missing")),
+            };
+            PackImportRow {
+                name: entry.name.clone().into(),
+                status,
+                detail: detail.into(),
+                url: entry
+                    .source
+                    .as_ref()
+                    .map(|s| s.url())
+                    .unwrap_or_default()
+                    .into(),
+            }
+        })
+        .collect()
+}
+
+fn is_bundle(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == BUNDLE_EXTENSION)
+}
+
+/// Bundles get unpacked next to the mods folder so installing from them is a rename
+fn staging_folder() -> Option<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let mut buf = get_mods_folder()?;
+    buf.pop();
+    buf.push(format!("rust4diva-import-{stamp}"));
+    Some(buf)
+}
+
+fn extract_bundle(path: &Path) -> Result<(PortablePack, PathBuf), Box<dyn Error + Send + Sync>> {
+    let staging = staging_folder().ok_or("Unable to find the mods folder")?;
+    fs::create_dir_all(&staging)?;
+    let res = uncompress_archive(File::open(path)?, &staging, Ownership::Ignore)
+        .map_err(|e| e.into())
+        .and_then(|_| read_pack_file(&staging.join(BUNDLE_PACK_FILE)));
+    match res {
+        Ok(pack) => Ok((pack, staging)),
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging);
+            Err(e)
+        }
+    }
+}
+
+/// Folder names the bundle actually has files for
+fn bundled_dirs(staging: &Path) -> Vec<String> {
+    match fs::read_dir(staging.join(BUNDLE_MODS_DIR)) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Moves the mods out of an unpacked bundle into the mods folder. Anything already installed
+/// with the same files is left alone, an installed copy with different files goes to the trash
+/// so the import can be undone. Returns the matches as they are after installing
+fn install_from_bundle(
+    staging: &Path,
+    pack: &PortablePack,
+    matches: &[Match],
+) -> Result<Vec<Match>, Box<dyn Error + Send + Sync>> {
+    let mut changes = vec![];
+    let mut after = matches.to_vec();
+    let res = install_entries(staging, pack, &mut after, &mut changes);
+    // whatever got trashed or installed before a failure can still be undone
+    record(format!("Import bundle {}", pack.name), changes);
+    let loaded = load_mods();
+    res?;
+    loaded?;
+    Ok(after)
+}
+
+fn install_entries(
+    staging: &Path,
+    pack: &PortablePack,
+    matches: &mut [Match],
+    changes: &mut Vec<Change>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mods_folder = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    for (i, entry) in pack.mods.iter().enumerate() {
+        if entry.separator {
+            continue;
+        }
+        let dir = entry_dir(entry)?;
+        let src = staging.join(BUNDLE_MODS_DIR).join(dir);
+        if dir.is_empty() || !src.exists() {
+            continue;
+        }
+        match &matches[i] {
+            Match::Installed(_) => continue,
+            Match::Different(installed) if installed == dir => changes.extend(trash_mod(dir)?),
+            _ => {}
+        }
+        let dest = mods_folder.join(dir);
+        if dest.exists() {
+            continue;
+        }
+        if fs::rename(&src, &dest).is_err() {
+            if let Err(e) = copy_dir_all(&src, &dest) {
+                let _ = fs::remove_dir_all(&dest);
+                return Err(e.into());
+            }
+        }
+        if let Some(change) = installed_change(dir) {
+            changes.push(change);
+        }
+        if let Some(source) = entry.source.clone() {
+            update_meta(dir, |meta| meta.source = Some(source))?;
+        }
+        matches[i] = Match::Installed(dir.to_owned());
+    }
+    Ok(())
+}
+
+fn write_pack(pack: &ModPack, dest: &Path, bundle: bool) -> Result<(), String> {
+    let portable = to_portable(pack);
+    let s = sonic_rs::to_string_pretty(&portable).map_err(|e| e.to_string())?;
+    if !bundle {
+        return fs::write(dest, s).map_err(|e| e.to_string());
+    }
+    let mods_folder = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    let dirs: Vec<PathBuf> = portable
+        .mods
+        .iter()
+        .filter(|m| !m.separator && !m.dir.is_empty())
+        .map(|m| mods_folder.join(&m.dir))
+        .filter(|d| d.exists())
+        .collect();
+    export_bundle(s.as_bytes(), &dirs, dest).map_err(|e| e.to_string())
+}
+
+fn open_export(ui: &App, name: String, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let pack = match MOD_PACKS.try_lock() {
+        Ok(packs) => packs.get(&name).cloned(),
+        Err(_) => None,
+    };
+    let Some(pack) = pack else {
+        return;
+    };
+    let dialog = match PackExportDialog::new() {
+        Ok(dialog) => dialog,
+        Err(e) => {
+            open_error_window(e.to_string());
+            return;
+        }
+    };
+    dialog.invoke_set_color_scheme(ui.get_color_scheme());
+    dialog.set_name(pack.name.clone().into());
+
+    let dweak = dialog.as_weak();
+    dialog.on_cancel(move || {
+        dweak.unwrap().hide().unwrap();
+    });
+
+    let dweak = dialog.as_weak();
+    dialog.on_export(move |bundle| {
+        let (filter, ext) = match bundle {
+            true => ("Rust4Diva bundle", BUNDLE_EXTENSION),
+            false => ("Rust4Diva pack", PACK_EXTENSION),
+        };
+        let picker = AsyncFileDialog::new()
+            .add_filter(filter, &[ext])
+            .set_file_name(format!("{}.{ext}", pack.name));
+        let pack = pack.clone();
+        let dweak = dweak.clone();
+        dweak.unwrap().set_exporting(true);
+        tokio::spawn(async move {
+            let Some(file) = picker.save_file().await else {
+                let _ = dweak.upgrade_in_event_loop(|ui| ui.set_exporting(false));
+                return;
+            };
+            let dest = file.path().to_path_buf();
+            let res = {
+                let dest = dest.clone();
+                tokio::task::spawn_blocking(move || write_pack(&pack, &dest, bundle)).await
+            };
+            let res = match res {
+                Ok(res) => res,
+                Err(e) => Err(e.to_string()),
+            };
+            let _ = dweak.upgrade_in_event_loop(move |ui| {
+                ui.set_exporting(false);
+                match res {
+                    Ok(_) => ui.hide().unwrap(),
+                    Err(e) => {
+                        let _ = fs::remove_file(&dest);
+                        open_error_window(format!("Unable to export the pack: \n{e}"));
+                    }
+                }
+            });
+        });
+    });
+
+    let dweak = dialog.as_weak();
+    let mut scheme_rx = dark_rx.resubscribe();
+    let scheme_task = tokio::spawn(async move {
+        while let Ok(scheme) = scheme_rx.recv().await {
+            let _ = dweak.upgrade_in_event_loop(move |ui| {
+                ui.invoke_set_color_scheme(scheme);
+            });
+        }
+    });
+
+    dialog.window().on_close_requested(move || {
+        scheme_task.abort();
+        slint::CloseRequestResponse::HideWindow
+    });
+
+    dialog.show().unwrap();
+}
+
+fn finish_import(main: &App, window: &PackImportWindow, pack: &PortablePack, matches: &[Match]) {
+    let name = unique_pack_name(window.get_pack_name().trim());
+    let res =
+        to_modpack(pack, name.clone(), matches).and_then(|modpack| add_modpack(main, modpack));
+    match res {
+        Ok(_) => {
+            let _ = set_mods_table(&get_mods(), main.as_weak());
+            main.global::<ModpackLogic>()
+                .invoke_change_modpack(name.into());
+            window.invoke_close();
+        }
+        Err(e) => open_error_window(format!("Unable to import the pack: \n{e}")),
+    }
+}
+
+fn show_import(ui: Weak<App>, path: PathBuf, dark_rx: broadcast::Receiver<ColorScheme>) {
+    tokio::spawn(async move {
+        let res = tokio::task::spawn_blocking(move || {
+            let (pack, staging) = match is_bundle(&path) {
+                true => {
+                    let (pack, staging) = extract_bundle(&path).map_err(|e| e.to_string())?;
+                    (pack, Some(staging))
+                }
+                false => (read_pack_file(&path).map_err(|e| e.to_string())?, None),
+            };
+            let mods = MODS.try_lock().map_err(|e| e.to_string())?.clone();
+            let matches: Vec<Match> = pack.mods.iter().map(|e| match_entry(e, &mods)).collect();
+            Ok::<_, String>((pack, matches, staging))
+        })
+        .await;
+        let (pack, matches, staging) = match res {
+            Ok(Ok(res)) => res,
+            Ok(Err(e)) => {
+                open_error_window(format!("Unable to read the pack: \n{e}"));
+                return;
+            }
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        let _ = ui.clone().upgrade_in_event_loop(move |main| {
+            let window = match PackImportWindow::new() {
+                Ok(window) => window,
+                Err(e) => {
+                    open_error_window(e.to_string());
+                    return;
+                }
+            };
+            window.invoke_set_color_scheme(main.get_color_scheme());
+            window.set_pack_name(unique_pack_name(&pack.name).into());
+            window.set_bundle(staging.is_some());
+            let bundled = staging.as_deref().map(bundled_dirs).unwrap_or_default();
+            let rows = import_rows(&pack, &matches, &bundled);
+            window.set_missing(rows.iter().filter(|r| r.status == 2).count() as i32);
+            window.set_rows(ModelRc::new(VecModel::from(rows)));
+
+            window.on_open_url(|url| {
+                if let Err(e) = open::that(url.to_string()) {
+                    open_error_window(e.to_string());
+                }
+            });
+
+            let window_weak = window.as_weak();
+            let import_staging = staging.clone();
+            window.on_import(move || {
+                let window = window_weak.unwrap();
+                let Some(staging) = import_staging.clone() else {
+                    finish_import(&ui.unwrap(), &window, &pack, &matches);
+                    return;
+                };
+                // copying mods can take a while so it happens off the ui thread
+                window.set_busy(true);
+                let (pack, matches) = (pack.clone(), matches.clone());
+                let (ui, window_weak) = (ui.clone(), window_weak.clone());
+                tokio::spawn(async move {
+                    let res = tokio::task::spawn_blocking({
+                        let (pack, staging) = (pack.clone(), staging.clone());
+                        move || {
+                            install_from_bundle(&staging, &pack, &matches)
+                                .map_err(|e| e.to_string())
+                        }
+                    })
+                    .await;
+                    let _ = window_weak.upgrade_in_event_loop(move |window| {
+                        window.set_busy(false);
+                        match res {
+                            Ok(Ok(matches)) => {
+                                finish_import(&ui.unwrap(), &window, &pack, &matches)
+                            }
+                            Ok(Err(e)) => {
+                                open_error_window(format!("Unable to install the bundle: \n{e}"))
+                            }
+                            Err(e) => open_error_window(e.to_string()),
+                        }
+                    });
+                });
+            });
+
+            let weak = window.as_weak();
+            let mut scheme_rx = dark_rx.resubscribe();
+            let scheme_task = tokio::spawn(async move {
+                while let Ok(scheme) = scheme_rx.recv().await {
+                    let _ = weak.upgrade_in_event_loop(move |ui| {
+                        ui.invoke_set_color_scheme(scheme);
+                    });
+                }
+            });
+
+            // the unpacked bundle is kept until the window goes away, so a failed
+            // install can be retried
+            window.on_close({
+                let window_weak = window.as_weak();
+                let scheme_task = scheme_task.abort_handle();
+                move || {
+                    scheme_task.abort();
+                    if let Some(staging) = staging.clone() {
+                        tokio::task::spawn_blocking(move || fs::remove_dir_all(staging));
+                    }
+                    window_weak.unwrap().hide().unwrap();
+                }
+            });
+
+            let window_weak = window.as_weak();
+            window.window().on_close_requested(move || {
+                window_weak.unwrap().invoke_close();
+                slint::CloseRequestResponse::HideWindow
+            });
+
+            window.show().unwrap();
+        });
+    });
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    let export_rx = dark_rx.resubscribe();
+    ui.global::<ModpackLogic>().on_export_pack(move |name| {
+        open_export(&weak.unwrap(), name.to_string(), export_rx.resubscribe());
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>().on_import_pack(move || {
+        let weak = weak.clone();
+        let dark_rx = dark_rx.resubscribe();
+        let picker = AsyncFileDialog::new()
+            .add_filter("Rust4Diva pack", &[PACK_EXTENSION, BUNDLE_EXTENSION]);
+        tokio::spawn(async move {
+            if let Some(file) = picker.pick_file().await {
+                show_import(weak, file.path().to_path_buf(), dark_rx);
+            }
+        });
+    });
+}
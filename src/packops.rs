@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use filenamify::filenamify;
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use tokio::sync::broadcast;
+
+use crate::config::write_config_sync;
+use crate::diva::open_error_window;
+use crate::history::{record, rename_pack_history, Change};
+use crate::layers::{children_of, set_parent};
+use crate::modpacks::{
+    get_modpacks_folder, refresh_pack_list, save_modpack_sync, ModPack, ModPackMod,
+};
+use crate::revisions::rename_revisions;
+use crate::slint_generatedApp::App;
+use crate::{MergePacksDialog, ModpackLogic, MOD_PACKS, R4D_CFG};
+
+/// What the merge dialog's combo boxes pick between
+const ORDER_OTHER: i32 = 1;
+const ENABLED_OTHER: i32 = 1;
+const ENABLED_EITHER: i32 = 2;
+const ENABLED_BOTH: i32 = 3;
+
+fn pack_file(name: &str) -> std::io::Result<PathBuf> {
+    Ok(get_modpacks_folder()?.join(filenamify(name) + ".json"))
+}
+
+/// Cleans up a name for a new (or renamed) pack, `current` is the pack being renamed
+fn check_name(
+    name: &str,
+    current: Option<&str>,
+    packs: &HashMap<String, ModPack>,
+) -> Result<String, String> {
+    let name = filenamify(name.trim());
+    if name.is_empty() || name == "All Mods" {
+        return Err(format!("\"{name}\" can't be used as a pack name"));
+    }
+    // the json files would clash on windows even if the names don't
+    let taken = packs
+        .keys()
+        .filter(|k| Some(k.as_str()) != current)
+        .any(|k| k.to_lowercase() == name.to_lowercase());
+    match taken {
+        true => Err(format!("There's already a pack called {name}")),
+        false => Ok(name),
+    }
+}
+
+/// Copies a pack under a new name, returns the name it got
+pub fn duplicate_pack(from: &str, name: &str) -> Result<ModPack, Box<dyn Error + Send + Sync>> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let name = check_name(name, None, &packs)?;
+    let mut pack = packs
+        .get(from)
+        .cloned()
+        .ok_or(format!("There's no pack called {from}"))?;
+    pack.name = name.clone();
+    save_modpack_sync(pack.clone())?;
+    packs.insert(name, pack.clone());
+    Ok(pack)
+}
+
+/// A new pack layered on `parent`, it starts out with the same list
+pub fn layer_pack(parent: &str, name: &str) -> Result<ModPack, Box<dyn Error + Send + Sync>> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let name = check_name(name, None, &packs)?;
+    if !packs.contains_key(parent) {
+        return Err(format!("There's no pack called {parent}").into());
+    }
+    packs.insert(name.clone(), ModPack::new(name.clone()));
+    let pack = match set_parent(&mut packs, &name, parent) {
+        Ok(mut pack) => {
+            pack.mods = pack.inherited.clone();
+            pack
+        }
+        Err(e) => {
+            packs.remove(&name);
+            return Err(e.into());
+        }
+    };
+    save_modpack_sync(pack.clone())?;
+    packs.insert(name, pack.clone());
+    Ok(pack)
+}
+
+/// Renames a pack and its json file, and follows it with `applied_pack` and the history
+pub fn rename_pack(from: &str, to: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let to = check_name(to, Some(from), &packs)?;
+    let mut pack = packs
+        .get(from)
+        .cloned()
+        .ok_or(format!("There's no pack called {from}"))?;
+    pack.name = to.clone();
+    let (old_file, new_file) = (pack_file(from)?, pack_file(&to)?);
+    // renaming first means a case only change doesn't end up with the old file deleted
+    if old_file.exists() {
+        fs::rename(&old_file, &new_file)?;
+    }
+    if let Err(e) = rename_revisions(from, &to) {
+        eprintln!("Unable to move the revisions of {from}: {e}");
+    }
+    if let Err(e) = save_modpack_sync(pack.clone()) {
+        let _ = fs::rename(&new_file, &old_file);
+        let _ = rename_revisions(&to, from);
+        return Err(e.into());
+    }
+    packs.remove(from);
+    packs.insert(to.clone(), pack);
+    for child in children_of(&packs, from) {
+        if let Some(child) = packs.get_mut(&child) {
+            child.parent = to.clone();
+            save_modpack_sync(child.clone())?;
+        }
+    }
+    drop(packs);
+
+    let mut cfg = R4D_CFG.try_lock().map_err(|e| e.to_string())?;
+    if cfg.applied_pack == from {
+        cfg.applied_pack = to.clone();
+        write_config_sync(cfg.clone())?;
+    }
+    drop(cfg);
+    rename_pack_history(from, &to);
+    Ok(())
+}
+
+/// `primary` with whatever only `secondary` has slotted in after the entry it follows there
+fn weave(primary: &[ModPackMod], secondary: &[ModPackMod]) -> Vec<ModPackMod> {
+    let mut list = primary.to_vec();
+    for (i, entry) in secondary.iter().enumerate() {
+        if list.contains(entry) {
+            continue;
+        }
+        let at = match i {
+            0 => 0,
+            _ => list
+                .iter()
+                .position(|m| *m == secondary[i - 1])
+                .map(|p| p + 1)
+                .unwrap_or(list.len()),
+        };
+        list.insert(at, entry.clone());
+    }
+    list
+}
+
+fn enabled_in(list: &[ModPackMod], entry: &ModPackMod) -> Option<bool> {
+    list.iter().find(|m| *m == entry).map(|m| m.enabled)
+}
+
+/// Both packs in one list. `order` picks whose order wins, the other pack's extras go after
+/// whatever they follow in it. `enabled` settles mods that are in both but only on in one
+pub fn merge_lists(base: &ModPack, other: &ModPack, order: i32, enabled: i32) -> Vec<ModPackMod> {
+    let mut list = match order {
+        ORDER_OTHER => weave(&other.mods, &base.mods),
+        _ => weave(&base.mods, &other.mods),
+    };
+    for entry in list.iter_mut().filter(|m| !m.separator) {
+        entry.enabled = match (
+            enabled_in(&base.mods, entry),
+            enabled_in(&other.mods, entry),
+        ) {
+            (Some(a), Some(b)) => match enabled {
+                ENABLED_OTHER => b,
+                ENABLED_EITHER => a || b,
+                ENABLED_BOTH => a && b,
+                _ => a,
+            },
+            (Some(a), None) => a,
+            (None, Some(b)) => b,
+            (None, None) => entry.enabled,
+        };
+    }
+    list
+}
+
+/// One line on what a merge would have to settle
+fn merge_summary(base: &ModPack, other: &ModPack) -> String {
+    let mods = |pack: &ModPack| -> Vec<ModPackMod> {
+        pack.mods.iter().filter(|m| !m.separator).cloned().collect()
+    };
+    let (ours, theirs) = (mods(base), mods(other));
+    let shared: Vec<&ModPackMod> = ours.iter().filter(|m| theirs.contains(m)).collect();
+    let shared_theirs: Vec<&ModPackMod> = theirs.iter().filter(|m| ours.contains(m)).collect();
+    let only_theirs = theirs.len() - shared_theirs.len();
+    let toggled = shared
+        .iter()
+        .filter(|m| enabled_in(&theirs, m).is_some_and(|e| e != m.enabled))
+        .count();
+    let moved = shared
+        .iter()
+        .zip(shared_theirs.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    format!(
+        "{} mods are in both packs and {only_theirs} only in {}. {toggled} of the shared ones are \
+         enabled differently and {moved} are in a different spot.",
+        shared.len(),
+        other.name
+    )
+}
+
+fn reload(ui: &App, pack: &str) {
+    refresh_pack_list(ui);
+    ui.global::<ModpackLogic>()
+        .invoke_change_modpack(pack.into());
+}
+
+fn open_merge(ui: &App, base: String, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let others: Vec<SharedString> = match MOD_PACKS.try_lock() {
+        Ok(packs) => {
+            let mut names: Vec<SharedString> = packs
+                .keys()
+                .filter(|k| **k != base)
+                .map(|k| k.into())
+                .collect();
+            names.sort_by_key(|s| s.to_lowercase());
+            names
+        }
+        Err(_) => return,
+    };
+    if others.is_empty() {
+        open_error_window("There's no other pack to merge with".to_owned());
+        return;
+    }
+    let dialog = match MergePacksDialog::new() {
+        Ok(dialog) => dialog,
+        Err(e) => {
+            open_error_window(e.to_string());
+            return;
+        }
+    };
+    dialog.invoke_set_color_scheme(ui.get_color_scheme());
+    dialog.set_base(base.clone().into());
+    dialog.set_pack_name(base.into());
+    dialog.set_others(ModelRc::new(VecModel::from(others)));
+
+    let dweak = dialog.as_weak();
+    dialog.on_other_changed(move |other| {
+        let dialog = dweak.unwrap();
+        let summary = match MOD_PACKS.try_lock() {
+            Ok(packs) => match (
+                packs.get(dialog.get_base().as_str()),
+                packs.get(other.as_str()),
+            ) {
+                (Some(base), Some(other)) => merge_summary(base, other),
+                _ => String::new(),
+            },
+            Err(_) => String::new(),
+        };
+        dialog.set_summary(summary.into());
+    });
+
+    let main_weak = ui.as_weak();
+    let dweak = dialog.as_weak();
+    dialog.on_merge(move |other, name, order, enabled| {
+        let dialog = dweak.unwrap();
+        let base = dialog.get_base().to_string();
+        let res = (|| -> Result<String, Box<dyn Error + Send + Sync>> {
+            let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+            let (Some(ours), Some(theirs)) = (packs.get(&base), packs.get(other.as_str())) else {
+                return Err("One of the packs is gone".into());
+            };
+            let merged = merge_lists(ours, theirs, order, enabled);
+            // merging into the pack it was opened on just edits it
+            if filenamify(name.trim()) == base {
+                let pack = packs.get_mut(&base).ok_or("The pack is gone")?;
+                let before = pack.mods.clone();
+                pack.mods = merged;
+                save_modpack_sync(pack.clone())?;
+                record(
+                    format!("Merge {other} into {base}"),
+                    vec![Change::Order {
+                        pack: base.clone(),
+                        before,
+                        after: pack.mods.clone(),
+                    }],
+                );
+                return Ok(base.clone());
+            }
+            let name = check_name(&name, None, &packs)?;
+            let mut pack = ModPack::new(name.clone());
+            pack.mods = merged;
+            save_modpack_sync(pack.clone())?;
+            packs.insert(name.clone(), pack.clone());
+            record(
+                format!("Merge {base} and {other} into {name}"),
+                vec![Change::PackCreated { pack }],
+            );
+            Ok(name)
+        })();
+        match res {
+            Ok(name) => {
+                reload(&main_weak.unwrap(), &name);
+                dialog.hide().unwrap();
+            }
+            Err(e) => open_error_window(format!("Unable to merge the packs: \n{e}")),
+        }
+    });
+
+    let dweak = dialog.as_weak();
+    dialog.on_cancel(move || {
+        dweak.unwrap().hide().unwrap();
+    });
+
+    let dweak = dialog.as_weak();
+    let mut scheme_rx = dark_rx.resubscribe();
+    let scheme_task = tokio::spawn(async move {
+        while let Ok(scheme) = scheme_rx.recv().await {
+            let _ = dweak.upgrade_in_event_loop(move |ui| {
+                ui.invoke_set_color_scheme(scheme);
+            });
+        }
+    });
+
+    dialog.window().on_close_requested(move || {
+        scheme_task.abort();
+        slint::CloseRequestResponse::HideWindow
+    });
+
+    dialog.invoke_other_changed(dialog.get_others().row_data(0).unwrap_or_default());
+    dialog.show().unwrap();
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>()
+        .on_duplicate_pack(move |from, name| match duplicate_pack(&from, &name) {
+            Ok(pack) => {
+                let name = pack.name.clone();
+                record(
+                    format!("Duplicate {from} as {name}"),
+                    vec![Change::PackCreated { pack }],
+                );
+                reload(&weak.unwrap(), &name);
+            }
+            Err(e) => open_error_window(format!("Unable to duplicate {from}: \n{e}")),
+        });
+
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>().on_rename_pack(move |from, to| {
+        let to = filenamify(to.trim());
+        if *from == to {
+            return;
+        }
+        match rename_pack(&from, &to) {
+            Ok(_) => {
+                record(
+                    format!("Rename pack {from} to {to}"),
+                    vec![Change::PackRenamed {
+                        from: from.to_string(),
+                        to: to.clone(),
+                    }],
+                );
+                reload(&weak.unwrap(), &to);
+            }
+            Err(e) => open_error_window(format!("Unable to rename {from}: \n{e}")),
+        }
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>()
+        .on_layer_pack(move |parent, name| match layer_pack(&parent, &name) {
+            Ok(pack) => {
+                let name = pack.name.clone();
+                record(
+                    format!("Layer {name} on {parent}"),
+                    vec![Change::PackCreated { pack }],
+                );
+                reload(&weak.unwrap(), &name);
+            }
+            Err(e) => open_error_window(format!("Unable to make the pack: \n{e}")),
+        });
+
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>().on_detach_pack(move |name| {
+        let res = match MOD_PACKS.try_lock() {
+            Ok(mut packs) => {
+                let parent = packs.get(name.as_str()).map(|p| p.parent.clone());
+                set_parent(&mut packs, &name, "").map(|pack| (pack, parent.unwrap_or_default()))
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        match res.and_then(|(pack, parent)| {
+            save_modpack_sync(pack).map_err(|e| e.to_string())?;
+            Ok(parent)
+        }) {
+            Ok(parent) => {
+                record(
+                    format!("Detach {name} from {parent}"),
+                    vec![Change::PackParent {
+                        pack: name.to_string(),
+                        before: parent,
+                        after: String::new(),
+                    }],
+                );
+                reload(&weak.unwrap(), &name);
+            }
+            Err(e) => open_error_window(format!("Unable to detach {name}: \n{e}")),
+        }
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>().on_merge_packs(move |base| {
+        open_merge(&weak.unwrap(), base.to_string(), dark_rx.resubscribe());
+    });
+}
@@ -0,0 +1,708 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::sync::broadcast;
+use toml_edit::DocumentMut;
+
+use crate::diva::open_error_window;
+use crate::history::{move_mod_path, record, trash_mod, Change};
+use crate::modmanagement::{get_mods, get_mods_folder, load_mods, set_mods_table, DivaMod};
+use crate::slint_generatedApp::App;
+use crate::{PathCheckWindow, PathIssue, WindowLogic};
+
+/// A path problem that only shows up when the game runs under Proton/Wine, where windows expects
+/// paths to be case-insensitive but the files on disk are not
+#[derive(Clone, Debug)]
+pub enum CaseIssue {
+    /// An `include` or `dll` entry in the mod's config.toml whose casing doesn't match the disk
+    ConfigMismatch {
+        mod_name: String,
+        config_path: PathBuf,
+        field: String,
+        index: usize,
+        configured: String,
+        actual: String,
+    },
+    /// An `include` or `dll` entry that doesn't exist with any casing
+    Missing {
+        mod_name: String,
+        field: String,
+        configured: String,
+    },
+    /// Entries in the same folder whose names only differ by case, wine will only ever see one.
+    /// `keep` is the one fixing it leaves, None when it's a mix of files and folders
+    Duplicates {
+        mod_name: String,
+        parent: PathBuf,
+        names: Vec<String>,
+        keep: Option<String>,
+    },
+    /// Mod folders that only differ by case, these are separate mods so they're left to the user
+    ModFolders { names: Vec<String> },
+}
+
+impl CaseIssue {
+    pub fn is_fixable(&self) -> bool {
+        match self {
+            CaseIssue::Missing { .. } | CaseIssue::ModFolders { .. } => false,
+            CaseIssue::Duplicates { keep, .. } => keep.is_some(),
+            CaseIssue::ConfigMismatch { .. } => true,
+        }
+    }
+}
+
+impl From<CaseIssue> for PathIssue {
+    fn from(value: CaseIssue) -> Self {
+        let fixable = value.is_fixable();
+        match value {
+            CaseIssue::ConfigMismatch {
+                mod_name,
+                field,
+                configured,
+                actual,
+                ..
+            } => PathIssue {
+                mod_name: mod_name.into(),
+                description: format!("{field}: \"{configured}\" is \"{actual}\" on disk").into(),
+                fixable,
+            },
+            CaseIssue::Missing {
+                mod_name,
+                field,
+                configured,
+            } => PathIssue {
+                mod_name: mod_name.into(),
+                description: format!("{field}: \"{configured}\" does not exist").into(),
+                fixable,
+            },
+            CaseIssue::Duplicates {
+                mod_name,
+                parent,
+                names,
+                keep,
+            } => {
+                let dirs = is_real_dir(&parent.join(&names[0]));
+                let plan = match keep {
+                    Some(keep) if dirs => format!(
+                        ". Fixing merges them into \"{keep}\", where a file is in more than one \
+                         the newest copy is kept and the rest goes to the trash"
+                    ),
+                    Some(keep) => format!(
+                        ". Fixing keeps \"{keep}\", the newest, and moves the others to the trash"
+                    ),
+                    None => " (a mix of files and folders)".to_owned(),
+                };
+                PathIssue {
+                    mod_name: mod_name.into(),
+                    description: format!(
+                        "{} contains entries that only differ by case: {}{plan}",
+                        parent.display(),
+                        names.join(", ")
+                    )
+                    .into(),
+                    fixable,
+                }
+            }
+            CaseIssue::ModFolders { names } => PathIssue {
+                mod_name: "".into(),
+                description: format!(
+                    "Mod folders that only differ by case: {}. Wine only sees one of them, \
+                     rename or remove the others",
+                    names.join(", ")
+                )
+                .into(),
+                fixable,
+            },
+        }
+    }
+}
+
+fn split_components(path: &str) -> Vec<&str> {
+    path.split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect()
+}
+
+/// Finds the entry in `dir` named `name`, ignoring case. An exact match always wins.
+pub fn find_entry_ignore_case(dir: &Path, name: &str) -> Option<String> {
+    let entries = fs::read_dir(dir).ok()?;
+    let lower = name.to_lowercase();
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|n| n.to_lowercase() == lower)
+        .collect();
+    if candidates.iter().any(|n| n == name) {
+        return Some(name.to_owned());
+    }
+    candidates.sort();
+    candidates.first().cloned()
+}
+
+/// Resolves a path relative to `base` the way windows would, returns the path with the casing
+/// that is actually used on disk
+pub fn resolve_ignore_case(base: &Path, relative: &str) -> Option<String> {
+    let mut buf = base.to_path_buf();
+    let mut resolved: Vec<String> = vec![];
+    for component in split_components(relative) {
+        if component == ".." {
+            buf.pop();
+            resolved.push(component.to_owned());
+            continue;
+        }
+        let actual = find_entry_ignore_case(&buf, component)?;
+        buf.push(&actual);
+        resolved.push(actual);
+    }
+    Some(resolved.join("/"))
+}
+
+/// Returns true if both paths point at the same place once separators and `.` are ignored
+fn same_path(left: &str, right: &str) -> bool {
+    split_components(left) == split_components(right)
+}
+
+fn config_entries(config: &DocumentMut, field: &str) -> Vec<String> {
+    match config.get(field).and_then(|i| i.as_array()) {
+        Some(entries) => entries
+            .iter()
+            .filter_map(|e| e.as_str())
+            .map(|e| e.to_owned())
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Folder check that doesn't follow symlinks, a link back up the tree would never end otherwise
+fn is_real_dir(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.is_dir())
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    fs::symlink_metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Names in `dir` that only differ by case, along with the real folders in it
+fn same_names(dir: &Path) -> (Vec<Vec<String>>, Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (vec![], vec![]);
+    };
+    let mut names: HashMap<String, Vec<String>> = HashMap::new();
+    let mut subdirs = vec![];
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_real_dir(&entry.path()) {
+            subdirs.push(entry.path());
+        }
+        names.entry(name.to_lowercase()).or_default().push(name);
+    }
+    let mut groups: Vec<Vec<String>> = names.into_values().filter(|g| g.len() > 1).collect();
+    for group in groups.iter_mut() {
+        group.sort();
+    }
+    groups.sort();
+    (groups, subdirs)
+}
+
+/// Walks `dir` looking for sibling entries that only differ by case
+pub fn find_duplicate_names(dir: &Path) -> Vec<(PathBuf, Vec<String>)> {
+    let (groups, subdirs) = same_names(dir);
+    let mut found: Vec<(PathBuf, Vec<String>)> = groups
+        .into_iter()
+        .map(|group| (dir.to_path_buf(), group))
+        .collect();
+    for sub in subdirs {
+        found.append(&mut find_duplicate_names(&sub));
+    }
+    found
+}
+
+pub fn scan_mod(module: &DivaMod) -> Vec<CaseIssue> {
+    let mut issues = vec![];
+    let config_path = PathBuf::from(module.path.clone());
+    let mut mod_dir = config_path.clone();
+    mod_dir.pop();
+    let mod_name = module.config["name"].as_str().unwrap_or("").to_owned();
+
+    for field in ["include", "dll"] {
+        for (index, configured) in config_entries(&module.config, field).iter().enumerate() {
+            match resolve_ignore_case(&mod_dir, configured) {
+                Some(actual) => {
+                    if !same_path(&actual, configured) {
+                        issues.push(CaseIssue::ConfigMismatch {
+                            mod_name: mod_name.clone(),
+                            config_path: config_path.clone(),
+                            field: field.to_owned(),
+                            index,
+                            configured: configured.clone(),
+                            actual,
+                        });
+                    }
+                }
+                None => issues.push(CaseIssue::Missing {
+                    mod_name: mod_name.clone(),
+                    field: field.to_owned(),
+                    configured: configured.clone(),
+                }),
+            }
+        }
+    }
+
+    // every path component the mod's config refers to
+    let mut preferred = config_entries(&module.config, "include");
+    preferred.append(&mut config_entries(&module.config, "dll"));
+    let preferred: Vec<String> = preferred
+        .iter()
+        .flat_map(|e| split_components(e))
+        .map(|c| c.to_owned())
+        .collect();
+    for (parent, names) in find_duplicate_names(&mod_dir) {
+        let keep = pick_keep(&parent, &names, &preferred);
+        issues.push(CaseIssue::Duplicates {
+            mod_name: mod_name.clone(),
+            parent,
+            names,
+            keep,
+        });
+    }
+    issues
+}
+
+pub fn scan_mods(mods: &[DivaMod]) -> Vec<CaseIssue> {
+    let mut mods = mods.to_vec();
+    mods.sort_by_key(|m| m.config["name"].as_str().unwrap_or("").to_lowercase());
+    // the mod folders themselves can clash too
+    let mut issues: Vec<CaseIssue> = match get_mods_folder() {
+        Some(folder) => same_names(&folder)
+            .0
+            .into_iter()
+            .map(|names| CaseIssue::ModFolders { names })
+            .collect(),
+        None => vec![],
+    };
+    issues.extend(mods.iter().flat_map(scan_mod));
+    issues
+}
+
+/// One step of fixing a duplicate, they're run through the history so the fix can be undone
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Trash(PathBuf),
+    Move(PathBuf, PathBuf),
+}
+
+/// What moving everything from `src` into `dest` takes, entries that exist in both (ignoring
+/// case) are merged and the newest file is kept. The older copies stay behind in `src`.
+/// Something that's a file on one side and a folder on the other stops the merge before anything
+/// is touched
+fn merge_dirs(src: &Path, dest: &Path) -> Result<Vec<Step>, String> {
+    let mut steps = vec![];
+    let mut entries: Vec<PathBuf> = fs::read_dir(src)
+        .map_err(|e| format!("{}: {e}", src.display()))?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+    for from in entries {
+        let name = from
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        match find_entry_ignore_case(dest, &name) {
+            Some(existing) => {
+                let to = dest.join(existing);
+                match (is_real_dir(&from), is_real_dir(&to)) {
+                    (true, true) => steps.append(&mut merge_dirs(&from, &to)?),
+                    (false, false) => {
+                        if modified(&from) > modified(&to) {
+                            steps.push(Step::Trash(to.clone()));
+                            steps.push(Step::Move(from, to));
+                        }
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{} is a file on one side and a folder on the other",
+                            to.display()
+                        ))
+                    }
+                }
+            }
+            None => steps.push(Step::Move(from, dest.join(&name))),
+        }
+    }
+    Ok(steps)
+}
+
+/// Which of the names fixing a duplicate leaves. Folders keep the one the config.toml refers to,
+/// otherwise the one with the most entries, files keep the newest.
+/// None when it's files and folders mixed, there's no sensible way to merge those
+fn pick_keep(parent: &Path, names: &[String], preferred: &[String]) -> Option<String> {
+    let dirs = names
+        .iter()
+        .filter(|n| is_real_dir(&parent.join(n)))
+        .count();
+    if dirs == 0 {
+        return names
+            .iter()
+            .max_by_key(|n| modified(&parent.join(n)))
+            .cloned();
+    }
+    if dirs != names.len() {
+        return None;
+    }
+    match names.iter().find(|n| preferred.contains(n)) {
+        Some(keep) => Some(keep.clone()),
+        None => names
+            .iter()
+            .max_by_key(|n| fs::read_dir(parent.join(n)).map(|d| d.count()).unwrap_or(0))
+            .cloned(),
+    }
+}
+
+/// `path` the way the history stores it, relative to the mods folder
+fn mods_relative(mods: &Path, path: &Path) -> Result<String, String> {
+    match path.strip_prefix(mods) {
+        Ok(rel) => Ok(rel.display().to_string()),
+        Err(_) => Err(format!("{} isn't in the mods folder", path.display())),
+    }
+}
+
+/// Runs the steps, the changes that made it are added to `changes` even when one fails
+fn run_steps(steps: Vec<Step>, changes: &mut Vec<Change>) -> Result<(), String> {
+    let mods = get_mods_folder().ok_or("Unable to find the mods folder")?;
+    for step in steps {
+        match step {
+            Step::Trash(path) => changes.extend(trash_mod(&mods_relative(&mods, &path)?)?),
+            Step::Move(from, to) => changes.push(move_mod_path(
+                &mods_relative(&mods, &from)?,
+                &mods_relative(&mods, &to)?,
+            )?),
+        }
+    }
+    Ok(())
+}
+
+/// Merges the other names into `keep`, whatever's left over goes to the trash
+fn fix_duplicates(
+    parent: &Path,
+    names: &[String],
+    keep: &str,
+    changes: &mut Vec<Change>,
+) -> Result<(), String> {
+    let dest = parent.join(keep);
+    if fs::symlink_metadata(&dest).is_err() {
+        return Err(format!("{keep} is gone, scan again before fixing"));
+    }
+    for name in names.iter().filter(|n| *n != keep) {
+        let src = parent.join(name);
+        let mut steps = match (is_real_dir(&dest), is_real_dir(&src)) {
+            (true, true) => merge_dirs(&src, &dest)?,
+            (false, false) => vec![],
+            _ => {
+                return Err(format!(
+                    "{name} and {keep} aren't both files or both folders"
+                ))
+            }
+        };
+        steps.push(Step::Trash(src));
+        run_steps(steps, changes)?;
+    }
+    Ok(())
+}
+
+/// Fixes everything that can be fixed, folders are merged before the config.toml files are
+/// rewritten since merging can change what the config should point at.
+/// Returns the errors that happened along the way.
+pub fn fix_issues(issues: &[CaseIssue]) -> Vec<String> {
+    let mut errors = vec![];
+    let mut changes = vec![];
+    let mods = get_mods();
+    for issue in issues {
+        if let CaseIssue::Duplicates {
+            parent,
+            names,
+            keep: Some(keep),
+            ..
+        } = issue
+        {
+            if let Err(e) = fix_duplicates(parent, names, keep, &mut changes) {
+                errors.push(format!("{}: {e}", parent.display()));
+            }
+        }
+    }
+    record("Fix path casing", changes);
+
+    // merging may have changed which casing survived, so check the configs again
+    let mods = match load_mods() {
+        Ok(_) => get_mods(),
+        Err(e) => {
+            errors.push(e.to_string());
+            mods
+        }
+    };
+    let mut configs: HashMap<PathBuf, DocumentMut> = HashMap::new();
+    for issue in scan_mods(&mods) {
+        if let CaseIssue::ConfigMismatch {
+            config_path,
+            field,
+            index,
+            actual,
+            ..
+        } = issue
+        {
+            // work on the file itself so the name load_mods defaults to doesn't end up on disk
+            if !configs.contains_key(&config_path) {
+                match fs::read_to_string(&config_path).map(|s| s.parse::<DocumentMut>()) {
+                    Ok(Ok(config)) => {
+                        configs.insert(config_path.clone(), config);
+                    }
+                    Ok(Err(e)) => {
+                        errors.push(format!("{}: {e}", config_path.display()));
+                        continue;
+                    }
+                    Err(e) => {
+                        errors.push(format!("{}: {e}", config_path.display()));
+                        continue;
+                    }
+                }
+            }
+            let Some(config) = configs.get_mut(&config_path) else {
+                continue;
+            };
+            if let Some(entries) = config.get_mut(&field).and_then(|i| i.as_array_mut()) {
+                entries.replace(index, actual);
+            }
+        }
+    }
+    for (config_path, config) in configs {
+        if let Err(e) = fs::write(&config_path, config.to_string()) {
+            errors.push(format!("{}: {e}", config_path.display()));
+        }
+    }
+    errors
+}
+
+fn set_issues(window: &PathCheckWindow, issues: &[CaseIssue]) {
+    let model: Vec<PathIssue> = issues.iter().cloned().map(|i| i.into()).collect();
+    window.set_fixable(issues.iter().any(|i| i.is_fixable()));
+    window.set_issues(ModelRc::new(VecModel::from(model)));
+    window.set_scanning(false);
+}
+
+fn scan_into(window: Weak<PathCheckWindow>, found: Arc<Mutex<Vec<CaseIssue>>>) {
+    tokio::spawn(async move {
+        let issues = scan_mods(&get_mods());
+        if let Ok(mut found) = found.lock() {
+            *found = issues.clone();
+        }
+        let _ = window.upgrade_in_event_loop(move |window| {
+            set_issues(&window, &issues);
+        });
+    });
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_path_check(move || {
+        let ui = weak.unwrap();
+        let window = match PathCheckWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        window.set_scanning(true);
+
+        let found: Arc<Mutex<Vec<CaseIssue>>> = Arc::new(Mutex::new(vec![]));
+        scan_into(window.as_weak(), found.clone());
+
+        let window_weak = window.as_weak();
+        let rescan_found = found.clone();
+        window.on_rescan(move || {
+            window_weak.unwrap().set_scanning(true);
+            scan_into(window_weak.clone(), rescan_found.clone());
+        });
+
+        let window_weak = window.as_weak();
+        let main_weak = weak.clone();
+        window.on_fix_all(move || {
+            window_weak.unwrap().set_scanning(true);
+            let issues = match found.lock() {
+                Ok(found) => found.clone(),
+                Err(_) => return,
+            };
+            let window_weak = window_weak.clone();
+            let main_weak = main_weak.clone();
+            let found = found.clone();
+            tokio::spawn(async move {
+                let errors = fix_issues(&issues);
+                if !errors.is_empty() {
+                    open_error_window(format!("Unable to fix some paths:\n{}", errors.join("\n")));
+                }
+                if load_mods().is_ok() {
+                    let _ = set_mods_table(&get_mods(), main_weak);
+                }
+                scan_into(window_weak, found);
+            });
+        });
+
+        let weak = window.as_weak();
+        window.on_close(move || {
+            weak.unwrap().hide().unwrap();
+        });
+
+        let weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, SystemTime};
+
+    /// A fresh temp folder holding `files`, folders end in a slash
+    fn test_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("rust4diva-pathcase-{}", std::process::id()))
+            .join(name);
+        let _ = fs::remove_dir_all(&dir);
+        for file in files {
+            let path = dir.join(file);
+            match file.ends_with('/') {
+                true => fs::create_dir_all(path).unwrap(),
+                false => {
+                    fs::create_dir_all(path.parent().unwrap()).unwrap();
+                    fs::write(path, file.as_bytes()).unwrap();
+                }
+            }
+        }
+        dir
+    }
+
+    fn age(path: &Path, secs: u64) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn resolves_the_casing_on_disk() {
+        let dir = test_dir("resolve", &["Rom/Spr/spr_ui.farc", "Plugin.dll", "a", "A"]);
+        assert_eq!(
+            resolve_ignore_case(&dir, "rom\\spr/SPR_UI.farc").as_deref(),
+            Some("Rom/Spr/spr_ui.farc")
+        );
+        assert_eq!(
+            resolve_ignore_case(&dir, "./plugin.DLL").as_deref(),
+            Some("Plugin.dll")
+        );
+        // an exact match wins over the other casing
+        assert_eq!(resolve_ignore_case(&dir, "A").as_deref(), Some("A"));
+        assert_eq!(
+            resolve_ignore_case(&dir, "rom/../PLUGIN.dll").as_deref(),
+            Some("Rom/../Plugin.dll")
+        );
+        assert_eq!(resolve_ignore_case(&dir, "rom/missing.farc"), None);
+    }
+
+    #[test]
+    fn keeps_the_newest_file() {
+        let dir = test_dir("keep-files", &["a.txt", "A.txt"]);
+        age(&dir.join("a.txt"), 60);
+        let names = vec!["A.txt".to_owned(), "a.txt".to_owned()];
+        assert_eq!(pick_keep(&dir, &names, &[]).as_deref(), Some("A.txt"));
+    }
+
+    #[test]
+    fn keeps_the_folder_the_config_uses() {
+        let dir = test_dir("keep-dirs", &["Rom/a", "rom/b", "rom/c"]);
+        let names = vec!["Rom".to_owned(), "rom".to_owned()];
+        // without the config it's the one with more in it
+        assert_eq!(pick_keep(&dir, &names, &[]).as_deref(), Some("rom"));
+        let preferred = vec!["Rom".to_owned()];
+        assert_eq!(pick_keep(&dir, &names, &preferred).as_deref(), Some("Rom"));
+    }
+
+    #[test]
+    fn mixed_files_and_folders_arent_fixed() {
+        let dir = test_dir("keep-mixed", &["rom/a", "ROM"]);
+        let names = vec!["ROM".to_owned(), "rom".to_owned()];
+        assert_eq!(pick_keep(&dir, &names, &[]), None);
+    }
+
+    #[test]
+    fn merge_moves_new_and_newer_files() {
+        let dir = test_dir(
+            "merge",
+            &[
+                "rom/only_old.txt",
+                "rom/old.txt",
+                "rom/New.txt",
+                "rom/Spr/deep.txt",
+                "Rom/OLD.txt",
+                "Rom/new.txt",
+                "Rom/spr/",
+            ],
+        );
+        let (src, dest) = (dir.join("rom"), dir.join("Rom"));
+        age(&dest.join("new.txt"), 60);
+        age(&src.join("old.txt"), 60);
+        let steps = merge_dirs(&src, &dest).unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step::Trash(dest.join("new.txt")),
+                Step::Move(src.join("New.txt"), dest.join("new.txt")),
+                Step::Move(src.join("Spr/deep.txt"), dest.join("spr/deep.txt")),
+                Step::Move(src.join("only_old.txt"), dest.join("only_old.txt")),
+            ]
+        );
+        // nothing's touched until the steps run
+        assert!(src.join("only_old.txt").exists());
+    }
+
+    #[test]
+    fn merge_stops_on_a_file_against_a_folder() {
+        let dir = test_dir("merge-mixed", &["rom/spr/a", "Rom/Spr"]);
+        assert!(merge_dirs(&dir.join("rom"), &dir.join("Rom")).is_err());
+    }
+
+    #[test]
+    fn finds_names_that_only_differ_by_case() {
+        let dir = test_dir("find", &["a.txt", "A.txt", "sub/b", "sub/B", "other"]);
+        let mut found = find_duplicate_names(&dir);
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (dir.clone(), vec!["A.txt".to_owned(), "a.txt".to_owned()]),
+                (dir.join("sub"), vec!["B".to_owned(), "b".to_owned()]),
+            ]
+        );
+    }
+}
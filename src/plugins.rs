@@ -0,0 +1,613 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use chrono::DateTime;
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, VecModel};
+use tokio::sync::broadcast;
+
+use crate::diva::{get_diva_folder, open_error_window};
+use crate::modmanagement::DivaMod;
+use crate::slint_generatedApp::App;
+use crate::{PluginImport, PluginInfo, PluginInspectorWindow, WindowLogic, MODS};
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x014c;
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+const IMAGE_FILE_MACHINE_ARM64: u16 = 0xaa64;
+
+const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+const IMAGE_DIRECTORY_ENTRY_RESOURCE: usize = 2;
+
+/// Signature of the VS_FIXEDFILEINFO block inside a version resource
+const VS_FFI_SIGNATURE: [u8; 4] = [0xbd, 0x04, 0xef, 0xfe];
+
+/// Functions Diva Mod Loader looks up in a plugin dll
+pub const DML_ENTRY_POINTS: [&str; 6] = [
+    "PreInit", "Init", "PostInit", "D3DInit", "OnFrame", "OnResize",
+];
+
+/// Dlls that are provided by Windows/Wine or ship with the game, these don't need to be next to
+/// the plugin for it to load
+const KNOWN_SYSTEM_DLLS: [&str; 52] = [
+    "advapi32.dll",
+    "bcrypt.dll",
+    "comctl32.dll",
+    "comdlg32.dll",
+    "crypt32.dll",
+    "d2d1.dll",
+    "d3d9.dll",
+    "d3d11.dll",
+    "d3d12.dll",
+    "d3dcompiler_47.dll",
+    "dbghelp.dll",
+    "dinput8.dll",
+    "dwmapi.dll",
+    "dwrite.dll",
+    "dxgi.dll",
+    "gdi32.dll",
+    "gdiplus.dll",
+    "hid.dll",
+    "imm32.dll",
+    "iphlpapi.dll",
+    "kernel32.dll",
+    "kernelbase.dll",
+    "msvcp140.dll",
+    "msvcp140_1.dll",
+    "msvcp140_2.dll",
+    "msvcrt.dll",
+    "ncrypt.dll",
+    "ntdll.dll",
+    "ole32.dll",
+    "oleaut32.dll",
+    "opengl32.dll",
+    "powrprof.dll",
+    "psapi.dll",
+    "rpcrt4.dll",
+    "secur32.dll",
+    "setupapi.dll",
+    "shell32.dll",
+    "shlwapi.dll",
+    "ucrtbase.dll",
+    "user32.dll",
+    "userenv.dll",
+    "uxtheme.dll",
+    "vcruntime140.dll",
+    "vcruntime140_1.dll",
+    "version.dll",
+    "windowscodecs.dll",
+    "winhttp.dll",
+    "wininet.dll",
+    "winmm.dll",
+    "wintrust.dll",
+    "ws2_32.dll",
+    "xinput1_4.dll",
+];
+
+/// Information pulled out of the headers of a PE (dll/exe) file
+#[derive(Clone, Debug, Default)]
+pub struct PeInfo {
+    pub machine: u16,
+    pub timestamp: u32,
+    pub file_version: Option<String>,
+    pub exports: Vec<String>,
+    pub imports: Vec<String>,
+}
+
+impl PeInfo {
+    pub fn machine_name(&self) -> String {
+        match self.machine {
+            IMAGE_FILE_MACHINE_I386 => "x86 (32-bit)".to_owned(),
+            IMAGE_FILE_MACHINE_AMD64 => "x64".to_owned(),
+            IMAGE_FILE_MACHINE_ARM64 => "ARM64".to_owned(),
+            other => format!("Unknown ({:#06x})", other),
+        }
+    }
+
+    pub fn is_32bit(&self) -> bool {
+        self.machine == IMAGE_FILE_MACHINE_I386
+    }
+
+    /// The DML callbacks this plugin exports
+    pub fn dml_exports(&self) -> Vec<String> {
+        DML_ENTRY_POINTS
+            .iter()
+            .filter(|entry| self.exports.iter().any(|e| e == *entry))
+            .map(|entry| entry.to_string())
+            .collect()
+    }
+}
+
+struct Section {
+    virtual_address: u32,
+    virtual_size: u32,
+    raw_size: u32,
+    raw_offset: u32,
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> std::io::Result<u16> {
+    match bytes.get(offset..offset + 2) {
+        Some(b) => Ok(u16::from_le_bytes([b[0], b[1]])),
+        None => Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Unexpected end of file",
+        )),
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> std::io::Result<u32> {
+    match bytes.get(offset..offset + 4) {
+        Some(b) => Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        None => Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            "Unexpected end of file",
+        )),
+    }
+}
+
+fn read_cstr(bytes: &[u8], offset: usize) -> std::io::Result<String> {
+    let rest = match bytes.get(offset..) {
+        Some(rest) => rest,
+        None => {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Unexpected end of file",
+            ))
+        }
+    };
+    let end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+    Ok(String::from_utf8_lossy(&rest[..end]).to_string())
+}
+
+/// None when the rva isn't in any section, or the section header is garbage
+fn rva_to_offset(sections: &[Section], rva: u32) -> Option<usize> {
+    for section in sections {
+        let size = section.virtual_size.max(section.raw_size);
+        let end = section.virtual_address.checked_add(size)?;
+        if rva >= section.virtual_address && rva < end {
+            let offset = rva
+                .checked_sub(section.virtual_address)?
+                .checked_add(section.raw_offset)?;
+            return Some(offset as usize);
+        }
+    }
+    None
+}
+
+/// Parses the headers of a PE file, only the bits needed to tell if a plugin will load are read
+pub fn parse_pe(bytes: &[u8]) -> std::io::Result<PeInfo> {
+    if bytes.get(0..2) != Some(b"MZ") {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Not a PE file (missing MZ header)",
+        ));
+    }
+    let pe_offset = read_u32(bytes, 0x3c)? as usize;
+    if bytes.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "Not a PE file (missing PE signature)",
+        ));
+    }
+    let coff = pe_offset + 4;
+    let mut info = PeInfo {
+        machine: read_u16(bytes, coff)?,
+        timestamp: read_u32(bytes, coff + 4)?,
+        ..Default::default()
+    };
+    let n_sections = read_u16(bytes, coff + 2)? as usize;
+    let optional_size = read_u16(bytes, coff + 16)? as usize;
+    let optional = coff + 20;
+
+    let (n_dirs_offset, dirs_offset) = match read_u16(bytes, optional)? {
+        0x10b => (optional + 92, optional + 96),
+        0x20b => (optional + 108, optional + 112),
+        magic => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Unknown optional header magic: {:#x}", magic),
+            ))
+        }
+    };
+    let n_dirs = read_u32(bytes, n_dirs_offset)? as usize;
+    let directory = |idx: usize| -> std::io::Result<(u32, u32)> {
+        if idx >= n_dirs {
+            return Ok((0, 0));
+        }
+        Ok((
+            read_u32(bytes, dirs_offset + idx * 8)?,
+            read_u32(bytes, dirs_offset + idx * 8 + 4)?,
+        ))
+    };
+
+    let mut sections = vec![];
+    let sections_offset = optional + optional_size;
+    for i in 0..n_sections {
+        let header = sections_offset + i * 40;
+        sections.push(Section {
+            virtual_size: read_u32(bytes, header + 8)?,
+            virtual_address: read_u32(bytes, header + 12)?,
+            raw_size: read_u32(bytes, header + 16)?,
+            raw_offset: read_u32(bytes, header + 20)?,
+        });
+    }
+
+    let (export_rva, export_size) = directory(IMAGE_DIRECTORY_ENTRY_EXPORT)?;
+    if export_rva != 0 && export_size != 0 {
+        if let Some(export) = rva_to_offset(&sections, export_rva) {
+            let n_names = read_u32(bytes, export + 24)? as usize;
+            let names_rva = read_u32(bytes, export + 32)?;
+            if let Some(names) = rva_to_offset(&sections, names_rva) {
+                for i in 0..n_names {
+                    let name_rva = read_u32(bytes, names + i * 4)?;
+                    if let Some(name) = rva_to_offset(&sections, name_rva) {
+                        info.exports.push(read_cstr(bytes, name)?);
+                    }
+                }
+            }
+        }
+    }
+
+    let (import_rva, import_size) = directory(IMAGE_DIRECTORY_ENTRY_IMPORT)?;
+    if import_rva != 0 && import_size != 0 {
+        if let Some(mut descriptor) = rva_to_offset(&sections, import_rva) {
+            loop {
+                let lookup = read_u32(bytes, descriptor)?;
+                let name_rva = read_u32(bytes, descriptor + 12)?;
+                if lookup == 0 && name_rva == 0 {
+                    break;
+                }
+                if let Some(name) = rva_to_offset(&sections, name_rva) {
+                    info.imports.push(read_cstr(bytes, name)?);
+                }
+                descriptor += 20;
+            }
+        }
+    }
+
+    let (resource_rva, resource_size) = directory(IMAGE_DIRECTORY_ENTRY_RESOURCE)?;
+    if resource_rva != 0 && resource_size != 0 {
+        if let Some(start) = rva_to_offset(&sections, resource_rva) {
+            let end = (start + resource_size as usize).min(bytes.len());
+            if let Some(resources) = bytes.get(start..end) {
+                if let Some(pos) = resources
+                    .windows(VS_FFI_SIGNATURE.len())
+                    .position(|w| w == VS_FFI_SIGNATURE)
+                {
+                    // a cut off version block only loses the version, the rest is still good
+                    if let (Ok(ms), Ok(ls)) =
+                        (read_u32(resources, pos + 8), read_u32(resources, pos + 12))
+                    {
+                        info.file_version = Some(format!(
+                            "{}.{}.{}.{}",
+                            ms >> 16,
+                            ms & 0xffff,
+                            ls >> 16,
+                            ls & 0xffff
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+pub fn is_system_dll(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.starts_with("api-ms-win-")
+        || name.starts_with("ext-ms-")
+        || KNOWN_SYSTEM_DLLS.contains(&name.as_str())
+}
+
+/// Looks for an imported dll in the places windows will search when DML loads the plugin
+pub fn find_import(name: &str, search_dirs: &[PathBuf]) -> bool {
+    if is_system_dll(name) {
+        return true;
+    }
+    let lower = name.to_lowercase();
+    for dir in search_dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        // windows doesn't care about the case of the file name
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().to_lowercase() == lower {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Gets the list of dlls the mod's config.toml tells DML to load
+pub fn get_mod_dlls(module: &DivaMod) -> Vec<String> {
+    match module.config.get("dll").and_then(|d| d.as_array()) {
+        Some(dlls) => dlls
+            .iter()
+            .filter_map(|d| d.as_str())
+            .map(|d| d.to_string())
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Result of inspecting a single plugin dll, converted to a [`PluginInfo`] for the ui
+#[derive(Clone, Debug, Default)]
+pub struct PluginReport {
+    pub dll: String,
+    pub path: PathBuf,
+    pub info: Option<PeInfo>,
+    /// imported dlls along with whether they could be found
+    pub imports: Vec<(String, bool)>,
+    pub error: Option<String>,
+}
+
+impl PluginReport {
+    pub fn missing_imports(&self) -> Vec<String> {
+        self.imports
+            .iter()
+            .filter(|(_, found)| !found)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+}
+
+impl From<PluginReport> for PluginInfo {
+    fn from(value: PluginReport) -> Self {
+        let mut plugin = PluginInfo {
+            name: value.dll.clone().into(),
+            path: value.path.display().to_string().into(),
+            error: value.error.clone().unwrap_or_default().into(),
+            missing: !value.missing_imports().is_empty(),
+            ..Default::default()
+        };
+        if let Some(info) = value.info {
+            plugin.machine = info.machine_name().into();
+            plugin.is_32bit = info.is_32bit();
+            plugin.timestamp =
+                match DateTime::<chrono::Utc>::from_timestamp(info.timestamp as i64, 0) {
+                    Some(date) => date.format("%d/%m/%Y %H:%M").to_string().into(),
+                    None => info.timestamp.to_string().into(),
+                };
+            plugin.version = info.file_version.clone().unwrap_or_default().into();
+            plugin.entry_points = info.dml_exports().join(", ").into();
+        }
+        let imports: Vec<PluginImport> = value
+            .imports
+            .iter()
+            .map(|(name, found)| PluginImport {
+                name: name.into(),
+                found: *found,
+            })
+            .collect();
+        plugin.imports = ModelRc::new(VecModel::from(imports));
+        plugin
+    }
+}
+
+pub fn inspect_plugin(mod_dir: &Path, dll: &str) -> PluginReport {
+    let mut buf = mod_dir.to_path_buf();
+    buf.push(dll);
+    let mut report = PluginReport {
+        dll: dll.to_owned(),
+        path: buf.clone(),
+        ..Default::default()
+    };
+    let bytes = match fs::read(&buf) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            report.error = Some(format!("Unable to read dll: {e}"));
+            return report;
+        }
+    };
+    let info = match parse_pe(&bytes) {
+        Ok(info) => info,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+
+    let mut search_dirs = vec![mod_dir.to_path_buf()];
+    if let Some(parent) = buf.parent() {
+        search_dirs.push(parent.to_path_buf());
+    }
+    if let Some(diva_dir) = get_diva_folder() {
+        search_dirs.push(PathBuf::from(diva_dir));
+    }
+
+    report.imports = info
+        .imports
+        .iter()
+        .map(|name| (name.clone(), find_import(name, &search_dirs)))
+        .collect();
+    report.info = Some(info);
+    report
+}
+
+pub fn inspect_mod_plugins(module: &DivaMod) -> Vec<PluginReport> {
+    let mut mod_dir = PathBuf::from(module.path.clone());
+    mod_dir.pop();
+    get_mod_dlls(module)
+        .iter()
+        .map(|dll| inspect_plugin(&mod_dir, dll))
+        .collect()
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>()
+        .on_open_plugin_inspector(move |module| {
+            let ui = weak.unwrap();
+            let module = match MODS.try_lock() {
+                Ok(mods) => match mods.get(&module.dir_name().unwrap_or_default()) {
+                    Some(m) => m.clone(),
+                    None => return,
+                },
+                Err(_) => return,
+            };
+            let inspector = match PluginInspectorWindow::new() {
+                Ok(inspector) => inspector,
+                Err(e) => {
+                    open_error_window(e.to_string());
+                    return;
+                }
+            };
+            inspector.invoke_set_color_scheme(ui.get_color_scheme());
+            inspector.set_name(module.config["name"].as_str().unwrap_or("").into());
+            inspector.set_loading(true);
+
+            let inspector_weak = inspector.as_weak();
+            tokio::spawn(async move {
+                let plugins = tokio::task::spawn_blocking(move || inspect_mod_plugins(&module))
+                    .await
+                    .unwrap_or_default();
+                let _ = inspector_weak.upgrade_in_event_loop(move |inspector| {
+                    let plugins: Vec<PluginInfo> = plugins.into_iter().map(|p| p.into()).collect();
+                    inspector.set_plugins(ModelRc::new(VecModel::from(plugins)));
+                    inspector.set_loading(false);
+                });
+            });
+
+            let weak = inspector.as_weak();
+            inspector.on_close(move || {
+                weak.unwrap().hide().unwrap();
+            });
+
+            let weak = inspector.as_weak();
+            let mut scheme_rx = dark_rx.resubscribe();
+            let scheme_task = tokio::spawn(async move {
+                while let Ok(scheme) = scheme_rx.recv().await {
+                    let _ = weak.upgrade_in_event_loop(move |ui| {
+                        ui.invoke_set_color_scheme(scheme);
+                    });
+                }
+            });
+
+            inspector.window().on_close_requested(move || {
+                scheme_task.abort();
+                slint::CloseRequestResponse::HideWindow
+            });
+
+            inspector.show().unwrap();
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put_u16(bytes: &mut [u8], offset: usize, value: u16) {
+        bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u32(bytes: &mut [u8], offset: usize, value: u32) {
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A tiny x64 dll with one section at rva 0x1000 that's at 0x200 in the file, exporting
+    /// Init and OnFrame, importing kernel32.dll and versioned 1.2.3.4
+    fn test_dll() -> Vec<u8> {
+        let mut bytes = vec![0u8; 0x400];
+        bytes[0..2].copy_from_slice(b"MZ");
+        put_u32(&mut bytes, 0x3c, 0x40);
+        bytes[0x40..0x44].copy_from_slice(b"PE\0\0");
+        put_u16(&mut bytes, 0x44, IMAGE_FILE_MACHINE_AMD64);
+        put_u16(&mut bytes, 0x46, 1);
+        put_u32(&mut bytes, 0x48, 1_700_000_000);
+        put_u16(&mut bytes, 0x54, 0xf0);
+        put_u16(&mut bytes, 0x58, 0x20b);
+        put_u32(&mut bytes, 0xc4, 16);
+        // export, import and resource directories
+        put_u32(&mut bytes, 0xc8, 0x1000);
+        put_u32(&mut bytes, 0xcc, 0x40);
+        put_u32(&mut bytes, 0xd0, 0x1080);
+        put_u32(&mut bytes, 0xd4, 0x28);
+        put_u32(&mut bytes, 0xd8, 0x1100);
+        put_u32(&mut bytes, 0xdc, 0x40);
+        // the section header
+        put_u32(&mut bytes, 0x148 + 8, 0x200);
+        put_u32(&mut bytes, 0x148 + 12, 0x1000);
+        put_u32(&mut bytes, 0x148 + 16, 0x200);
+        put_u32(&mut bytes, 0x148 + 20, 0x200);
+
+        put_u32(&mut bytes, 0x200 + 24, 2);
+        put_u32(&mut bytes, 0x200 + 32, 0x1040);
+        put_u32(&mut bytes, 0x240, 0x1050);
+        put_u32(&mut bytes, 0x244, 0x1058);
+        bytes[0x250..0x254].copy_from_slice(b"Init");
+        bytes[0x258..0x25f].copy_from_slice(b"OnFrame");
+
+        put_u32(&mut bytes, 0x280, 1);
+        put_u32(&mut bytes, 0x280 + 12, 0x10c0);
+        bytes[0x2c0..0x2cc].copy_from_slice(b"kernel32.dll");
+
+        bytes[0x310..0x314].copy_from_slice(&VS_FFI_SIGNATURE);
+        put_u32(&mut bytes, 0x318, 1 << 16 | 2);
+        put_u32(&mut bytes, 0x31c, 3 << 16 | 4);
+        bytes
+    }
+
+    #[test]
+    fn parses_headers() {
+        let info = parse_pe(&test_dll()).unwrap();
+        assert_eq!(info.machine, IMAGE_FILE_MACHINE_AMD64);
+        assert!(!info.is_32bit());
+        assert_eq!(info.timestamp, 1_700_000_000);
+        assert_eq!(info.exports, vec!["Init", "OnFrame"]);
+        assert_eq!(info.dml_exports(), vec!["Init", "OnFrame"]);
+        assert_eq!(info.imports, vec!["kernel32.dll"]);
+        assert_eq!(info.file_version.as_deref(), Some("1.2.3.4"));
+    }
+
+    #[test]
+    fn rejects_non_pe() {
+        assert!(parse_pe(b"not a dll").is_err());
+        let mut bytes = test_dll();
+        bytes[0x40] = b'X';
+        assert!(parse_pe(&bytes).is_err());
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        assert!(parse_pe(&test_dll()[..0x100]).is_err());
+    }
+
+    #[test]
+    fn garbage_sections_dont_panic() {
+        let mut bytes = test_dll();
+        put_u32(&mut bytes, 0x148 + 12, 0xffff_ff00);
+        let info = parse_pe(&bytes).unwrap();
+        assert!(info.exports.is_empty());
+        assert!(info.imports.is_empty());
+
+        let section = Section {
+            virtual_address: 0x1000,
+            virtual_size: 0x200,
+            raw_size: 0x200,
+            raw_offset: u32::MAX,
+        };
+        assert_eq!(rva_to_offset(&[section], 0x1080), None);
+    }
+
+    #[test]
+    fn truncated_version_only_drops_the_version() {
+        let mut bytes = test_dll();
+        // the resource directory now ends right after the signature
+        put_u32(&mut bytes, 0xdc, 0x14);
+        let info = parse_pe(&bytes).unwrap();
+        assert_eq!(info.file_version, None);
+        assert_eq!(info.exports, vec!["Init", "OnFrame"]);
+        assert_eq!(info.imports, vec!["kernel32.dll"]);
+    }
+
+    #[test]
+    fn system_dlls_ignore_case() {
+        assert!(is_system_dll("KERNEL32.dll"));
+        assert!(is_system_dll("api-ms-win-crt-runtime-l1-1-0.dll"));
+        assert!(!is_system_dll("MinHook.dll"));
+    }
+}
@@ -0,0 +1,585 @@
+use std::path::PathBuf;
+
+use crate::metadata::get_meta;
+use crate::moddetails::{find_preview, find_readme};
+use crate::modmanagement::DivaMod;
+use crate::tags::{mod_author, normalize_tag};
+use crate::MOD_PACKS;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SizeOp {
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    Eq,
+}
+
+#[derive(Clone, Debug)]
+enum TermKind {
+    /// free text, fuzzy matched against the name and folder
+    Text(String),
+    /// quoted free text, matched as is
+    Phrase(String),
+    Name(String),
+    Dir(String),
+    Author(String),
+    Tag(String),
+    Enabled(bool),
+    Has(String),
+    /// dir names of every mod in the pack, resolved when the query is parsed
+    In(Vec<String>),
+    Size(SizeOp, u64),
+}
+
+#[derive(Clone, Debug)]
+struct Term {
+    negate: bool,
+    kind: TermKind,
+}
+
+/// Parsed search box contents, every term has to match
+///
+/// `author:x tag:ui enabled:false has:dll size>1GB in:"pack name" "exact phrase" -excluded`
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    terms: Vec<Term>,
+}
+
+/// Splits on whitespace, keeping anything inside quotes together. The bool is whether the
+/// token had quotes in it.
+fn tokenize(input: &str) -> Vec<(String, bool)> {
+    let mut tokens = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_quotes = false;
+    for c in input.chars() {
+        match c {
+            '"' | '“' | '”' | '「' | '」' => {
+                in_quotes = !in_quotes;
+                quoted = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() || quoted {
+                    tokens.push((std::mem::take(&mut current), quoted));
+                }
+                quoted = false;
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() || quoted {
+        tokens.push((current, quoted));
+    }
+    tokens
+}
+
+/// "1.5GB", "200mb", "10k", same 1024 steps as `format_size`
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (num, unit) = s.split_at(split);
+    let num: f64 = num.parse().ok()?;
+    let mult: u64 = match unit.trim() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((num * mult as f64) as u64)
+}
+
+fn parse_size_term(token: &str) -> Option<TermKind> {
+    let lower = token.to_lowercase();
+    let rest = lower.strip_prefix("size")?;
+    let rest = rest.strip_prefix(':').unwrap_or(rest);
+    let (op, rest) = if let Some(r) = rest.strip_prefix(">=") {
+        (SizeOp::GreaterEq, r)
+    } else if let Some(r) = rest.strip_prefix("<=") {
+        (SizeOp::LessEq, r)
+    } else if let Some(r) = rest.strip_prefix('>') {
+        (SizeOp::Greater, r)
+    } else if let Some(r) = rest.strip_prefix('<') {
+        (SizeOp::Less, r)
+    } else if let Some(r) = rest.strip_prefix('=') {
+        (SizeOp::Eq, r)
+    } else {
+        // `size:1GB` on its own reads as "at least"
+        (SizeOp::GreaterEq, rest)
+    };
+    Some(TermKind::Size(op, parse_size(rest)?))
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "true" | "yes" | "y" | "on" | "1" => Some(true),
+        "false" | "no" | "n" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn pack_members(name: &str) -> Vec<String> {
+    let Ok(packs) = MOD_PACKS.try_lock() else {
+        return vec![];
+    };
+    let name = name.to_lowercase();
+    // exact name first so "in:songs" doesn't also pull in "songs old"
+    let pack = packs
+        .values()
+        .find(|p| p.name.to_lowercase() == name)
+        .or_else(|| {
+            packs
+                .values()
+                .find(|p| p.name.to_lowercase().starts_with(&name))
+        });
+    match pack {
+        Some(pack) => pack.mods.iter().filter_map(|m| m.dir_name()).collect(),
+        None => vec![],
+    }
+}
+
+fn parse_term(token: &str, quoted: bool) -> Option<Term> {
+    let (negate, token) = match token.strip_prefix(['-', '!']) {
+        Some(rest) if !rest.is_empty() => (true, rest),
+        _ => (false, token),
+    };
+    if token.is_empty() {
+        return None;
+    }
+    if let Some(kind) = parse_size_term(token) {
+        return Some(Term { negate, kind });
+    }
+    let kind = match token.split_once(':') {
+        Some((key, value)) if !value.is_empty() => match key.to_lowercase().as_str() {
+            "name" => TermKind::Name(value.to_string()),
+            "dir" | "folder" => TermKind::Dir(value.to_string()),
+            "author" | "by" => TermKind::Author(value.to_string()),
+            "tag" => TermKind::Tag(normalize_tag(value)),
+            "enabled" => match parse_bool(value) {
+                Some(enabled) => TermKind::Enabled(enabled),
+                None => TermKind::Text(token.to_string()),
+            },
+            "is" => match value.to_lowercase().as_str() {
+                "enabled" => TermKind::Enabled(true),
+                "disabled" => TermKind::Enabled(false),
+                _ => TermKind::Text(token.to_string()),
+            },
+            "has" => TermKind::Has(value.to_lowercase()),
+            "in" | "pack" => TermKind::In(pack_members(value)),
+            _ => TermKind::Text(token.to_string()),
+        },
+        _ if quoted => TermKind::Phrase(token.to_string()),
+        _ => TermKind::Text(token.to_string()),
+    };
+    Some(Term { negate, kind })
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Self {
+        Self {
+            terms: tokenize(input)
+                .into_iter()
+                .filter_map(|(token, quoted)| parse_term(&token, quoted))
+                .collect(),
+        }
+    }
+
+    pub fn matches(&self, module: &DivaMod) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term_matches(&term.kind, module) != term.negate)
+    }
+}
+
+fn term_matches(kind: &TermKind, module: &DivaMod) -> bool {
+    let name = module.config["name"].as_str().unwrap_or("").to_string();
+    let dir = module.dir_name().unwrap_or_default();
+    match kind {
+        TermKind::Text(text) => fuzzy_contains(&name, text) || fuzzy_contains(&dir, text),
+        TermKind::Phrase(text) => {
+            let text = text.to_lowercase();
+            name.to_lowercase().contains(&text) || dir.to_lowercase().contains(&text)
+        }
+        TermKind::Name(text) => fuzzy_contains(&name, text),
+        TermKind::Dir(text) => fuzzy_contains(&dir, text),
+        TermKind::Author(text) => fuzzy_contains(&mod_author(module), text),
+        TermKind::Tag(tag) => get_meta(&dir)
+            .tags
+            .iter()
+            .any(|t| t == tag || t.starts_with(tag.as_str())),
+        TermKind::Enabled(enabled) => {
+            module.config["enabled"].as_bool().unwrap_or(true) == *enabled
+        }
+        TermKind::Has(what) => {
+            let mut mod_dir = PathBuf::from(module.path.clone());
+            mod_dir.pop();
+            match what.as_str() {
+                "dll" | "dlls" | "plugin" => module
+                    .config
+                    .get("dll")
+                    .and_then(|d| d.as_array())
+                    .is_some_and(|d| !d.is_empty()),
+                "readme" => find_readme(&mod_dir).is_some(),
+                "preview" | "image" => find_preview(&mod_dir).is_some(),
+                "tags" | "tag" => !get_meta(&dir).tags.is_empty(),
+                "source" | "url" => get_meta(&dir).source.is_some(),
+                "description" => module
+                    .config
+                    .get("description")
+                    .and_then(|d| d.as_str())
+                    .is_some_and(|d| !d.is_empty()),
+                _ => false,
+            }
+        }
+        TermKind::In(members) => members.contains(&dir),
+        TermKind::Size(op, size) => match op {
+            SizeOp::Less => module.size < *size,
+            SizeOp::LessEq => module.size <= *size,
+            SizeOp::Greater => module.size > *size,
+            SizeOp::GreaterEq => module.size >= *size,
+            SizeOp::Eq => module.size == *size,
+        },
+    }
+}
+
+#[rustfmt::skip]
+const HIRAGANA: [(char, &str); 85] = [
+    ('あ', "a"), ('い', "i"), ('う', "u"), ('え', "e"), ('お', "o"),
+    ('か', "ka"), ('き', "ki"), ('く', "ku"), ('け', "ke"), ('こ', "ko"),
+    ('が', "ga"), ('ぎ', "gi"), ('ぐ', "gu"), ('げ', "ge"), ('ご', "go"),
+    ('さ', "sa"), ('し', "shi"), ('す', "su"), ('せ', "se"), ('そ', "so"),
+    ('ざ', "za"), ('じ', "ji"), ('ず', "zu"), ('ぜ', "ze"), ('ぞ', "zo"),
+    ('た', "ta"), ('ち', "chi"), ('つ', "tsu"), ('て', "te"), ('と', "to"),
+    ('だ', "da"), ('ぢ', "ji"), ('づ', "zu"), ('で', "de"), ('ど', "do"),
+    ('な', "na"), ('に', "ni"), ('ぬ', "nu"), ('ね', "ne"), ('の', "no"),
+    ('は', "ha"), ('ひ', "hi"), ('ふ', "fu"), ('へ', "he"), ('ほ', "ho"),
+    ('ば', "ba"), ('び', "bi"), ('ぶ', "bu"), ('べ', "be"), ('ぼ', "bo"),
+    ('ぱ', "pa"), ('ぴ', "pi"), ('ぷ', "pu"), ('ぺ', "pe"), ('ぽ', "po"),
+    ('ま', "ma"), ('み', "mi"), ('む', "mu"), ('め', "me"), ('も', "mo"),
+    ('や', "ya"), ('ゆ', "yu"), ('よ', "yo"),
+    ('ら', "ra"), ('り', "ri"), ('る', "ru"), ('れ', "re"), ('ろ', "ro"),
+    ('わ', "wa"), ('ゐ', "i"), ('ゑ', "e"), ('を', "o"), ('ん', "n"), ('ゔ', "vu"),
+    ('ぁ', "a"), ('ぃ', "i"), ('ぅ', "u"), ('ぇ', "e"), ('ぉ', "o"),
+    ('ゃ', "ya"), ('ゅ', "yu"), ('ょ', "yo"), ('ゎ', "wa"), ('ゕ', "ka"), ('ゖ', "ke"),
+];
+
+fn is_kana(c: char) -> bool {
+    ('\u{3041}'..='\u{3096}').contains(&c) || ('\u{30A1}'..='\u{30FA}').contains(&c)
+}
+
+/// Katakana to hiragana and fullwidth ascii to ascii, everything else as is
+fn narrow(c: char) -> char {
+    match c {
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        'ā' | 'â' => 'a',
+        'ī' | 'î' => 'i',
+        'ū' | 'û' => 'u',
+        'ē' | 'ê' => 'e',
+        'ō' | 'ô' => 'o',
+        c => c,
+    }
+}
+
+/// Hepburn romaji for any kana in `s`
+fn romanize(s: &str) -> String {
+    let chars: Vec<char> = s.chars().map(narrow).collect();
+    let mut out = String::new();
+    let mut double_next = false;
+    for c in chars {
+        if c == 'っ' {
+            double_next = true;
+            continue;
+        }
+        if c == 'ー' {
+            continue;
+        }
+        let Some((_, roma)) = HIRAGANA.iter().find(|(k, _)| *k == c) else {
+            out.extend(c.to_lowercase());
+            double_next = false;
+            continue;
+        };
+        let small = matches!(c, 'ゃ' | 'ゅ' | 'ょ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ');
+        if small && !out.is_empty() {
+            // きゃ -> kya, しゃ -> sha, ファ -> fa
+            match c {
+                'ゃ' | 'ゅ' | 'ょ' if out.ends_with('i') => {
+                    out.pop();
+                    if out.ends_with("sh") || out.ends_with("ch") || out.ends_with('j') {
+                        out.push_str(&roma[1..]);
+                    } else {
+                        out.push_str(roma);
+                    }
+                }
+                'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' => {
+                    if out.ends_with(['a', 'i', 'u', 'e', 'o']) {
+                        out.pop();
+                    }
+                    out.push_str(roma);
+                }
+                _ => out.push_str(roma),
+            }
+            continue;
+        }
+        if double_next {
+            if let Some(first) = roma.chars().next() {
+                if !"aiueon".contains(first) {
+                    out.push(first);
+                }
+            }
+            double_next = false;
+        }
+        out.push_str(roma);
+    }
+    out
+}
+
+/// Lowercase romaji with the usual spelling differences ironed out, so "Tōkyō", "toukyou",
+/// "TOKYO" and "とうきょう" all end up the same
+fn fold(s: &str) -> String {
+    let romaji = romanize(s);
+    let mut out: String = romaji
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    for (from, to) in [
+        ("shi", "si"),
+        ("chi", "ti"),
+        ("tsu", "tu"),
+        ("fu", "hu"),
+        ("ji", "zi"),
+        ("sh", "sy"),
+        ("ch", "ty"),
+        ("j", "zy"),
+        ("l", "r"),
+        ("v", "b"),
+        ("ou", "o"),
+        ("oo", "o"),
+        ("uu", "u"),
+        ("aa", "a"),
+        ("ii", "i"),
+        ("ee", "e"),
+    ] {
+        out = out.replace(from, to);
+    }
+    out
+}
+
+/// Just the consonants with voicing and doubling dropped, "meruto" and "melt" both come out
+/// as "mrt"
+fn skeleton(folded: &str) -> String {
+    let mut out = String::new();
+    for c in folded.chars().filter(|c| !"aiueo".contains(*c)) {
+        let c = match c {
+            'z' => 's',
+            'g' => 'k',
+            'd' => 't',
+            'b' | 'p' => 'h',
+            c => c,
+        };
+        if !out.ends_with(c) {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Smallest edit distance between `needle` and any substring of `hay`
+fn substring_distance(hay: &[char], needle: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=needle.len()).collect();
+    let mut best = prev[needle.len()];
+    for h in hay {
+        let mut cur = vec![0; needle.len() + 1];
+        for (j, n) in needle.iter().enumerate() {
+            let cost = if h == n { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        best = best.min(cur[needle.len()]);
+        prev = cur;
+    }
+    best
+}
+
+/// Case, width, kana and romanisation insensitive substring match with a little typo tolerance
+pub fn fuzzy_contains(hay: &str, needle: &str) -> bool {
+    let needle_folded = fold(needle);
+    if needle_folded.is_empty() {
+        return true;
+    }
+    let hay_folded = fold(hay);
+    if hay_folded.contains(&needle_folded) {
+        return true;
+    }
+    // only loosen up to consonants when one side was actually japanese
+    let had_kana = hay.chars().any(is_kana) || needle.chars().any(is_kana);
+    if had_kana {
+        let needle_skel = skeleton(&needle_folded);
+        if needle_skel.chars().count() >= 3 && skeleton(&hay_folded).contains(&needle_skel) {
+            return true;
+        }
+    }
+    let needle_chars: Vec<char> = needle_folded.chars().collect();
+    if needle_chars.len() < 5 {
+        return false;
+    }
+    let hay_chars: Vec<char> = hay_folded.chars().collect();
+    substring_distance(&hay_chars, &needle_chars) <= needle_chars.len() / 5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_mod(config: &str, size: u64) -> DivaMod {
+        DivaMod {
+            path: "/nonexistent/mods/test/config.toml".to_owned(),
+            config: config.parse().unwrap(),
+            size,
+            file_count: 1,
+            id: String::new(),
+        }
+    }
+
+    #[test]
+    fn tokenize_keeps_quotes_together() {
+        let tokens = tokenize(r#"author:x  "exact phrase" in:"pack name" -excluded """#);
+        assert_eq!(
+            tokens,
+            vec![
+                ("author:x".to_owned(), false),
+                ("exact phrase".to_owned(), true),
+                ("in:pack name".to_owned(), true),
+                ("-excluded".to_owned(), false),
+                ("".to_owned(), true),
+            ]
+        );
+        assert_eq!(
+            tokenize("「初音 ミク」"),
+            vec![("初音 ミク".to_owned(), true)]
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1.5GB"), Some(3 << 29));
+        assert_eq!(parse_size("200mb"), Some(200 << 20));
+        assert_eq!(parse_size("10k"), Some(10 << 10));
+        assert_eq!(parse_size("42"), Some(42));
+        assert_eq!(parse_size("5 parsecs"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn size_terms() {
+        assert!(matches!(
+            parse_size_term("size>1GB"),
+            Some(TermKind::Size(SizeOp::Greater, n)) if n == 1 << 30
+        ));
+        assert!(matches!(
+            parse_size_term("size:<=10mb"),
+            Some(TermKind::Size(SizeOp::LessEq, n)) if n == 10 << 20
+        ));
+        assert!(matches!(
+            parse_size_term("SIZE:1GB"),
+            Some(TermKind::Size(SizeOp::GreaterEq, _))
+        ));
+        assert!(parse_size_term("size>big").is_none());
+        assert!(parse_size_term("sized").is_none());
+    }
+
+    #[test]
+    fn terms() {
+        let term = parse_term("-author:foo", false).unwrap();
+        assert!(term.negate);
+        assert!(matches!(term.kind, TermKind::Author(ref a) if a == "foo"));
+
+        let term = parse_term("!is:disabled", false).unwrap();
+        assert!(term.negate);
+        assert!(matches!(term.kind, TermKind::Enabled(false)));
+
+        assert!(matches!(
+            parse_term("enabled:yes", false).unwrap().kind,
+            TermKind::Enabled(true)
+        ));
+        // values that don't make sense fall back to plain text
+        assert!(matches!(
+            parse_term("enabled:maybe", false).unwrap().kind,
+            TermKind::Text(ref t) if t == "enabled:maybe"
+        ));
+        assert!(matches!(
+            parse_term("foo:", false).unwrap().kind,
+            TermKind::Text(_)
+        ));
+        assert!(matches!(
+            parse_term("has:DLL", false).unwrap().kind,
+            TermKind::Has(ref h) if h == "dll"
+        ));
+        assert!(matches!(
+            parse_term("exact phrase", true).unwrap().kind,
+            TermKind::Phrase(_)
+        ));
+        // a lone dash is searched for, not a negated nothing
+        let term = parse_term("-", false).unwrap();
+        assert!(!term.negate);
+        assert!(matches!(term.kind, TermKind::Text(ref t) if t == "-"));
+        assert!(parse_term("", true).is_none());
+    }
+
+    #[test]
+    fn query_matches() {
+        let module = test_mod(
+            "name = \"Project DIVA HD UI\"\nauthor = \"someone\"\nenabled = false\ndll = [\"ui.dll\"]",
+            2 << 30,
+        );
+        assert!(Query::parse("").matches(&module));
+        assert!(Query::parse("diva ui").matches(&module));
+        assert!(Query::parse("author:someone is:disabled has:dll size>1GB").matches(&module));
+        assert!(!Query::parse("diva -ui").matches(&module));
+        assert!(!Query::parse("enabled:true").matches(&module));
+        assert!(!Query::parse("size<1GB").matches(&module));
+        assert!(Query::parse("\"diva hd\"").matches(&module));
+        assert!(!Query::parse("\"hd diva\"").matches(&module));
+    }
+
+    #[test]
+    fn romanization() {
+        assert_eq!(romanize("とうきょう"), "toukyou");
+        assert_eq!(romanize("しゃしん"), "shashin");
+        assert_eq!(romanize("ちょっと"), "chotto");
+        assert_eq!(romanize("がっこう"), "gakkou");
+        assert_eq!(romanize("ミク"), "miku");
+        assert_eq!(romanize("ファン"), "fan");
+        assert_eq!(romanize("メルト"), "meruto");
+        assert_eq!(romanize("ＤＩＶＡ"), "diva");
+    }
+
+    #[test]
+    fn folding() {
+        for spelling in ["Tōkyō", "toukyou", "TOKYO", "とうきょう", "トーキョー"] {
+            assert_eq!(fold(spelling), "tokyo", "{spelling}");
+        }
+        assert_eq!(fold("shi"), fold("si"));
+        assert_eq!(fold("Rin"), fold("Lin"));
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert!(fuzzy_contains("Hatsune Miku", "miku"));
+        assert!(fuzzy_contains("初音ミク", "ミク"));
+        assert!(fuzzy_contains("ミクの歌", "miku"));
+        assert!(fuzzy_contains("メルト", "melt"));
+        assert!(fuzzy_contains("anything", ""));
+        // one typo is fine in a long enough word
+        assert!(fuzzy_contains("Project DIVA", "projact diva"));
+        assert!(!fuzzy_contains("Miku", "mika"));
+        assert!(!fuzzy_contains("Teto", "miku"));
+        // no kana, no consonant-only matching
+        assert!(!fuzzy_contains("malt", "melt"));
+    }
+}
@@ -0,0 +1,363 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use filenamify::filenamify;
+
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, Model, ModelRc, VecModel, Weak};
+use tokio::sync::broadcast;
+
+use crate::diva::{get_temp_folder, open_error_window};
+use crate::divamodarchive::fetch_post;
+use crate::downloads::download_file;
+use crate::gamebanana::fetch_mod_info;
+use crate::groups::missing_entries;
+use crate::history::{record, Change};
+use crate::metadata::{get_meta, mod_id, record_source, ModSource, Provider};
+use crate::modmanagement::{get_mods, load_mods, set_mods_table, unpack_mod_path};
+use crate::modpacks::{save_modpack_sync, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::util::format_size;
+use crate::{MissingModsWindow, ModpackLogic, PackImportRow, MODS, MOD_PACKS};
+
+/// The applied pack we already asked about, so reloading the tables doesn't keep popping the
+/// window back up. Switching to another pack forgets it
+static OFFERED: LazyLock<Mutex<Option<String>>> = LazyLock::new(|| Mutex::new(None));
+
+fn pack_missing(pack_name: &str) -> Vec<ModPackMod> {
+    let packs = match MOD_PACKS.try_lock() {
+        Ok(packs) => packs,
+        Err(_) => return vec![],
+    };
+    let mods = match MODS.try_lock() {
+        Ok(mods) => mods,
+        Err(_) => return vec![],
+    };
+    match packs.get(pack_name) {
+        Some(pack) => missing_entries(&pack.mods, &mods),
+        None => vec![],
+    }
+}
+
+/// Opens the missing mods window when a pack with missing mods gets applied
+pub fn offer_missing(ui: &App, pack_name: &str) {
+    {
+        let mut offered = match OFFERED.try_lock() {
+            Ok(offered) => offered,
+            Err(_) => return,
+        };
+        if offered.as_deref() == Some(pack_name) {
+            return;
+        }
+        *offered = None;
+        if pack_missing(pack_name).is_empty() {
+            return;
+        }
+        *offered = Some(pack_name.to_owned());
+    }
+    ui.global::<ModpackLogic>()
+        .invoke_resolve_missing(pack_name.into());
+}
+
+fn missing_rows(missing: &[ModPackMod]) -> Vec<(ModPackMod, Option<ModSource>, PackImportRow)> {
+    missing
+        .iter()
+        .map(|entry| {
+            let source = get_meta(&entry.stored_dir()).source;
+            let row = PackImportRow {
+                name: entry.name.clone().into(),
+                status: if source.is_some() { 1 } else { 2 },
+                detail: match &source {
+                    Some(source) => match source.provider {
+                        Provider::GameBanana => "From GameBanana",
+                        Provider::DivaModArchive => "From DivaModArchive",
+                    },
+                    None => "No source known",
+                }
+                .into(),
+                url: source.as_ref().map(|s| s.url()).unwrap_or_default().into(),
+            };
+            (entry.clone(), source, row)
+        })
+        .collect()
+}
+
+/// The download url and file name for a source, falls back to the first file when we don't
+/// know which one was used
+async fn find_download(
+    source: &ModSource,
+) -> Result<(String, String), Box<dyn Error + Send + Sync>> {
+    match source.provider {
+        Provider::GameBanana => {
+            let files = fetch_mod_info(source.id).await?.files.unwrap_or_default();
+            let file = files
+                .iter()
+                .find(|f| f.id.to_string() == source.file_id)
+                .or(files.first())
+                .ok_or("The mod has no files")?;
+            Ok((file.download_url.clone(), file.file.clone()))
+        }
+        Provider::DivaModArchive => {
+            let post = fetch_post(source.id.to_string()).await?;
+            // dma file ids are the post id with the file's index in the upper half
+            let idx = source
+                .file_id
+                .parse::<i64>()
+                .map(|id| (id >> 32) as usize)
+                .unwrap_or(0);
+            let idx = if idx < post.files.len() { idx } else { 0 };
+            match (post.files.get(idx), post.file_names.get(idx)) {
+                (Some(url), Some(name)) => Ok((url.clone(), name.clone())),
+                _ => Err("The post has no files".into()),
+            }
+        }
+    }
+}
+
+/// Downloads and installs a missing mod, returns the folder it ended up in. `progress` gets
+/// how much is downloaded so far and the total
+async fn install_missing<F: FnMut(u64, u64)>(
+    entry: &ModPackMod,
+    source: &ModSource,
+    mut progress: F,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    let (url, name) = find_download(source).await?;
+    let name = match filenamify(name) {
+        name if name.is_empty() => "download".to_owned(),
+        name => name,
+    };
+    let mut buf = PathBuf::from(get_temp_folder().ok_or("Unable to find the temp folder")?);
+    buf.push(name);
+    let mut done = 0;
+    download_file(&url, &buf, |len, size| {
+        done += len as u64;
+        progress(done, size);
+    })
+    .await?;
+    let installed = unpack_mod_path(buf).await?;
+    let dirs: Vec<String> = installed
+        .iter()
+        .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .collect();
+    for dir in dirs.iter() {
+        record_source(dir.clone(), source.clone()).await;
+    }
+    let expected = entry.stored_dir();
+    // archives don't always use the folder name the pack remembers
+    match dirs.len() {
+        1 if !dirs.contains(&expected) => Ok(dirs[0].clone()),
+        _ => Ok(expected),
+    }
+}
+
+/// Points pack entries at the folders their mods got installed to
+fn repoint_entries(pack_name: &str, moved: &[(String, String)]) {
+    let Ok(mut packs) = MOD_PACKS.try_lock() else {
+        return;
+    };
+    let Some(pack) = packs.get_mut(pack_name) else {
+        return;
+    };
+    for entry in pack.mods.iter_mut().filter(|m| !m.separator) {
+        if let Some((_, dir)) = moved.iter().find(|(from, _)| *from == entry.stored_dir()) {
+            let mut buf = PathBuf::from(&entry.path);
+            buf.pop();
+            buf.set_file_name(dir);
+            entry.path = buf.join("config.toml").display().to_string();
+            entry.id = mod_id(dir);
+        }
+    }
+    if let Err(e) = save_modpack_sync(pack.clone()) {
+        eprintln!("{e}");
+    }
+}
+
+/// Takes the missing mods out of the pack, this is the only place that's allowed to
+fn drop_missing(pack_name: &str) -> Result<(), String> {
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let mods = MODS.try_lock().map_err(|e| e.to_string())?;
+    let pack = packs.get_mut(pack_name).ok_or("The pack is gone")?;
+    let before = pack.mods.clone();
+    pack.mods
+        .retain(|m| m.separator || mods.contains_key(&m.dir_name().unwrap_or_default()));
+    record(
+        format!("Drop missing mods from {pack_name}"),
+        vec![Change::Order {
+            pack: pack_name.to_owned(),
+            before,
+            after: pack.mods.clone(),
+        }],
+    );
+    save_modpack_sync(pack.clone()).map_err(|e| e.to_string())
+}
+
+fn reapply(ui: &App, pack_name: &str) {
+    let _ = set_mods_table(&get_mods(), ui.as_weak());
+    ui.global::<ModpackLogic>()
+        .invoke_change_modpack(pack_name.into());
+}
+
+fn fill_window(window: &MissingModsWindow) -> Vec<(ModPackMod, Option<ModSource>)> {
+    let rows = missing_rows(&pack_missing(&window.get_pack_name()));
+    window.set_resolvable(rows.iter().filter(|r| r.1.is_some()).count() as i32);
+    window.set_rows(ModelRc::new(VecModel::from(
+        rows.iter().map(|r| r.2.clone()).collect::<Vec<_>>(),
+    )));
+    rows.into_iter()
+        .map(|(entry, source, _)| (entry, source))
+        .collect()
+}
+
+fn set_row(window: &Weak<MissingModsWindow>, idx: usize, status: i32, detail: String) {
+    let _ = window.upgrade_in_event_loop(move |window| {
+        let rows = window.get_rows();
+        if let Some(mut row) = rows.row_data(idx) {
+            row.status = status;
+            row.detail = detail.into();
+            rows.set_row_data(idx, row);
+        }
+    });
+}
+
+/// Installs everything with a known source one at a time, then applies the pack again
+fn install_all(
+    ui: Weak<App>,
+    window: Weak<MissingModsWindow>,
+    pack_name: String,
+    entries: Vec<(ModPackMod, Option<ModSource>)>,
+) {
+    tokio::spawn(async move {
+        let mut moved = vec![];
+        let mut failed = vec![];
+        for (idx, (entry, source)) in entries.iter().enumerate() {
+            let Some(source) = source else {
+                continue;
+            };
+            set_row(&window, idx, 1, "Downloading...".to_owned());
+            let mut shown = Instant::now();
+            let row_window = window.clone();
+            let progress = move |done: u64, size: u64| {
+                // the window only needs a few updates a second
+                if shown.elapsed() < Duration::from_millis(200) {
+                    return;
+                }
+                shown = Instant::now();
+                let detail = match size {
+                    0 => format!("Downloading... {}", format_size(done)),
+                    size => format!(
+                        "Downloading... {} / {}",
+                        format_size(done),
+                        format_size(size)
+                    ),
+                };
+                set_row(&row_window, idx, 1, detail);
+            };
+            match install_missing(entry, source, progress).await {
+                Ok(dir) => {
+                    set_row(&window, idx, 0, "Installed".to_owned());
+                    let from = entry.stored_dir();
+                    if dir != from {
+                        moved.push((from, dir));
+                    }
+                }
+                Err(e) => {
+                    set_row(&window, idx, 2, e.to_string());
+                    failed.push(format!("{}: {e}", entry.name));
+                }
+            }
+        }
+        if let Err(e) = load_mods() {
+            eprintln!("{e}");
+        }
+        if !failed.is_empty() {
+            open_error_window(format!(
+                "Some mods couldn't be installed: \n{}",
+                failed.join("\n")
+            ));
+        }
+        let _ = ui.upgrade_in_event_loop(move |ui| {
+            repoint_entries(&pack_name, &moved);
+            reapply(&ui, &pack_name);
+            if let Some(window) = window.upgrade() {
+                window.set_working(false);
+                fill_window(&window);
+            }
+        });
+    });
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>()
+        .on_resolve_missing(move |pack_name| {
+            let ui = weak.unwrap();
+            let window = match MissingModsWindow::new() {
+                Ok(window) => window,
+                Err(e) => {
+                    open_error_window(e.to_string());
+                    return;
+                }
+            };
+            window.invoke_set_color_scheme(ui.get_color_scheme());
+            window.set_pack_name(pack_name);
+            fill_window(&window);
+
+            window.on_open_url(|url| {
+                if let Err(e) = open::that(url.to_string()) {
+                    open_error_window(e.to_string());
+                }
+            });
+
+            let main_weak = weak.clone();
+            let window_weak = window.as_weak();
+            window.on_install(move || {
+                let window = window_weak.unwrap();
+                window.set_working(true);
+                let entries = fill_window(&window);
+                install_all(
+                    main_weak.clone(),
+                    window_weak.clone(),
+                    window.get_pack_name().to_string(),
+                    entries,
+                );
+            });
+
+            let main_weak = weak.clone();
+            let window_weak = window.as_weak();
+            window.on_drop_missing(move || {
+                let window = window_weak.unwrap();
+                let pack_name = window.get_pack_name().to_string();
+                match drop_missing(&pack_name) {
+                    Ok(_) => {
+                        reapply(&main_weak.unwrap(), &pack_name);
+                        window.hide().unwrap();
+                    }
+                    Err(e) => open_error_window(format!("Unable to update the pack: \n{e}")),
+                }
+            });
+
+            let window_weak = window.as_weak();
+            window.on_close(move || {
+                window_weak.unwrap().hide().unwrap();
+            });
+
+            let window_weak = window.as_weak();
+            let mut scheme_rx = dark_rx.resubscribe();
+            let scheme_task = tokio::spawn(async move {
+                while let Ok(scheme) = scheme_rx.recv().await {
+                    let _ = window_weak.upgrade_in_event_loop(move |ui| {
+                        ui.invoke_set_color_scheme(scheme);
+                    });
+                }
+            });
+
+            window.window().on_close_requested(move || {
+                scheme_task.abort();
+                slint::CloseRequestResponse::HideWindow
+            });
+
+            window.show().unwrap();
+        });
+}
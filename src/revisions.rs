@@ -0,0 +1,379 @@
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{Local, TimeZone};
+use filenamify::filenamify;
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, SharedString, VecModel};
+use sonic_rs::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::diva::open_error_window;
+use crate::history::{record, Change};
+use crate::layers::{rebase, PackLayer};
+use crate::modpacks::{get_modpacks_folder, save_modpack_sync, ModPack, ModPackMod};
+use crate::slint_generatedApp::App;
+use crate::{ModpackLogic, PackRevisionRow, PackRevisionsWindow, MOD_PACKS, R4D_CFG};
+
+/// Revisions kept per pack, the oldest ones go first
+const MAX_REVISIONS: usize = 50;
+
+/// A pack as it was before a save replaced it
+#[derive(Serialize, Deserialize)]
+struct Revision {
+    /// what the save that replaced it changed
+    summary: String,
+    pack: ModPack,
+}
+
+fn revisions_folder(name: &str) -> std::io::Result<PathBuf> {
+    Ok(get_modpacks_folder()?
+        .join("revisions")
+        .join(filenamify(name)))
+}
+
+/// Revision files in `folder` with when they were replaced, newest first
+fn revisions_in(folder: &Path) -> Vec<(i64, PathBuf)> {
+    let Ok(dir) = fs::read_dir(folder) else {
+        return vec![];
+    };
+    let mut revisions: Vec<(i64, PathBuf)> = dir
+        .flatten()
+        .filter_map(|e| {
+            let stamp = e.path().file_stem()?.to_str()?.parse::<i64>().ok()?;
+            Some((stamp, e.path()))
+        })
+        .collect();
+    revisions.sort_by_key(|r| Reverse(r.0));
+    revisions
+}
+
+fn list_revisions(name: &str) -> Vec<(i64, PathBuf)> {
+    match revisions_folder(name) {
+        Ok(folder) => revisions_in(&folder),
+        Err(_) => vec![],
+    }
+}
+
+/// Drops the oldest revisions past `MAX_REVISIONS`
+fn prune(folder: &Path) -> std::io::Result<()> {
+    for (_, old) in revisions_in(folder).iter().skip(MAX_REVISIONS) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+fn read_revision(path: &Path) -> Result<Revision, Box<dyn Error + Send + Sync>> {
+    Ok(sonic_rs::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn stamp_time(stamp: i64) -> String {
+    match Local.timestamp_millis_opt(stamp).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        None => stamp.to_string(),
+    }
+}
+
+/// What changed between two versions of a list, a part per kind of change
+fn change_summary(before: &[ModPackMod], after: &[ModPackMod]) -> Vec<String> {
+    let layer = PackLayer::between(before, after);
+    let (mut added, mut moved, mut toggled) = (0, 0, 0);
+    for change in layer.changed.iter() {
+        match before.iter().find(|m| **m == change.entry) {
+            None => added += 1,
+            Some(old) => {
+                moved += change.placed as usize;
+                toggled += (old.enabled != change.entry.enabled) as usize;
+            }
+        }
+    }
+    [
+        (added, "added"),
+        (layer.removed.len(), "removed"),
+        (moved, "moved"),
+        (toggled, "toggled"),
+    ]
+    .iter()
+    .filter(|(n, _)| *n > 0)
+    .map(|(n, what)| format!("{n} {what}"))
+    .collect()
+}
+
+/// The pack's list out of what's on disk, layered packs are resolved against the parent the
+/// pack has now
+fn disk_mods(saved: &ModPack, inherited: &[ModPackMod]) -> Vec<ModPackMod> {
+    match &saved.layer {
+        Some(layer) => layer.apply(inherited),
+        None => saved.mods.clone(),
+    }
+}
+
+/// Keeps what's in a pack's file before `save_modpack` writes `contents` over it
+pub fn snapshot(pack: &ModPack, file: &Path, contents: &str) {
+    let Ok(old) = fs::read_to_string(file) else {
+        return;
+    };
+    if old == contents {
+        return;
+    }
+    let Ok(saved) = sonic_rs::from_str::<ModPack>(&old) else {
+        return;
+    };
+    let mut summary = change_summary(&disk_mods(&saved, &pack.inherited), &pack.mods);
+    if saved.name != pack.name {
+        summary.insert(0, format!("renamed from {}", saved.name));
+    }
+    if saved.parent != pack.parent {
+        summary.insert(
+            0,
+            match pack.parent.is_empty() {
+                true => format!("detached from {}", saved.parent),
+                false => format!("layered on {}", pack.parent),
+            },
+        );
+    }
+    let summary = match summary.is_empty() {
+        true => "Saved without changing the list".to_owned(),
+        false => summary.join(", "),
+    };
+    if let Err(e) = write_revision(
+        &pack.name,
+        Revision {
+            summary,
+            pack: saved,
+        },
+    ) {
+        eprintln!("Unable to keep a revision of {}: {e}", pack.name);
+    }
+}
+
+fn write_revision(name: &str, revision: Revision) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let folder = revisions_folder(name)?;
+    fs::create_dir_all(&folder)?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    fs::write(
+        folder.join(format!("{stamp}.json")),
+        sonic_rs::to_string_pretty(&revision)?,
+    )?;
+    prune(&folder)?;
+    Ok(())
+}
+
+/// Revisions follow a pack when it's renamed
+pub fn rename_revisions(from: &str, to: &str) -> std::io::Result<()> {
+    let (old, new) = (revisions_folder(from)?, revisions_folder(to)?);
+    if !old.exists() || new.exists() {
+        return Ok(());
+    }
+    fs::rename(old, new)
+}
+
+/// Puts a pack back the way it was in a revision, the current state becomes a revision itself
+fn restore(name: &str, path: &Path) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let revision = read_revision(path)?;
+    let mut packs = MOD_PACKS.try_lock().map_err(|e| e.to_string())?;
+    let current = packs
+        .get(name)
+        .cloned()
+        .ok_or(format!("There's no pack called {name}"))?;
+    let mut pack = revision.pack;
+    pack.name = name.to_owned();
+    pack.inherited = vec![];
+    // a parent that's gone since leaves only what the layer itself adds
+    let orphaned = !pack.parent.is_empty() && !packs.contains_key(&pack.parent);
+    packs.insert(name.to_owned(), pack);
+    rebase(&mut packs, name);
+    let pack = packs.get_mut(name).ok_or("The pack is gone")?;
+    if orphaned {
+        pack.parent = String::new();
+        pack.inherited = vec![];
+    }
+    save_modpack_sync(pack.clone())?;
+
+    let mut changes = vec![];
+    if current.parent != pack.parent {
+        changes.push(Change::PackParent {
+            pack: name.to_owned(),
+            before: current.parent,
+            after: pack.parent.clone(),
+        });
+    }
+    changes.push(Change::Order {
+        pack: name.to_owned(),
+        before: current.mods,
+        after: pack.mods.clone(),
+    });
+    let time = path
+        .file_stem()
+        .and_then(|s| s.to_str()?.parse::<i64>().ok())
+        .map(stamp_time)
+        .unwrap_or_default();
+    record(format!("Restore {name} from {time}"), changes);
+    Ok(())
+}
+
+/// The list a revision had, for looking at before restoring it
+fn preview(name: &str, path: &Path) -> Vec<SharedString> {
+    let revision = match read_revision(path) {
+        Ok(revision) => revision,
+        Err(e) => return vec![format!("Unable to read the revision: {e}").into()],
+    };
+    let inherited = match MOD_PACKS.try_lock() {
+        Ok(mut packs) => {
+            rebase(&mut packs, &revision.pack.parent);
+            packs
+                .get(&revision.pack.parent)
+                .map(|p| p.mods.clone())
+                .unwrap_or_default()
+        }
+        Err(_) => vec![],
+    };
+    let mut lines: Vec<SharedString> = disk_mods(&revision.pack, &inherited)
+        .iter()
+        .enumerate()
+        .map(|(idx, m)| match m.separator {
+            true => format!("── {}", m.name).into(),
+            false => format!(
+                "#{} {} {}",
+                idx + 1,
+                if m.enabled { "on " } else { "off" },
+                m.name
+            )
+            .into(),
+        })
+        .collect();
+    if !revision.pack.parent.is_empty() {
+        lines.insert(0, format!("Layered on {}", revision.pack.parent).into());
+    }
+    if revision.pack.name != name {
+        lines.insert(0, format!("Saved as {}", revision.pack.name).into());
+    }
+    lines
+}
+
+fn fill_window(window: &PackRevisionsWindow) {
+    let rows: Vec<PackRevisionRow> = list_revisions(&window.get_pack_name())
+        .iter()
+        .map(|(stamp, path)| PackRevisionRow {
+            time: stamp_time(*stamp).into(),
+            summary: match read_revision(path) {
+                Ok(revision) => revision.summary.into(),
+                Err(e) => e.to_string().into(),
+            },
+        })
+        .collect();
+    window.set_rows(ModelRc::new(VecModel::from(rows)));
+    window.set_selected(-1);
+    window.set_preview(ModelRc::default());
+}
+
+fn revision_at(window: &PackRevisionsWindow, idx: i32) -> Option<PathBuf> {
+    list_revisions(&window.get_pack_name())
+        .get(idx as usize)
+        .map(|r| r.1.clone())
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<ModpackLogic>().on_open_revisions(move |pack| {
+        let ui = weak.unwrap();
+        let window = match PackRevisionsWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        window.set_pack_name(pack);
+        fill_window(&window);
+
+        let window_weak = window.as_weak();
+        window.on_select(move |idx| {
+            let window = window_weak.unwrap();
+            if let Some(path) = revision_at(&window, idx) {
+                let lines = preview(&window.get_pack_name(), &path);
+                window.set_preview(ModelRc::new(VecModel::from(lines)));
+                window.set_selected(idx);
+            }
+        });
+
+        let main_weak = weak.clone();
+        let window_weak = window.as_weak();
+        window.on_restore(move |idx| {
+            let window = window_weak.unwrap();
+            let name = window.get_pack_name().to_string();
+            let Some(path) = revision_at(&window, idx) else {
+                return;
+            };
+            match restore(&name, &path) {
+                Ok(_) => {
+                    let ui = main_weak.unwrap();
+                    let applied = match R4D_CFG.try_lock() {
+                        Ok(cfg) if !cfg.applied_pack.is_empty() => cfg.applied_pack.clone().into(),
+                        _ => ui.get_active_pack(),
+                    };
+                    ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+                    fill_window(&window);
+                }
+                Err(e) => open_error_window(format!("Unable to restore {name}: \n{e}")),
+            }
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let window_weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = window_weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_keeps_the_newest() {
+        let dir = std::env::temp_dir().join(format!("rust4diva-revisions-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let extra = 5;
+        for stamp in 1..=(MAX_REVISIONS + extra) {
+            fs::write(dir.join(format!("{stamp}.json")), "{}").unwrap();
+        }
+        // only stamped files are revisions
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        prune(&dir).unwrap();
+        let left = revisions_in(&dir);
+        assert_eq!(left.len(), MAX_REVISIONS);
+        assert_eq!(left[0].0, (MAX_REVISIONS + extra) as i64);
+        assert_eq!(left[MAX_REVISIONS - 1].0, extra as i64 + 1);
+        assert!(!dir.join(format!("{extra}.json")).exists());
+        assert!(dir.join("notes.txt").exists());
+
+        // nothing to drop under the limit
+        prune(&dir).unwrap();
+        assert_eq!(revisions_in(&dir).len(), MAX_REVISIONS);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
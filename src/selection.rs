@@ -0,0 +1,382 @@
+use std::cmp::{max, min};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+use slint::{ComponentHandle, Model, ModelRc, SharedString, Weak};
+use toml_edit::value;
+
+use crate::diva::open_error_window;
+use crate::groups::{current_order, edit_current_order, view_indices};
+use crate::history::{order_key, record, trash_mod, Change};
+use crate::metadata::save_metadata;
+use crate::modmanagement::{get_mods, load_mods, save_mod_config, set_mods_table};
+use crate::modpacks::ModPackMod;
+use crate::slint_generatedApp::App;
+use crate::tags::parse_tags;
+use crate::{ConfirmDelete, DivaModElement, ModLogic, ModpackLogic, MODS, MOD_META};
+
+/// Table ids used by the ui, the installed mods list and the load order
+pub const INSTALLED: usize = 0;
+pub const PACK: usize = 1;
+
+#[derive(Default)]
+struct Selection {
+    dirs: Vec<String>,
+    /// last row clicked, shift+click selects from here
+    anchor: Option<usize>,
+}
+
+static SELECTION: LazyLock<Mutex<[Selection; 2]>> =
+    LazyLock::new(|| Mutex::new([Selection::default(), Selection::default()]));
+
+fn selectable(element: &DivaModElement) -> bool {
+    !element.separator && !element.dirname.is_empty()
+}
+
+fn table_model(ui: &App, table: usize) -> ModelRc<DivaModElement> {
+    match table {
+        INSTALLED => ui.get_mods(),
+        _ => ui.get_pack_mods(),
+    }
+}
+
+fn set_count(ui: &App, table: usize, count: usize) {
+    match table {
+        INSTALLED => ui.global::<ModLogic>().set_selected_installed(count as i32),
+        _ => ui.global::<ModLogic>().set_selected_pack(count as i32),
+    }
+}
+
+pub fn selected_dirs(table: usize) -> Vec<String> {
+    match SELECTION.lock() {
+        Ok(sel) => sel[table].dirs.clone(),
+        Err(_) => vec![],
+    }
+}
+
+/// Everything selected in either table
+fn all_selected() -> Vec<String> {
+    let mut dirs = selected_dirs(INSTALLED);
+    for dir in selected_dirs(PACK) {
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Marks the selected rows of a freshly built table, anything that's no longer shown drops out
+/// of the selection
+pub fn mark_selected(ui: &App, table: usize, elements: &mut [DivaModElement]) {
+    let Ok(mut sel) = SELECTION.lock() else {
+        return;
+    };
+    let sel = &mut sel[table];
+    sel.dirs
+        .retain(|d| elements.iter().any(|e| e.dirname.as_str() == d));
+    sel.anchor = None;
+    for element in elements.iter_mut() {
+        element.selected = selectable(element) && sel.dirs.contains(&element.dirname.to_string());
+    }
+    set_count(ui, table, sel.dirs.len());
+}
+
+/// Pushes the selection into the rows that are already shown
+fn refresh(ui: &App, table: usize) {
+    let dirs = selected_dirs(table);
+    let model = table_model(ui, table);
+    for row in 0..model.row_count() {
+        if let Some(mut element) = model.row_data(row) {
+            let want = selectable(&element) && dirs.contains(&element.dirname.to_string());
+            if element.selected != want {
+                element.selected = want;
+                model.set_row_data(row, element);
+            }
+        }
+    }
+    set_count(ui, table, dirs.len());
+}
+
+fn clear(ui: &App, table: usize) {
+    if let Ok(mut sel) = SELECTION.lock() {
+        sel[table] = Selection::default();
+    }
+    refresh(ui, table);
+}
+
+fn select_row(ui: &App, table: usize, row: usize, range: bool) {
+    let model = table_model(ui, table);
+    {
+        let Ok(mut sel) = SELECTION.lock() else {
+            return;
+        };
+        let sel = &mut sel[table];
+        match (range, sel.anchor) {
+            (true, Some(anchor)) => {
+                for r in min(anchor, row)..=max(anchor, row) {
+                    if let Some(element) = model.row_data(r) {
+                        let dir = element.dirname.to_string();
+                        if selectable(&element) && !sel.dirs.contains(&dir) {
+                            sel.dirs.push(dir);
+                        }
+                    }
+                }
+            }
+            _ => {
+                if let Some(element) = model.row_data(row) {
+                    let dir = element.dirname.to_string();
+                    if selectable(&element) {
+                        match sel.dirs.iter().position(|d| *d == dir) {
+                            Some(idx) => {
+                                sel.dirs.remove(idx);
+                            }
+                            None => sel.dirs.push(dir),
+                        }
+                    }
+                }
+            }
+        }
+        sel.anchor = Some(row);
+    }
+    refresh(ui, table);
+}
+
+fn is_selected(entry: &ModPackMod, dirs: &[String]) -> bool {
+    entry.dir_name().is_some_and(|d| dirs.contains(&d))
+}
+
+/// Pulls the selected entries out and puts them back as one block at table row `row`
+fn move_selected(list: &mut Vec<ModPackMod>, dirs: &[String], row: usize) {
+    let view = view_indices(list);
+    let target = match view.get(row) {
+        Some(idx) => *idx,
+        None => list.len(),
+    };
+    let at = list[..target]
+        .iter()
+        .filter(|m| !is_selected(m, dirs))
+        .count();
+    let block: Vec<ModPackMod> = list
+        .iter()
+        .filter(|m| is_selected(m, dirs))
+        .cloned()
+        .collect();
+    list.retain(|m| !is_selected(m, dirs));
+    list.splice(at..at, block);
+}
+
+/// Saves the load order once for the whole batch then reloads the tables
+fn edit_order_and_reload<F: FnOnce(&mut Vec<ModPackMod>)>(ui: &App, label: Option<&str>, edit: F) {
+    match edit_current_order(label, edit) {
+        Ok(applied) => {
+            let _ = set_mods_table(&get_mods(), ui.as_weak());
+            ui.global::<ModpackLogic>()
+                .invoke_change_modpack(SharedString::from(applied));
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            open_error_window(format!("Unable to update the load order: \n{e}"));
+        }
+    }
+}
+
+fn set_enabled(ui: &App, enabled: bool) {
+    let dirs = all_selected();
+    let mut changes = vec![];
+    if let Ok(mut mods) = MODS.try_lock() {
+        for dir in dirs.iter() {
+            if let Some(m) = mods.get_mut(dir) {
+                // every mod has its own config.toml, only the ones that change get written
+                let before = m.config["enabled"].as_bool().unwrap_or(true);
+                if before == enabled {
+                    continue;
+                }
+                m.config["enabled"] = value(enabled);
+                if let Err(e) = save_mod_config(PathBuf::from(m.path.clone()), &m.config) {
+                    eprintln!("{e}");
+                }
+                changes.push(Change::Enabled {
+                    dir: dir.clone(),
+                    before,
+                    after: enabled,
+                });
+            }
+        }
+    }
+    let label = match enabled {
+        true => format!("Enable {} mods", dirs.len()),
+        false => format!("Disable {} mods", dirs.len()),
+    };
+    // one history entry for the configs and the order together
+    let before = current_order();
+    edit_order_and_reload(ui, None, |list| {
+        for entry in list.iter_mut().filter(|m| is_selected(m, &dirs)) {
+            entry.enabled = enabled;
+        }
+    });
+    changes.push(Change::Order {
+        pack: order_key(&ui.get_active_pack()),
+        before,
+        after: current_order(),
+    });
+    record(label, changes);
+}
+
+fn delete_selected(weak: Weak<App>) {
+    let ui = weak.unwrap();
+    let dirs = all_selected();
+    if dirs.is_empty() {
+        return;
+    }
+    let confirm = ConfirmDelete::new().unwrap();
+    confirm.invoke_set_color_scheme(ui.get_color_scheme());
+    confirm.set_item(format!("{} mods", dirs.len()).into());
+    let cweak = confirm.as_weak();
+    confirm.on_close(move || {
+        cweak.unwrap().hide().unwrap();
+    });
+    let cweak = confirm.as_weak();
+    confirm.on_confirm(move || {
+        cweak.unwrap().hide().unwrap();
+        let ui = weak.unwrap();
+        clear(&ui, INSTALLED);
+        clear(&ui, PACK);
+        let (dirs, weak) = (dirs.clone(), weak.clone());
+        tokio::task::spawn_blocking(move || {
+            // into the trash so it can be undone
+            let mut changes = vec![];
+            for dir in dirs.iter() {
+                match trash_mod(dir) {
+                    Ok(change) => changes.extend(change),
+                    Err(e) => open_error_window(format!("Unable to delete {dir}: \n{e}")),
+                }
+            }
+            record(format!("Delete {} mods", dirs.len()), changes);
+            // one reload for the whole lot
+            if let Err(e) = load_mods() {
+                open_error_window(e.to_string());
+                return;
+            }
+            let _ = set_mods_table(&get_mods(), weak.clone());
+            let _ = weak.upgrade_in_event_loop(|ui| {
+                let applied = ui.get_active_pack();
+                ui.global::<ModpackLogic>().invoke_change_modpack(applied);
+            });
+        });
+    });
+    confirm.show().unwrap();
+}
+
+fn tag_selected(ui: &App, tags: &str) {
+    let tags = parse_tags(tags);
+    if tags.is_empty() {
+        return;
+    }
+    if let Ok(mut meta) = MOD_META.try_lock() {
+        for dir in all_selected() {
+            let entry = meta.entry(dir).or_default();
+            for tag in tags.iter() {
+                if !entry.tags.contains(tag) {
+                    entry.tags.push(tag.clone());
+                }
+            }
+        }
+    }
+    if let Err(e) = save_metadata() {
+        open_error_window(format!("Unable to save tags: {e}"));
+    }
+    let _ = set_mods_table(&get_mods(), ui.as_weak());
+}
+
+fn in_real_pack(ui: &App) -> bool {
+    let pack = ui.get_active_pack();
+    !pack.is_empty() && pack != "All Mods"
+}
+
+pub async fn init(ui: &App) {
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>()
+        .on_select_row(move |table, row, range| {
+            if table < 0 || row < 0 {
+                return;
+            }
+            select_row(&weak.unwrap(), table as usize, row as usize, range);
+        });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_clear_selection(move |table| {
+        if table >= 0 {
+            clear(&weak.unwrap(), table as usize);
+        }
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_bulk_enable(move |enabled| {
+        set_enabled(&weak.unwrap(), enabled);
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_bulk_delete(move || {
+        delete_selected(weak.clone());
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_bulk_tag(move |tags| {
+        tag_selected(&weak.unwrap(), &tags);
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_bulk_add_to_pack(move || {
+        let ui = weak.unwrap();
+        if !in_real_pack(&ui) {
+            return;
+        }
+        let dirs = selected_dirs(INSTALLED);
+        let to_add: Vec<ModPackMod> = match MODS.try_lock() {
+            Ok(mods) => dirs
+                .iter()
+                .filter_map(|d| mods.get(d))
+                .map(|m| m.clone().into())
+                .collect(),
+            Err(_) => return,
+        };
+        clear(&ui, INSTALLED);
+        let label = format!("Add {} mods to {}", to_add.len(), ui.get_active_pack());
+        edit_order_and_reload(&ui, Some(&label), |list| {
+            for entry in to_add {
+                if !list.contains(&entry) {
+                    list.push(entry);
+                }
+            }
+        });
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_bulk_remove_from_pack(move || {
+        let ui = weak.unwrap();
+        if !in_real_pack(&ui) {
+            return;
+        }
+        let dirs = selected_dirs(PACK);
+        clear(&ui, PACK);
+        let label = format!("Remove {} mods from {}", dirs.len(), ui.get_active_pack());
+        edit_order_and_reload(&ui, Some(&label), |list| {
+            list.retain(|m| !is_selected(m, &dirs));
+        });
+    });
+
+    let weak = ui.as_weak();
+    ui.global::<ModLogic>().on_bulk_move(move |position| {
+        let ui = weak.unwrap();
+        let row = match position.trim().parse::<usize>() {
+            Ok(pos) if pos > 0 => pos - 1,
+            _ => {
+                open_error_window(format!("\"{position}\" isn't a position in the list"));
+                return;
+            }
+        };
+        let dirs = selected_dirs(PACK);
+        let label = format!("Move {} mods to {}", dirs.len(), row + 1);
+        edit_order_and_reload(&ui, Some(&label), |list| move_selected(list, &dirs, row));
+    });
+}
@@ -0,0 +1,250 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, ModelRc, VecModel, Weak};
+use tokio::sync::broadcast;
+
+use crate::diva::{get_temp_folder, open_error_window};
+use crate::divamodarchive::PostType;
+use crate::metadata::{get_meta, get_preview_cache_folder, Provider};
+use crate::modmanagement::{get_mods, DivaMod};
+use crate::slint_generatedApp::App;
+use crate::util::{dir_files, dir_usage, format_size};
+use crate::{StorageReportWindow, StorageRow, WindowLogic, MOD_PACKS, R4D_CFG};
+
+const CATEGORIES: [PostType; 6] = [
+    PostType::Song,
+    PostType::Module,
+    PostType::Cover,
+    PostType::Ui,
+    PostType::Plugin,
+    PostType::Other,
+];
+
+/// DMA tells us the post type, anything else we have to guess from what's in the folder
+pub fn mod_category(module: &DivaMod) -> PostType {
+    let dir = module.dir_name().unwrap_or_default();
+    if let Some(source) = get_meta(&dir).source {
+        if source.provider == Provider::DivaModArchive {
+            if let Some(t) = CATEGORIES.iter().find(|t| t.to_string() == source.category) {
+                return t.clone();
+            }
+        }
+    }
+    let has_dll = module
+        .config
+        .get("dll")
+        .and_then(|d| d.as_array())
+        .is_some_and(|d| !d.is_empty());
+    if has_dll {
+        return PostType::Plugin;
+    }
+    let mut mod_dir = PathBuf::from(module.path.clone());
+    mod_dir.pop();
+    guess_category(&mod_dir)
+}
+
+fn guess_category(mod_dir: &Path) -> PostType {
+    let files: Vec<String> = dir_files(mod_dir)
+        .into_iter()
+        .map(|(rel, _)| rel.to_string_lossy().replace('\\', "/").to_lowercase())
+        .collect();
+    let has = |f: &dyn Fn(&String) -> bool| files.iter().any(f);
+    if has(&|f| f.contains("pv_db") || f.ends_with(".ogg")) {
+        PostType::Song
+    } else if has(&|f| f.contains("module_tbl") || f.contains("customize_item")) {
+        PostType::Module
+    } else if has(&|f| f.contains("rom/2d/") || f.contains("aet_") || f.contains("spr_")) {
+        PostType::Ui
+    } else {
+        PostType::Other
+    }
+}
+
+/// Mods that aren't enabled in the global priority or any pack, these are the easy ones to prune
+fn unused_mods(mods: &[DivaMod]) -> Vec<DivaMod> {
+    let mut enabled: Vec<String> = vec![];
+    if let Ok(packs) = MOD_PACKS.try_lock() {
+        for pack in packs.values() {
+            enabled.extend(
+                pack.mods
+                    .iter()
+                    .filter(|m| m.enabled)
+                    .filter_map(|m| m.dir_name()),
+            );
+        }
+    }
+    if let Ok(cfg) = R4D_CFG.try_lock() {
+        enabled.extend(
+            cfg.priority
+                .iter()
+                .filter(|m| m.enabled)
+                .filter_map(|m| m.dir_name()),
+        );
+    }
+    mods.iter()
+        .filter(|m| !enabled.contains(&m.dir_name().unwrap_or_default()))
+        .cloned()
+        .collect()
+}
+
+fn row(label: impl Into<String>, size: u64, detail: impl Into<String>) -> StorageRow {
+    StorageRow {
+        label: label.into().into(),
+        size: format_size(size).into(),
+        detail: detail.into().into(),
+    }
+}
+
+/// Plain data for the report window, `ModelRc` can't leave the ui thread
+struct Report {
+    total: u64,
+    mod_count: usize,
+    categories: Vec<(String, u64, usize)>,
+    unused_total: u64,
+    unused: Vec<DivaMod>,
+    cache: Vec<(String, u64, String)>,
+}
+
+fn build_report() -> Report {
+    let mods = get_mods();
+    let mut categories: HashMap<String, (u64, usize)> = HashMap::new();
+    for module in mods.iter() {
+        let entry = categories
+            .entry(mod_category(module).to_string())
+            .or_default();
+        entry.0 += module.size;
+        entry.1 += 1;
+    }
+    let mut categories: Vec<(String, u64, usize)> = categories
+        .into_iter()
+        .map(|(name, (size, count))| (name, size, count))
+        .collect();
+    categories.sort_by_key(|c| Reverse(c.1));
+
+    let mut unused = unused_mods(&mods);
+    unused.sort_by_key(|m| Reverse(m.size));
+
+    let mut cache = vec![];
+    if let Some(temp) = get_temp_folder() {
+        let (size, files) = dir_usage(Path::new(&temp));
+        cache.push((
+            "Temp folder".to_string(),
+            size,
+            format!("{files} files in {temp}"),
+        ));
+    }
+    if let Ok(previews) = get_preview_cache_folder() {
+        let (size, files) = dir_usage(&previews);
+        cache.push((
+            "Preview cache".to_string(),
+            size,
+            format!("{files} files in {}", previews.display()),
+        ));
+    }
+
+    Report {
+        total: mods.iter().map(|m| m.size).sum(),
+        mod_count: mods.len(),
+        categories,
+        unused_total: unused.iter().map(|m| m.size).sum(),
+        unused,
+        cache,
+    }
+}
+
+fn show_report(window: Weak<StorageReportWindow>) {
+    tokio::spawn(async move {
+        let report = build_report();
+        let _ = window.upgrade_in_event_loop(move |window| {
+            window.set_total(
+                format!(
+                    "{} across {} mods",
+                    format_size(report.total),
+                    report.mod_count
+                )
+                .into(),
+            );
+            let categories: Vec<StorageRow> = report
+                .categories
+                .into_iter()
+                .map(|(name, size, count)| row(name, size, format!("{count} mods")))
+                .collect();
+            window.set_categories(ModelRc::new(VecModel::from(categories)));
+            window.set_unused_total(
+                format!(
+                    "{} in {} mods",
+                    format_size(report.unused_total),
+                    report.unused.len()
+                )
+                .into(),
+            );
+            let unused: Vec<StorageRow> = report
+                .unused
+                .iter()
+                .map(|m| {
+                    row(
+                        m.config["name"].as_str().unwrap_or(""),
+                        m.size,
+                        m.dir_name().unwrap_or_default(),
+                    )
+                })
+                .collect();
+            window.set_unused(ModelRc::new(VecModel::from(unused)));
+            let cache: Vec<StorageRow> = report
+                .cache
+                .into_iter()
+                .map(|(name, size, detail)| row(name, size, detail))
+                .collect();
+            window.set_cache(ModelRc::new(VecModel::from(cache)));
+            window.set_loading(false);
+        });
+    });
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_storage_report(move || {
+        let ui = weak.unwrap();
+        let window = match StorageReportWindow::new() {
+            Ok(window) => window,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        window.invoke_set_color_scheme(ui.get_color_scheme());
+        window.set_loading(true);
+        show_report(window.as_weak());
+
+        let window_weak = window.as_weak();
+        window.on_refresh(move || {
+            window_weak.unwrap().set_loading(true);
+            show_report(window_weak.clone());
+        });
+
+        let window_weak = window.as_weak();
+        window.on_close(move || {
+            window_weak.unwrap().hide().unwrap();
+        });
+
+        let weak = window.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        window.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        window.show().unwrap();
+    });
+}
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use slint::private_unstable_api::re_exports::ColorScheme;
+use slint::{ComponentHandle, Model, ModelRc, SharedString, VecModel};
+use tokio::sync::broadcast;
+
+use crate::diva::open_error_window;
+use crate::metadata::{get_meta, update_meta};
+use crate::modmanagement::{get_mods, set_mods_table, DivaMod};
+use crate::slint_generatedApp::App;
+use crate::storage::mod_category;
+use crate::{EditTagsDialog, ModLogic, WindowLogic, MODS, MOD_META, MOD_PACKS};
+
+/// What gets offered in the tag editor even if no mod uses it yet
+pub const DEFAULT_TAGS: [&str; 7] = [
+    "song pack",
+    "module",
+    "ui",
+    "plugin",
+    "fix",
+    "cover",
+    "other",
+];
+
+pub fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+/// Comma separated tags from the editor, blanks and repeats dropped
+pub fn parse_tags(s: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in s.split(',').map(normalize_tag) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Turns a DMA post type or GameBanana category ("Songs", "Modules", "UI"...) into our tags
+pub fn tags_for_category(category: &str) -> Vec<String> {
+    let words: Vec<String> = category
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .map(|w| w.to_string())
+        .collect();
+    let has = |names: &[&str]| words.iter().any(|w| names.contains(&w.as_str()));
+    let mut tags = vec![];
+    if has(&["song", "songs", "pv", "pvs", "chart", "charts"]) {
+        tags.push("song pack");
+    }
+    if has(&["module", "modules", "costume", "costumes", "skin", "skins"]) {
+        tags.push("module");
+    }
+    if has(&["ui", "hud", "interface", "menu", "menus"]) {
+        tags.push("ui");
+    }
+    if has(&["plugin", "plugins", "dll"]) {
+        tags.push("plugin");
+    }
+    if has(&["cover", "covers"]) {
+        tags.push("cover");
+    }
+    if has(&["fix", "fixes", "patch", "patches"]) {
+        tags.push("fix");
+    }
+    tags.into_iter().map(String::from).collect()
+}
+
+pub fn get_tags(dir_name: &str) -> Vec<String> {
+    get_meta(dir_name).tags
+}
+
+/// Tags for a mod that's already installed, from where it came from and what's in the folder
+pub fn suggest_tags(module: &DivaMod) -> Vec<String> {
+    let dir = module.dir_name().unwrap_or_default();
+    let mut tags = match get_meta(&dir).source {
+        Some(source) => tags_for_category(&source.category),
+        None => vec![],
+    };
+    for tag in tags_for_category(&mod_category(module).to_string()) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Authors are free text, split up the usual "a, b & c" lists so each person can be picked
+pub fn split_authors(author: &str) -> Vec<String> {
+    author
+        .split([',', '&', '/'])
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+pub fn mod_author(module: &DivaMod) -> String {
+    module
+        .config
+        .get("author")
+        .and_then(|a| a.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// How many packs each mod (by dir name) shows up in
+pub fn pack_counts() -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    if let Ok(packs) = MOD_PACKS.try_lock() {
+        for pack in packs.values() {
+            for m in pack.mods.iter() {
+                if let Some(dir) = m.dir_name() {
+                    *counts.entry(dir).or_default() += 1;
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// The filter dropdowns above the installed mods list, index 0 of each list means "any"
+pub struct ModFilter {
+    tag: Option<String>,
+    author: Option<String>,
+    enabled: Option<bool>,
+    pack: Option<Vec<String>>,
+}
+
+fn selected(list: ModelRc<SharedString>, idx: i32) -> Option<String> {
+    if idx <= 0 {
+        return None;
+    }
+    list.row_data(idx as usize).map(|s| s.to_string())
+}
+
+impl ModFilter {
+    pub fn from_ui(logic: &ModLogic) -> Self {
+        let pack =
+            selected(logic.get_filter_packs(), logic.get_filter_pack()).map(|name| match MOD_PACKS
+                .try_lock()
+            {
+                Ok(packs) => match packs.get(&name) {
+                    Some(pack) => pack.mods.iter().filter_map(|m| m.dir_name()).collect(),
+                    None => vec![],
+                },
+                Err(_) => vec![],
+            });
+        Self {
+            tag: selected(logic.get_filter_tags(), logic.get_filter_tag()),
+            author: selected(logic.get_filter_authors(), logic.get_filter_author()),
+            enabled: match logic.get_filter_enabled() {
+                1 => Some(true),
+                2 => Some(false),
+                _ => None,
+            },
+            pack,
+        }
+    }
+
+    pub fn matches(&self, module: &DivaMod) -> bool {
+        let dir = module.dir_name().unwrap_or_default();
+        if let Some(tag) = &self.tag {
+            if !get_tags(&dir).contains(tag) {
+                return false;
+            }
+        }
+        if let Some(author) = &self.author {
+            if !split_authors(&mod_author(module)).contains(author) {
+                return false;
+            }
+        }
+        if let Some(enabled) = self.enabled {
+            if module.config["enabled"].as_bool().unwrap_or(true) != enabled {
+                return false;
+            }
+        }
+        if let Some(pack) = &self.pack {
+            if !pack.contains(&dir) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Swaps in a new option list, keeping whatever was selected if it's still there
+fn refresh_options(
+    list: ModelRc<SharedString>,
+    idx: i32,
+    any: &str,
+    options: Vec<String>,
+) -> (ModelRc<SharedString>, i32) {
+    let current = selected(list, idx);
+    let mut model: Vec<SharedString> = vec![any.into()];
+    model.extend(options.into_iter().map(SharedString::from));
+    let idx = match current {
+        Some(current) => model
+            .iter()
+            .position(|o| o.as_str() == current)
+            .unwrap_or(0) as i32,
+        None => 0,
+    };
+    (ModelRc::new(VecModel::from(model)), idx)
+}
+
+/// Fills the tag, author and pack dropdowns from every installed mod
+pub fn set_filter_options(ui: &App) {
+    let mods: Vec<DivaMod> = match MODS.try_lock() {
+        Ok(mods) => mods.values().cloned().collect(),
+        Err(_) => return,
+    };
+    let mut tags: Vec<String> = vec![];
+    let mut authors: Vec<String> = vec![];
+    for module in mods.iter() {
+        for tag in get_tags(&module.dir_name().unwrap_or_default()) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        for author in split_authors(&mod_author(module)) {
+            if !authors.contains(&author) {
+                authors.push(author);
+            }
+        }
+    }
+    tags.sort();
+    authors.sort_by_key(|a| a.to_lowercase());
+    let mut packs: Vec<String> = match MOD_PACKS.try_lock() {
+        Ok(packs) => packs.keys().cloned().collect(),
+        Err(_) => vec![],
+    };
+    packs.sort_by_key(|p| p.to_lowercase());
+
+    let logic = ui.global::<ModLogic>();
+    let (model, idx) = refresh_options(
+        logic.get_filter_tags(),
+        logic.get_filter_tag(),
+        "Any tag",
+        tags,
+    );
+    logic.set_filter_tags(model);
+    logic.set_filter_tag(idx);
+    let (model, idx) = refresh_options(
+        logic.get_filter_authors(),
+        logic.get_filter_author(),
+        "Any author",
+        authors,
+    );
+    logic.set_filter_authors(model);
+    logic.set_filter_author(idx);
+    let (model, idx) = refresh_options(
+        logic.get_filter_packs(),
+        logic.get_filter_pack(),
+        "Any pack",
+        packs,
+    );
+    logic.set_filter_packs(model);
+    logic.set_filter_pack(idx);
+}
+
+pub async fn init(ui: &App, dark_rx: broadcast::Receiver<ColorScheme>) {
+    let weak = ui.as_weak();
+    ui.global::<WindowLogic>().on_open_edit_tags(move |module| {
+        let ui = weak.unwrap();
+        let dir = module.dirname.to_string();
+        let found = match MODS.try_lock() {
+            Ok(mods) => mods.get(&dir).cloned(),
+            Err(_) => None,
+        };
+        let Some(found) = found else {
+            return;
+        };
+        let dialog = match EditTagsDialog::new() {
+            Ok(dialog) => dialog,
+            Err(e) => {
+                open_error_window(e.to_string());
+                return;
+            }
+        };
+        dialog.invoke_set_color_scheme(ui.get_color_scheme());
+        dialog.set_name(module.name.clone());
+        dialog.set_tags(get_tags(&dir).join(", ").into());
+
+        // suggestions for this mod first, then the usual ones, then anything else already in use
+        let mut suggestions = suggest_tags(&found);
+        let mut known: Vec<String> = DEFAULT_TAGS.iter().map(|t| t.to_string()).collect();
+        if let Ok(meta) = MOD_META.try_lock() {
+            for m in meta.values() {
+                known.extend(m.tags.iter().cloned());
+            }
+        }
+        for tag in known {
+            if !suggestions.contains(&tag) {
+                suggestions.push(tag);
+            }
+        }
+        let suggestions: Vec<SharedString> = suggestions.into_iter().map(Into::into).collect();
+        dialog.set_suggestions(ModelRc::new(VecModel::from(suggestions)));
+
+        dialog.on_add_tag(|tags, tag| {
+            let mut list = parse_tags(&tags);
+            let tag = normalize_tag(&tag);
+            if !list.contains(&tag) {
+                list.push(tag);
+            }
+            list.join(", ").into()
+        });
+
+        let dialog_weak = dialog.as_weak();
+        dialog.on_cancel(move || {
+            dialog_weak.unwrap().hide().unwrap();
+        });
+
+        let dialog_weak = dialog.as_weak();
+        let main_weak = weak.clone();
+        dialog.on_save(move || {
+            let dialog = dialog_weak.unwrap();
+            let tags = parse_tags(&dialog.get_tags());
+            if let Err(e) = update_meta(&dir, |meta| meta.tags = tags) {
+                open_error_window(format!("Unable to save tags: {e}"));
+                return;
+            }
+            let _ = set_mods_table(&get_mods(), main_weak.clone());
+            let ui = main_weak.unwrap();
+            if ui.get_mod_details().dirname == module.dirname {
+                ui.global::<ModLogic>().invoke_select_mod(module.clone());
+            }
+            dialog.hide().unwrap();
+        });
+
+        let dialog_weak = dialog.as_weak();
+        let mut scheme_rx = dark_rx.resubscribe();
+        let scheme_task = tokio::spawn(async move {
+            while let Ok(scheme) = scheme_rx.recv().await {
+                let _ = dialog_weak.upgrade_in_event_loop(move |ui| {
+                    ui.invoke_set_color_scheme(scheme);
+                });
+            }
+        });
+
+        dialog.window().on_close_requested(move || {
+            scheme_task.abort();
+            slint::CloseRequestResponse::HideWindow
+        });
+
+        dialog.show().unwrap();
+    });
+}
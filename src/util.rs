@@ -1,3 +1,10 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::SystemTime;
+
+use filenamify::filenamify;
 use reqwest::{Client, ClientBuilder};
 
 pub fn reqwest_client() -> Client {
@@ -10,3 +17,134 @@ pub fn reqwest_client() -> Client {
         .build()
         .expect("Something went horrible wrong when constructing our reqwest client")
 }
+
+/// Every file under `dir` (relative to `dir`) with its size in bytes, sorted by path. Symlinks
+/// are followed so mods linked in from the library count what they point at
+pub fn dir_files(dir: &Path) -> Vec<(PathBuf, u64)> {
+    let mut files = vec![];
+    let mut seen = HashSet::new();
+    let mut stack = vec![PathBuf::new()];
+    while let Some(rel) = stack.pop() {
+        let path = dir.join(&rel);
+        // a link back up the tree would go round forever
+        let Ok(real) = fs::canonicalize(&path) else {
+            continue;
+        };
+        if !seen.insert(real) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&path) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let rel = rel.join(entry.file_name());
+            match fs::metadata(entry.path()) {
+                Ok(meta) if meta.is_dir() => stack.push(rel),
+                Ok(meta) => files.push((rel, meta.len())),
+                Err(_) => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Total size in bytes and file count of everything under `dir`
+pub fn dir_usage(dir: &Path) -> (u64, usize) {
+    let files = dir_files(dir);
+    (files.iter().map(|(_, size)| size).sum(), files.len())
+}
+
+/// A folder's stamp with the size and file count `dir_usage` worked out for it
+type Usage = (Vec<SystemTime>, u64, usize);
+
+static USAGE_CACHE: LazyLock<Mutex<HashMap<PathBuf, Usage>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Modification times of `dir` and what's directly in it, links are followed. Installing,
+/// replacing or toggling a mod touches at least one of them
+fn usage_stamp(dir: &Path) -> Option<Vec<SystemTime>> {
+    let mut stamp = vec![fs::metadata(dir).ok()?.modified().ok()?];
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if let Ok(time) = fs::metadata(entry).and_then(|m| m.modified()) {
+            stamp.push(time);
+        }
+    }
+    Some(stamp)
+}
+
+/// `dir_usage` that only walks `dir` again when its stamp changed since the last call
+pub fn cached_dir_usage(dir: &Path) -> (u64, usize) {
+    let Some(stamp) = usage_stamp(dir) else {
+        return dir_usage(dir);
+    };
+    if let Ok(cache) = USAGE_CACHE.lock() {
+        if let Some((_, size, count)) = cache.get(dir).filter(|c| c.0 == stamp) {
+            return (*size, *count);
+        }
+    }
+    let (size, count) = dir_usage(dir);
+    if let Ok(mut cache) = USAGE_CACHE.lock() {
+        cache.insert(dir.to_path_buf(), (stamp, size, count));
+    }
+    (size, count)
+}
+
+/// Whether `name` is a single plain folder name, nothing that climbs out of or reaches past
+/// the folder it gets joined onto
+pub fn is_folder_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && filenamify(name) == name
+}
+
+/// Copies `src` and everything in it to `dest`
+pub fn copy_dir_all(src: &Path, dest: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let to = dest.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            // links into the shared library stay links
+            symlink(&fs::read_link(entry.path())?, &to)?;
+        } else if file_type.is_dir() {
+            copy_dir_all(&entry.path(), &to)?;
+        } else {
+            fs::copy(entry.path(), to)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+pub fn symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(src, dest)
+}
+
+#[cfg(windows)]
+pub fn symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    match src.is_dir() {
+        true => std::os::windows::fs::symlink_dir(src, dest),
+        false => std::os::windows::fs::symlink_file(src, dest),
+    }
+}